@@ -1,7 +1,16 @@
-use crate::fun::{display::DisplayFn, Name};
+use crate::fun::{
+  check::{
+    shared_names::{NameKind, RepeatedTopLevelNameErr},
+    unbound_vars::UnboundVarErr,
+  },
+  display::DisplayFn,
+  transform::desugar_match_defs::DesugarMatchDefErr,
+  Name,
+};
 use std::{
   collections::BTreeMap,
   fmt::{Display, Formatter},
+  ops::Range,
 };
 
 pub const ERR_INDENT_SIZE: usize = 2;
@@ -20,6 +29,10 @@ pub struct DiagnosticsConfig {
   pub redundant_match: Severity,
   pub unreachable_match: Severity,
   pub unused_definition: Severity,
+  pub unused_variable: Severity,
+  pub shadowed_variable: Severity,
+  pub dup_usage: Severity,
+  pub division_by_zero: Severity,
   pub repeated_bind: Severity,
   pub recursion_cycle: Severity,
   pub missing_main: Severity,
@@ -30,6 +43,7 @@ pub struct DiagnosticsConfig {
 pub struct Diagnostic {
   message: String,
   severity: Severity,
+  kind: Option<CompileError>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -46,17 +60,48 @@ pub enum DiagnosticOrigin {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Severity {
+  /// Not reported.
   Allow,
+  /// Reported, but less important than a warning.
+  Info,
+  /// Reported without stopping the compilation.
   Warning,
+  /// Stops the compilation at the end of the pass that found it.
   Error,
 }
 
+/// The cause of a compilation error, for the errors that programs using the compiler
+/// may want to handle themselves.
+///
+/// The spans are the location of the whole definition in its source file, if it has one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompileError {
+  /// A variable used without being bound.
+  /// `similar` is a bound variable with a close name, if there's one.
+  UnboundVar { def_name: Name, var: Name, similar: Option<Name>, span: Option<Range<usize>> },
+  /// A reference to a function that isn't defined.
+  UnboundRef { def_name: Name, ref_name: Name, span: Option<Range<usize>> },
+  /// Pattern matching rules that don't cover the constructors in `missing`.
+  /// `adt` is `None` when the missing case is the default case of a number match.
+  NonExhaustiveMatch { def_name: Name, adt: Option<Name>, missing: Vec<Name>, span: Option<Range<usize>> },
+  /// Two top-level items with the same name.
+  /// `previous` is the kind of the first item and `kind` the kind of the repeated one.
+  DuplicateName { name: Name, previous: NameKind, kind: NameKind },
+  /// A pattern matching rule with a different number of arguments than the first rule.
+  /// `rule` is the position of the rule in its definition, starting from 0.
+  ArityMismatch { def_name: Name, rule: usize, expected: usize, found: usize, span: Option<Range<usize>> },
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum WarningType {
   IrrefutableMatch,
   RedundantMatch,
   UnreachableMatch,
   UnusedDefinition,
+  UnusedVariable,
+  ShadowedVariable,
+  DupUsage,
+  DivisionByZero,
   RepeatedBind,
   RecursionCycle,
   MissingMain,
@@ -83,6 +128,14 @@ impl Diagnostics {
     self.add_diagnostic(err, Severity::Error, DiagnosticOrigin::Inet(def_name));
   }
 
+  /// Adds an error with a known cause, printed with the message of its [`Display`].
+  pub fn add_compile_error(&mut self, kind: CompileError) {
+    self.err_counter += 1;
+    let orig = kind.origin();
+    let diag = Diagnostic { message: kind.to_string(), severity: Severity::Error, kind: Some(kind) };
+    self.diagnostics.entry(orig).or_default().push(diag)
+  }
+
   pub fn add_rule_warning(&mut self, warn: impl std::fmt::Display, warn_type: WarningType, def_name: Name) {
     let severity = self.config.warning_severity(warn_type);
     if severity == Severity::Error {
@@ -100,7 +153,7 @@ impl Diagnostics {
   }
 
   pub fn add_diagnostic(&mut self, msg: impl ToString, severity: Severity, orig: DiagnosticOrigin) {
-    let diag = Diagnostic { message: msg.to_string(), severity };
+    let diag = Diagnostic { message: msg.to_string(), severity, kind: None };
     self.diagnostics.entry(orig).or_default().push(diag)
   }
 
@@ -140,6 +193,11 @@ impl Diagnostics {
     self.has_severity(Severity::Error)
   }
 
+  /// Returns the errors that have a known cause.
+  pub fn compile_errors(&self) -> impl Iterator<Item = &CompileError> {
+    self.diagnostics.values().flatten().filter_map(|diag| diag.kind.as_ref())
+  }
+
   /// Resets the internal counter
   pub fn start_pass(&mut self) {
     self.err_counter = 0;
@@ -208,6 +266,9 @@ impl Diagnostics {
 
 impl Display for Diagnostics {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    if self.has_severity(Severity::Info) {
+      write!(f, "\x1b[4m\x1b[1m\x1b[36mInfo:\x1b[0m\n{}", self.display_with_severity(Severity::Info))?;
+    }
     if self.has_severity(Severity::Warning) {
       write!(f, "\x1b[4m\x1b[1m\x1b[33mWarnings:\x1b[0m\n{}", self.display_with_severity(Severity::Warning))?;
     }
@@ -223,7 +284,7 @@ impl From<String> for Diagnostics {
     Self {
       diagnostics: BTreeMap::from_iter([(
         DiagnosticOrigin::Book,
-        vec![Diagnostic { message: value, severity: Severity::Error }],
+        vec![Diagnostic { message: value, severity: Severity::Error, kind: None }],
       )]),
       ..Default::default()
     }
@@ -240,8 +301,15 @@ impl DiagnosticsConfig {
       repeated_bind: severity,
       recursion_cycle: severity,
       import_shadow: severity,
+      division_by_zero: severity,
       // Should only be changed manually, as a missing main is always a error to hvm
       missing_main: Severity::Error,
+      // Opt-in, since unused variables are common in pattern matching rules
+      unused_variable: Severity::Allow,
+      // Opt-in, since some programs shadow variables on purpose
+      shadowed_variable: Severity::Allow,
+      // Opt-in, since using a variable after duplicating it is still a valid program
+      dup_usage: Severity::Allow,
       verbose,
     }
   }
@@ -249,6 +317,10 @@ impl DiagnosticsConfig {
   pub fn warning_severity(&self, warn: WarningType) -> Severity {
     match warn {
       WarningType::UnusedDefinition => self.unused_definition,
+      WarningType::UnusedVariable => self.unused_variable,
+      WarningType::ShadowedVariable => self.shadowed_variable,
+      WarningType::DupUsage => self.dup_usage,
+      WarningType::DivisionByZero => self.division_by_zero,
       WarningType::RepeatedBind => self.repeated_bind,
       WarningType::RecursionCycle => self.recursion_cycle,
       WarningType::IrrefutableMatch => self.irrefutable_match,
@@ -268,8 +340,108 @@ impl Default for DiagnosticsConfig {
   }
 }
 
+impl Diagnostic {
+  pub fn kind(&self) -> Option<&CompileError> {
+    self.kind.as_ref()
+  }
+
+  pub fn severity(&self) -> Severity {
+    self.severity
+  }
+}
+
 impl Display for Diagnostic {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
     write!(f, "{}", self.message)
   }
 }
+
+impl CompileError {
+  fn origin(&self) -> DiagnosticOrigin {
+    match self {
+      CompileError::UnboundVar { def_name, .. }
+      | CompileError::UnboundRef { def_name, .. }
+      | CompileError::NonExhaustiveMatch { def_name, .. }
+      | CompileError::ArityMismatch { def_name, .. } => {
+        DiagnosticOrigin::Rule(def_name.def_name_from_generated())
+      }
+      CompileError::DuplicateName { .. } => DiagnosticOrigin::Book,
+    }
+  }
+}
+
+impl Display for CompileError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      CompileError::UnboundVar { var, similar, .. } => {
+        UnboundVarErr::Local { var: var.clone(), similar: similar.clone() }.fmt(f)
+      }
+      CompileError::UnboundRef { ref_name, .. } => write!(f, "Reference to undefined function '{ref_name}'"),
+      CompileError::NonExhaustiveMatch { adt: Some(adt), missing, .. } => {
+        DesugarMatchDefErr::AdtNotExhaustive { adt: adt.clone(), ctrs: missing.clone() }.fmt(f)
+      }
+      CompileError::NonExhaustiveMatch { adt: None, .. } => DesugarMatchDefErr::NumMissingDefault.fmt(f),
+      CompileError::DuplicateName { name, previous, kind } => {
+        RepeatedTopLevelNameErr { kind_fst: *previous, kind_snd: *kind, name: name.clone() }.fmt(f)
+      }
+      CompileError::ArityMismatch { rule, expected, found, .. } => write!(
+        f,
+        "Incorrect pattern matching rule arity in rule {}. Expected {} args, found {}.",
+        rule + 1,
+        expected,
+        found
+      ),
+    }
+  }
+}
+
+impl std::error::Error for CompileError {}
+
+#[test]
+fn compile_error_kinds() {
+  use crate::{fun::load_book::do_parse_book_default, CompileOpts};
+  let errors = |code: &str| {
+    let mut book = do_parse_book_default(code, std::path::Path::new("errors")).unwrap();
+    let diags =
+      crate::check_book(&mut book, DiagnosticsConfig::default(), CompileOpts::default()).unwrap_err();
+    // The message of each diagnostic is the display of its cause.
+    for diag in diags.diagnostics.values().flatten() {
+      if let Some(kind) = diag.kind() {
+        assert_eq!(diag.to_string(), kind.to_string());
+      }
+    }
+    diags.compile_errors().cloned().collect::<Vec<_>>()
+  };
+
+  let errs = errors("main = (@y y x)");
+  assert!(matches!(&errs[..], [CompileError::UnboundVar { def_name, var, span: Some(_), .. }]
+    if def_name == "main" && var == "x"));
+  assert_eq!(errs[0].to_string(), "Unbound variable 'x'.");
+
+  let code = "type Res = (Ok x) | (Err e)\nRes/bind val nxt = *\nmain = with Res { (wrap 1) }";
+  let errs = errors(code);
+  assert!(matches!(&errs[..], [CompileError::UnboundRef { ref_name, .. }] if ref_name == "Res/wrap"));
+
+  let errs = errors("type Col = Red | Green | Blue\n(f Col/Red) = 0\nmain = (f Col/Blue)");
+  assert!(matches!(&errs[..], [CompileError::NonExhaustiveMatch { adt: Some(adt), missing, .. }]
+    if adt == "Col" && missing.len() == 2));
+
+  let errs = errors("(f 0) = 0\n(f 1 y) = y\nmain = (f 0)");
+  assert!(matches!(&errs[..], [CompileError::ArityMismatch { rule: 1, expected: 1, found: 2, .. }]));
+  assert_eq!(
+    errs[0].to_string(),
+    "Incorrect pattern matching rule arity in rule 2. Expected 1 args, found 2."
+  );
+
+  // Repeated names are usually caught by the parser, but not in books built from many sources.
+  let mut book = do_parse_book_default("type Foo = A\nmain = *", std::path::Path::new("errors")).unwrap();
+  let mut def = book.defs[&Name::new("main")].clone();
+  def.name = Name::new("Foo/A");
+  book.defs.insert(def.name.clone(), def);
+  let diags = crate::check_book(&mut book, DiagnosticsConfig::default(), CompileOpts::default()).unwrap_err();
+  let errs = diags.compile_errors().cloned().collect::<Vec<_>>();
+  assert!(
+    matches!(&errs[..], [CompileError::DuplicateName { name, previous: NameKind::Ctr, kind: NameKind::Def }]
+    if name == "Foo/A")
+  );
+}
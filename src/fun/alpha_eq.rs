@@ -0,0 +1,190 @@
+use crate::{
+  fun::{Name, Pattern, Term},
+  maybe_grow,
+};
+use itertools::Itertools;
+use std::collections::HashMap;
+
+impl Term {
+  /// Checks if two terms are equal up to a consistent renaming of their bound variables.
+  ///
+  /// Free variables must have exactly the same name.
+  /// Unscoped variables are compared by how they're paired, so `λ$a $a` is equal to `λ$b $b`.
+  pub fn alpha_eq(&self, other: &Term) -> bool {
+    AlphaEq::default().terms(self, other)
+  }
+}
+
+impl Pattern {
+  /// Checks if two patterns have the same shape, ignoring the names of the variables they bind.
+  pub fn alpha_eq(&self, other: &Pattern) -> bool {
+    AlphaEq::default().pats(self, other)
+  }
+}
+
+#[derive(Default)]
+struct AlphaEq {
+  /// The ids of the bound variables of each side, with the innermost binder last.
+  lft_scope: HashMap<Name, Vec<u64>>,
+  rgt_scope: HashMap<Name, Vec<u64>>,
+  bind_count: u64,
+  /// Which unscoped variable of one side corresponds to each unscoped variable of the other.
+  lft_links: HashMap<Name, Name>,
+  rgt_links: HashMap<Name, Name>,
+}
+
+impl AlphaEq {
+  fn terms(&mut self, lft: &Term, rgt: &Term) -> bool {
+    maybe_grow(|| match (lft, rgt) {
+      (Term::Var { nam: lft }, Term::Var { nam: rgt }) => self.vars(lft, rgt),
+      (Term::Link { nam: lft }, Term::Link { nam: rgt }) => self.links(lft, rgt),
+      (
+        Term::Open { typ: lft_typ, var: lft_var, bod: lft_bod },
+        Term::Open { typ: rgt_typ, var: rgt_var, bod: rgt_bod },
+      ) => lft_typ == rgt_typ && self.vars(lft_var, rgt_var) && self.terms(lft_bod, rgt_bod),
+      (Term::Def { def: lft_def, nxt: lft_nxt }, Term::Def { def: rgt_def, nxt: rgt_nxt }) => {
+        lft_def.name == rgt_def.name && lft_def.rules == rgt_def.rules && self.terms(lft_nxt, rgt_nxt)
+      }
+      _ => {
+        if !self.same_node(lft, rgt) {
+          return false;
+        }
+        let lft_children = lft.children_with_binds().collect_vec();
+        let rgt_children = rgt.children_with_binds().collect_vec();
+        if lft_children.len() != rgt_children.len() {
+          return false;
+        }
+        for ((lft, lft_binds), (rgt, rgt_binds)) in lft_children.into_iter().zip(rgt_children) {
+          let lft_binds = lft_binds.collect_vec();
+          let rgt_binds = rgt_binds.collect_vec();
+          if lft_binds.len() != rgt_binds.len() {
+            return false;
+          }
+          for (lft, rgt) in lft_binds.iter().zip(&rgt_binds) {
+            if !self.push(lft.as_ref(), rgt.as_ref()) {
+              return false;
+            }
+          }
+          let eq = self.terms(lft, rgt);
+          for (lft, rgt) in lft_binds.iter().zip(&rgt_binds) {
+            self.pop(lft.as_ref(), rgt.as_ref());
+          }
+          if !eq {
+            return false;
+          }
+        }
+        true
+      }
+    })
+  }
+
+  /// Compares everything but the children of two terms.
+  fn same_node(&mut self, lft: &Term, rgt: &Term) -> bool {
+    match (lft, rgt) {
+      (Term::Lam { tag: lft_tag, pat: lft_pat, .. }, Term::Lam { tag: rgt_tag, pat: rgt_pat, .. }) => {
+        lft_tag == rgt_tag && self.pats(lft_pat, rgt_pat)
+      }
+      (Term::Let { pat: lft, .. }, Term::Let { pat: rgt, .. })
+      | (Term::Ask { pat: lft, .. }, Term::Ask { pat: rgt, .. }) => self.pats(lft, rgt),
+      (Term::With { typ: lft, .. }, Term::With { typ: rgt, .. }) => lft == rgt,
+      (Term::Use { .. }, Term::Use { .. }) => true,
+      (Term::App { tag: lft, .. }, Term::App { tag: rgt, .. }) => lft == rgt,
+      (Term::Fan { fan: lft_fan, tag: lft_tag, .. }, Term::Fan { fan: rgt_fan, tag: rgt_tag, .. }) => {
+        lft_fan == rgt_fan && lft_tag == rgt_tag
+      }
+      (Term::Num { val: lft }, Term::Num { val: rgt }) => lft == rgt,
+      (Term::Nat { val: lft }, Term::Nat { val: rgt }) => lft == rgt,
+      (Term::Str { val: lft }, Term::Str { val: rgt }) => lft == rgt,
+      (Term::List { .. }, Term::List { .. }) => true,
+      (Term::Oper { opr: lft, .. }, Term::Oper { opr: rgt, .. }) => lft == rgt,
+      (Term::Mat { arms: lft, .. }, Term::Mat { arms: rgt, .. })
+      | (Term::Fold { arms: lft, .. }, Term::Fold { arms: rgt, .. }) => {
+        lft.len() == rgt.len() && lft.iter().zip(rgt).all(|(lft, rgt)| lft.0 == rgt.0)
+      }
+      (Term::Swt { .. }, Term::Swt { .. }) => true,
+      (Term::Bend { .. }, Term::Bend { .. }) => true,
+      (Term::Ref { nam: lft }, Term::Ref { nam: rgt }) => lft == rgt,
+      (Term::Era, Term::Era) => true,
+      (Term::Err, Term::Err) => true,
+      _ => false,
+    }
+  }
+
+  fn pats(&mut self, lft: &Pattern, rgt: &Pattern) -> bool {
+    maybe_grow(|| {
+      let same_node = match (lft, rgt) {
+        (Pattern::Var(lft), Pattern::Var(rgt)) => lft.is_some() == rgt.is_some(),
+        (Pattern::Chn(lft), Pattern::Chn(rgt)) => self.links(lft, rgt),
+        (Pattern::Ctr(lft, lft_args), Pattern::Ctr(rgt, rgt_args)) => {
+          lft == rgt && lft_args.len() == rgt_args.len()
+        }
+        (Pattern::Num(lft), Pattern::Num(rgt)) => lft == rgt,
+        (Pattern::Fan(lft_fan, lft_tag, lft_els), Pattern::Fan(rgt_fan, rgt_tag, rgt_els)) => {
+          lft_fan == rgt_fan && lft_tag == rgt_tag && lft_els.len() == rgt_els.len()
+        }
+        (Pattern::Lst(lft), Pattern::Lst(rgt)) => lft.len() == rgt.len(),
+        (Pattern::Str(lft), Pattern::Str(rgt)) => lft == rgt,
+        _ => false,
+      };
+      same_node && lft.children().zip(rgt.children()).all(|(lft, rgt)| self.pats(lft, rgt))
+    })
+  }
+
+  /// Bound variables are equal if they were bound at the same place, free ones if they have the same name.
+  fn vars(&self, lft: &Name, rgt: &Name) -> bool {
+    let lft_id = self.lft_scope.get(lft).and_then(|ids| ids.last());
+    let rgt_id = self.rgt_scope.get(rgt).and_then(|ids| ids.last());
+    match (lft_id, rgt_id) {
+      (Some(lft_id), Some(rgt_id)) => lft_id == rgt_id,
+      (None, None) => lft == rgt,
+      _ => false,
+    }
+  }
+
+  fn links(&mut self, lft: &Name, rgt: &Name) -> bool {
+    let lft_ok = self.lft_links.entry(lft.clone()).or_insert_with(|| rgt.clone()) == rgt;
+    let rgt_ok = self.rgt_links.entry(rgt.clone()).or_insert_with(|| lft.clone()) == lft;
+    lft_ok && rgt_ok
+  }
+
+  fn push(&mut self, lft: Option<&Name>, rgt: Option<&Name>) -> bool {
+    match (lft, rgt) {
+      (Some(lft), Some(rgt)) => {
+        self.lft_scope.entry(lft.clone()).or_default().push(self.bind_count);
+        self.rgt_scope.entry(rgt.clone()).or_default().push(self.bind_count);
+        self.bind_count += 1;
+        true
+      }
+      (None, None) => true,
+      _ => false,
+    }
+  }
+
+  fn pop(&mut self, lft: Option<&Name>, rgt: Option<&Name>) {
+    if let (Some(lft), Some(rgt)) = (lft, rgt) {
+      self.lft_scope.get_mut(lft).unwrap().pop();
+      self.rgt_scope.get_mut(rgt).unwrap().pop();
+    }
+  }
+}
+
+#[test]
+fn alpha_eq() {
+  use crate::fun::load_book::parse_test_term;
+  let alpha_eq = |lft: &str, rgt: &str| parse_test_term(lft).alpha_eq(&parse_test_term(rgt));
+
+  assert!(alpha_eq("λa λb (a b)", "λx λy (x y)"));
+  assert!(!alpha_eq("λa λb (a b)", "λx λy (y x)"));
+  assert!(alpha_eq("λa λa a", "λx λy y"));
+  assert!(!alpha_eq("λa λa a", "λx λy x"));
+  assert!(alpha_eq("λ* x", "λ* x"));
+  assert!(!alpha_eq("λ* x", "λa x"));
+  assert!(!alpha_eq("λa x", "λa y"));
+  assert!(!alpha_eq("λx x", "λy x"));
+  assert!(alpha_eq("let (a, b) = x; (b a)", "let (c, d) = x; (d c)"));
+  assert!(alpha_eq("let {a b} = x; (a b)", "let {b a} = x; (b a)"));
+  assert!(alpha_eq("(λ$a $b λ$b $a)", "(λ$x $y λ$y $x)"));
+  assert!(!alpha_eq("(λ$a $a λ$b $b)", "(λ$x $y λ$y $x)"));
+  assert!(alpha_eq("match x = y { Nat/Zero: x; Nat/Succ: x }", "match z = y { Nat/Zero: z; Nat/Succ: z }"));
+  assert!(alpha_eq("switch x { 0: 1; _: x-1 }", "switch y = x { 0: 1; _: y-1 }"));
+}
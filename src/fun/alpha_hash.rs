@@ -0,0 +1,224 @@
+use crate::{
+  fun::{Definition, Name, Pattern, Rule, Term},
+  maybe_grow,
+};
+use std::{
+  collections::{hash_map::DefaultHasher, HashMap},
+  hash::{Hash, Hasher},
+  mem::discriminant,
+};
+
+impl Term {
+  /// A hash of the term that doesn't depend on the names of its bound variables.
+  ///
+  /// Terms that are [`Term::alpha_eq`] have the same hash, so it can be used to
+  /// look up equal terms. Free variables are hashed by their name.
+  ///
+  /// The hash is the same between runs of the same compiler build,
+  /// but may change between versions.
+  pub fn alpha_hash(&self) -> u64 {
+    let mut hasher = AlphaHasher::default();
+    hasher.term(self);
+    hasher.state.finish()
+  }
+}
+
+impl Rule {
+  /// Like [`Term::alpha_hash`], with the variables bound by the patterns also
+  /// hashed by where they're bound.
+  pub fn alpha_hash(&self) -> u64 {
+    let mut hasher = AlphaHasher::default();
+    hasher.rule(self);
+    hasher.state.finish()
+  }
+}
+
+impl Definition {
+  /// Hashes the name of the definition and the [`Rule::alpha_hash`] of its rules.
+  pub fn alpha_hash(&self) -> u64 {
+    let mut hasher = AlphaHasher::default();
+    hasher.def(self);
+    hasher.state.finish()
+  }
+}
+
+/// Follows the same traversal as `AlphaEq`, numbering the binders in the order they're found.
+#[derive(Default)]
+struct AlphaHasher {
+  state: DefaultHasher,
+  /// The ids of the bound variables, with the innermost binder last.
+  scope: HashMap<Name, Vec<u64>>,
+  bind_count: u64,
+  /// The order in which each unscoped variable was first found.
+  links: HashMap<Name, u64>,
+}
+
+impl AlphaHasher {
+  fn term(&mut self, term: &Term) {
+    maybe_grow(|| {
+      discriminant(term).hash(&mut self.state);
+      match term {
+        Term::Var { nam } => self.var(nam),
+        Term::Link { nam } => self.link(nam),
+        Term::Open { typ, var, bod } => {
+          typ.hash(&mut self.state);
+          self.var(var);
+          self.term(bod);
+        }
+        Term::Def { def, nxt } => {
+          // Local definitions are closed, so they can be hashed on their own.
+          def.alpha_hash().hash(&mut self.state);
+          self.term(nxt);
+        }
+        _ => {
+          self.node(term);
+          for (child, binds) in term.children_with_binds() {
+            let binds = binds.collect::<Vec<_>>();
+            binds.len().hash(&mut self.state);
+            for bind in &binds {
+              self.push(bind.as_ref());
+            }
+            self.term(child);
+            for bind in &binds {
+              self.pop(bind.as_ref());
+            }
+          }
+        }
+      }
+    })
+  }
+
+  /// Hashes everything but the children of a term.
+  fn node(&mut self, term: &Term) {
+    match term {
+      Term::Lam { tag, pat, .. } => {
+        tag.hash(&mut self.state);
+        self.pat(pat);
+      }
+      Term::Let { pat, .. } | Term::Ask { pat, .. } => self.pat(pat),
+      Term::With { typ, .. } => typ.hash(&mut self.state),
+      Term::App { tag, .. } => tag.hash(&mut self.state),
+      Term::Fan { fan, tag, .. } => {
+        fan.hash(&mut self.state);
+        tag.hash(&mut self.state);
+      }
+      Term::Num { val } => val.hash(&mut self.state),
+      Term::Nat { val } => val.hash(&mut self.state),
+      Term::Str { val } => val.hash(&mut self.state),
+      Term::Oper { opr, .. } => opr.hash(&mut self.state),
+      Term::Mat { arms, .. } | Term::Fold { arms, .. } => {
+        for arm in arms {
+          arm.0.hash(&mut self.state);
+        }
+      }
+      Term::Ref { nam } => nam.hash(&mut self.state),
+      _ => (),
+    }
+  }
+
+  fn pat(&mut self, pat: &Pattern) {
+    maybe_grow(|| {
+      discriminant(pat).hash(&mut self.state);
+      match pat {
+        Pattern::Var(nam) => nam.is_some().hash(&mut self.state),
+        Pattern::Chn(nam) => self.link(nam),
+        Pattern::Ctr(nam, args) => {
+          nam.hash(&mut self.state);
+          args.len().hash(&mut self.state);
+        }
+        Pattern::Num(val) => val.hash(&mut self.state),
+        Pattern::Fan(fan, tag, els) => {
+          fan.hash(&mut self.state);
+          tag.hash(&mut self.state);
+          els.len().hash(&mut self.state);
+        }
+        Pattern::Lst(els) => els.len().hash(&mut self.state),
+        Pattern::Str(val) => val.hash(&mut self.state),
+      }
+      for child in pat.children() {
+        self.pat(child);
+      }
+    })
+  }
+
+  fn rule(&mut self, rule: &Rule) {
+    for pat in &rule.pats {
+      self.pat(pat);
+    }
+    let binds = rule.pats.iter().flat_map(Pattern::binds).collect::<Vec<_>>();
+    for bind in &binds {
+      self.push(bind.as_ref());
+    }
+    if let Some(guard) = &rule.guard {
+      self.term(guard);
+    }
+    self.term(&rule.body);
+    for bind in &binds {
+      self.pop(bind.as_ref());
+    }
+  }
+
+  fn def(&mut self, def: &Definition) {
+    def.name.hash(&mut self.state);
+    for rule in &def.rules {
+      rule.alpha_hash().hash(&mut self.state);
+    }
+  }
+
+  /// Bound variables are hashed by the binder they refer to, free ones by their name.
+  fn var(&mut self, nam: &Name) {
+    match self.scope.get(nam).and_then(|ids| ids.last()) {
+      Some(id) => (true, id).hash(&mut self.state),
+      None => (false, nam).hash(&mut self.state),
+    }
+  }
+
+  fn link(&mut self, nam: &Name) {
+    let next = self.links.len() as u64;
+    self.links.entry(nam.clone()).or_insert(next).hash(&mut self.state);
+  }
+
+  fn push(&mut self, bind: Option<&Name>) {
+    if let Some(bind) = bind {
+      self.scope.entry(bind.clone()).or_default().push(self.bind_count);
+      self.bind_count += 1;
+    }
+  }
+
+  fn pop(&mut self, bind: Option<&Name>) {
+    if let Some(bind) = bind {
+      self.scope.get_mut(bind).unwrap().pop();
+    }
+  }
+}
+
+#[test]
+fn alpha_hash() {
+  use crate::fun::{load_book::parse_test_term, parser::TermParser};
+  let same_hash =
+    |lft: &str, rgt: &str| parse_test_term(lft).alpha_hash() == parse_test_term(rgt).alpha_hash();
+
+  assert!(same_hash("λa λb (a b)", "λx λy (x y)"));
+  assert!(!same_hash("λa λb (a b)", "λx λy (y x)"));
+  assert!(same_hash("λa λa a", "λx λy y"));
+  assert!(!same_hash("λa λa a", "λx λy x"));
+  assert!(!same_hash("λ* x", "λa x"));
+  assert!(!same_hash("λa x", "λa y"));
+  assert!(!same_hash("λx x", "λy x"));
+  assert!(same_hash("let (a, b) = x; (b a)", "let (c, d) = x; (d c)"));
+  assert!(same_hash("(λ$a $b λ$b $a)", "(λ$x $y λ$y $x)"));
+  assert!(!same_hash("(λ$a $a λ$b $b)", "(λ$x $y λ$y $x)"));
+  assert!(same_hash("match x = y { Nat/Zero: x; Nat/Succ: x }", "match z = y { Nat/Zero: z; Nat/Succ: z }"));
+  assert!(!same_hash("(+ 1 2)", "(+ 2 1)"));
+  assert!(!same_hash("(+ 1 2)", "(* 1 2)"));
+  assert!(!same_hash("(a (b c))", "((a b) c)"));
+  assert!(!same_hash("1", "+1"));
+
+  let rule = |code: &str| {
+    let book = TermParser::new(code).parse_book(Default::default(), false).unwrap();
+    book.fun_defs[0].rules[0].alpha_hash()
+  };
+  assert_eq!(rule("(f x (y, z)) = (x y z)"), rule("(f a (b, c)) = (a b c)"));
+  assert_ne!(rule("(f x (y, z)) = (x y z)"), rule("(f a (b, c)) = (a c b)"));
+  assert_ne!(rule("(f x) = x"), rule("(f x) = y"));
+}
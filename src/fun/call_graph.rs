@@ -0,0 +1,137 @@
+use crate::{
+  fun::{Book, Name, Term},
+  maybe_grow,
+};
+use std::collections::{HashMap, HashSet};
+
+impl Book {
+  /// Returns the definitions referenced by each definition of the book.
+  ///
+  /// Only resolved references, `Term::Ref`, that point to a function or a
+  /// native hvm definition are included, which also covers constructors once
+  /// their datatypes are encoded. References inside local definitions and
+  /// rule guards count as references of the definition that contains them.
+  pub fn call_graph(&self) -> HashMap<Name, HashSet<Name>> {
+    let mut graph = HashMap::new();
+    for (def_name, def) in &self.defs {
+      let mut refs = HashSet::new();
+      for rule in &def.rules {
+        rule.body.collect_def_refs(&mut refs);
+        if let Some(guard) = &rule.guard {
+          guard.collect_def_refs(&mut refs);
+        }
+      }
+      refs.retain(|nam: &Name| self.defs.contains_key(nam) || self.hvm_defs.contains_key(nam));
+      graph.insert(def_name.clone(), refs);
+    }
+    graph
+  }
+
+  /// Renders the [call graph](Book::call_graph) in the Graphviz DOT format.
+  ///
+  /// Each definition is a node and each reference an edge, with the entrypoint drawn in bold.
+  /// The native hvm definitions that are referenced are also nodes, drawn dashed.
+  /// Definitions and their references are listed in the order of the book, so the output is stable.
+  pub fn to_dot(&self) -> String {
+    let graph = self.call_graph();
+    let mut dot = String::from("digraph {\n");
+    for def_name in self.defs.keys() {
+      if self.entrypoint.as_ref() == Some(def_name) {
+        dot.push_str(&format!("  \"{def_name}\" [style=bold];\n"));
+      } else {
+        dot.push_str(&format!("  \"{def_name}\";\n"));
+      }
+    }
+    for def_name in self.hvm_defs.keys() {
+      if graph.values().any(|refs| refs.contains(def_name)) {
+        dot.push_str(&format!("  \"{def_name}\" [style=dashed];\n"));
+      }
+    }
+    let order = |nam: &Name| match self.defs.get_index_of(nam) {
+      Some(idx) => idx,
+      None => self.defs.len() + self.hvm_defs.get_index_of(nam).unwrap(),
+    };
+    for def_name in self.defs.keys() {
+      let mut refs = graph[def_name].iter().collect::<Vec<_>>();
+      refs.sort_by_key(|nam| order(nam));
+      for nam in refs {
+        dot.push_str(&format!("  \"{def_name}\" -> \"{nam}\";\n"));
+      }
+    }
+    dot.push('}');
+    dot
+  }
+}
+
+impl Term {
+  fn collect_def_refs(&self, refs: &mut HashSet<Name>) {
+    maybe_grow(|| match self {
+      Term::Ref { nam } => {
+        refs.insert(nam.clone());
+      }
+      Term::Def { def, nxt } => {
+        for rule in &def.rules {
+          rule.body.collect_def_refs(refs);
+        }
+        nxt.collect_def_refs(refs);
+      }
+      _ => {
+        for child in self.children() {
+          child.collect_def_refs(refs);
+        }
+      }
+    })
+  }
+}
+
+#[test]
+fn call_graph() {
+  use crate::fun::load_book::parse_test_book;
+  let code = "
+type Bin = (Node l r) | Leaf
+count (Bin/Node l r) = (+ (count l) (count r))
+count Bin/Leaf = 1
+leaf = Bin/Leaf
+main = let f = count; (f (Bin/Node leaf leaf))
+";
+  let book = parse_test_book(code);
+
+  let graph = book.call_graph();
+  let refs = |nam: &str| {
+    let mut refs = graph[&Name::new(nam)].iter().map(|nam| nam.to_string()).collect::<Vec<_>>();
+    refs.sort();
+    refs
+  };
+  assert_eq!(refs("count"), ["count"]);
+  assert_eq!(refs("leaf"), ["Bin/Leaf"]);
+  assert_eq!(refs("main"), ["Bin/Node", "count", "leaf"]);
+  // Encoded constructors refer to their tag.
+  assert_eq!(refs("Bin/Leaf"), ["Bin/Leaf/tag"]);
+}
+
+#[test]
+fn call_graph_to_dot() {
+  use crate::fun::load_book::parse_test_book;
+  let code = "
+hvm native:
+  (x x)
+hvm unused:
+  (x x)
+loop x = (loop x)
+main = (loop (native 1))
+";
+  let mut book = parse_test_book(code);
+  book.entrypoint = Some(Name::new("main"));
+
+  let dot = book.to_dot();
+  assert!(dot.starts_with("digraph {\n"));
+  assert!(dot.ends_with("\n}"));
+  assert!(dot.contains("\n  \"loop\";\n"));
+  assert!(dot.contains("\n  \"main\" [style=bold];\n"));
+  assert!(dot.contains("\n  \"loop\" -> \"loop\";\n"));
+  assert!(dot.contains("\n  \"main\" -> \"loop\";\n"));
+  // Every edge goes to a declared node, but unused native definitions are not drawn.
+  assert!(dot.contains("\n  \"native\" [style=dashed];\n"));
+  assert!(dot.contains("\n  \"main\" -> \"native\";\n"));
+  assert!(!dot.contains("\"unused\""));
+}
@@ -0,0 +1,76 @@
+use crate::{
+  diagnostics::WarningType,
+  fun::{Ctx, FanKind, Name, Pattern, Source, Term},
+  maybe_grow,
+};
+
+pub enum DupVarWarn {
+  /// One of the outputs of a duplication is never used.
+  UnusedOutput(Name),
+  /// The duplicated variable is still used after the duplication.
+  UsedAfterDup(Name),
+}
+
+impl Ctx<'_> {
+  /// Warns about explicit duplications, `let {a b} = x; ...`, that are not affine.
+  ///
+  /// Each output of the duplication should be used (or erased with `*`),
+  /// and the duplicated variable shouldn't be used again after it,
+  /// since that would implicitly create another duplication.
+  pub fn check_dup_vars(&mut self) {
+    for (def_name, def) in self.book.defs.iter() {
+      if matches!(def.source, Source::Builtin | Source::Generated) {
+        continue;
+      }
+      let mut warns = vec![];
+      for rule in &def.rules {
+        check_term(&rule.body, &mut warns);
+      }
+      for warn in warns {
+        self.info.add_rule_warning(warn, WarningType::DupUsage, def_name.clone());
+      }
+    }
+  }
+}
+
+fn check_term(term: &Term, warns: &mut Vec<DupVarWarn>) {
+  maybe_grow(|| {
+    if let Term::Let { pat, val, nxt } = term {
+      if let Pattern::Fan(FanKind::Dup, _, els) = pat.as_ref() {
+        let used = nxt.free_vars();
+        for nam in els.iter().flat_map(Pattern::binds).flatten() {
+          if !used.contains_key(nam) && !nam.is_generated() {
+            warns.push(DupVarWarn::UnusedOutput(nam.clone()));
+          }
+        }
+        if let Term::Var { nam } = val.as_ref() {
+          let rebound = pat.binds().flatten().any(|bind| bind == nam);
+          if !rebound && used.contains_key(nam) {
+            warns.push(DupVarWarn::UsedAfterDup(nam.clone()));
+          }
+        }
+      }
+    }
+    if let Term::Def { def, nxt } = term {
+      for rule in &def.rules {
+        check_term(&rule.body, warns);
+      }
+      check_term(nxt, warns);
+    } else {
+      for child in term.children() {
+        check_term(child, warns);
+      }
+    }
+  })
+}
+
+impl std::fmt::Display for DupVarWarn {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      DupVarWarn::UnusedOutput(nam) => {
+        write!(f, "Duplicated variable '{nam}' is never used. Erase it explicitly with '*'.")
+      }
+      DupVarWarn::UsedAfterDup(nam) => write!(f, "Variable '{nam}' is used after being duplicated."),
+    }
+  }
+}
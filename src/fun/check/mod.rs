@@ -1,4 +1,10 @@
+pub mod dup_vars;
+pub mod num_operands;
 pub mod set_entrypoint;
+pub mod shadowed_vars;
 pub mod shared_names;
 pub mod unbound_refs;
 pub mod unbound_vars;
+pub mod unreachable_rules;
+pub mod unused_vars;
+pub mod validate;
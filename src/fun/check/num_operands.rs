@@ -0,0 +1,98 @@
+use crate::{
+  diagnostics::{Diagnostics, WarningType},
+  fun::{Ctx, FanKind, Num, Op, Term},
+  maybe_grow,
+};
+use std::fmt::Display;
+
+#[derive(Debug, Clone)]
+pub enum NumOperandErr {
+  MixedNumTypes(Term),
+  /// An operand that can't be a number, like a lambda.
+  NotANumber {
+    opr: Op,
+    operand: Term,
+  },
+  /// Not an error, since it's still a valid program, but reported as a warning.
+  DivisionByZero(Term),
+}
+
+impl Ctx<'_> {
+  /// Checks that numeric operations don't have obviously wrong operands.
+  ///
+  /// HVM decides the type of an operation from the tag of its operands, so
+  /// mixing an integer and a float would silently reinterpret the bits of one of them.
+  /// Since there's no type system, this can only be checked when both operands are literals.
+  ///
+  /// Operands that are lambdas or tuples are also rejected, since they can never be a number.
+  /// Superpositions are allowed, since operations are applied to each of their elements.
+  ///
+  /// Also warns about integer divisions and remainders where the divisor is the literal `0`.
+  pub fn check_num_operands(&mut self) -> Result<(), Diagnostics> {
+    self.info.start_pass();
+
+    for (def_name, def) in self.book.defs.iter() {
+      let mut errs = Vec::new();
+      for rule in def.rules.iter() {
+        rule.body.check_num_operands(&mut errs);
+      }
+      for err in errs {
+        match err {
+          NumOperandErr::DivisionByZero(_) => {
+            self.info.add_rule_warning(err, WarningType::DivisionByZero, def_name.clone())
+          }
+          NumOperandErr::MixedNumTypes(_) | NumOperandErr::NotANumber { .. } => {
+            self.info.add_rule_error(err, def_name.clone())
+          }
+        }
+      }
+    }
+
+    self.info.fatal(())
+  }
+}
+
+impl Term {
+  pub fn check_num_operands(&self, errs: &mut Vec<NumOperandErr>) {
+    maybe_grow(|| {
+      if let Term::Oper { opr, fst, snd } = self {
+        if let (Term::Num { val: fst }, Term::Num { val: snd }) = (fst.as_ref(), snd.as_ref()) {
+          if matches!(fst, Num::F24(_)) != matches!(snd, Num::F24(_)) {
+            errs.push(NumOperandErr::MixedNumTypes(self.clone()));
+          }
+        }
+        for operand in [fst.as_ref(), snd.as_ref()] {
+          if matches!(operand, Term::Lam { .. } | Term::Fan { fan: FanKind::Tup, .. }) {
+            errs.push(NumOperandErr::NotANumber { opr: *opr, operand: operand.clone() });
+          }
+        }
+        if matches!(opr, Op::DIV | Op::REM)
+          && matches!(snd.as_ref(), Term::Num { val: Num::U24(0) | Num::I24(0) })
+        {
+          errs.push(NumOperandErr::DivisionByZero(self.clone()));
+        }
+      }
+      for child in self.children() {
+        child.check_num_operands(errs);
+      }
+    })
+  }
+}
+
+impl Display for NumOperandErr {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      NumOperandErr::MixedNumTypes(term) => {
+        write!(
+          f,
+          "Operation '{term}' mixes an integer and a float. Use the same number type for both operands."
+        )
+      }
+      NumOperandErr::NotANumber { opr, operand } => {
+        let kind = if let Term::Lam { .. } = operand { "a lambda" } else { "a tuple" };
+        write!(f, "Operand '{operand}' of operation '{opr}' is {kind}, not a number.")
+      }
+      NumOperandErr::DivisionByZero(term) => write!(f, "Operation '{term}' divides by zero."),
+    }
+  }
+}
@@ -12,29 +12,37 @@ pub enum EntryErr {
 }
 
 impl Ctx<'_> {
+  /// Sets the definition the program starts from.
+  ///
+  /// A custom entrypoint given in `book.entrypoint` takes precedence over `main`,
+  /// and it's an error if it doesn't exist, instead of falling back to `main`.
   pub fn set_entrypoint(&mut self) {
-    let mut entrypoint = None;
-
-    let (custom, main, hvm1_main) = self.book.get_possible_entry_points();
-    match (custom, main, hvm1_main) {
-      (Some(entry), None, None) | (None, Some(entry), None) | (None, None, Some(entry)) => {
-        match validate_entry_point(entry) {
-          Ok(name) => entrypoint = Some(name),
-          Err(err) => self.info.add_book_error(err),
+    if let Some(custom) = &self.book.entrypoint {
+      let entrypoint = match self.book.defs.get(custom) {
+        Some(entry) => validate_entry_point(entry),
+        None => Err(EntryErr::NotFound(custom.clone())),
+      };
+      match entrypoint {
+        Ok(name) => self.book.entrypoint = Some(name),
+        Err(err) => {
+          self.info.add_book_error(err);
+          self.book.entrypoint = None;
         }
       }
+      return;
+    }
 
-      (Some(a), Some(b), None) | (None, Some(a), Some(b)) | (Some(a), None, Some(b)) => {
-        self.info.add_book_error(EntryErr::Multiple(vec![a.name.clone(), b.name.clone()]));
+    let mut entrypoint = None;
 
-        match validate_entry_point(a) {
-          Ok(name) => entrypoint = Some(name),
-          Err(err) => self.info.add_book_error(err),
-        }
-      }
+    let (main, hvm1_main) = self.book.get_possible_entry_points();
+    match (main, hvm1_main) {
+      (Some(entry), None) | (None, Some(entry)) => match validate_entry_point(entry) {
+        Ok(name) => entrypoint = Some(name),
+        Err(err) => self.info.add_book_error(err),
+      },
 
-      (Some(a), Some(b), Some(c)) => {
-        self.info.add_book_error(EntryErr::Multiple(vec![a.name.clone(), b.name.clone(), c.name.clone()]));
+      (Some(a), Some(b)) => {
+        self.info.add_book_error(EntryErr::Multiple(vec![a.name.clone(), b.name.clone()]));
 
         match validate_entry_point(a) {
           Ok(name) => entrypoint = Some(name),
@@ -42,9 +50,8 @@ impl Ctx<'_> {
         }
       }
 
-      (None, None, None) => {
-        let entrypoint = self.book.entrypoint.clone().unwrap_or(Name::new(ENTRY_POINT));
-        self.info.add_book_warning(EntryErr::NotFound(entrypoint), WarningType::MissingMain)
+      (None, None) => {
+        self.info.add_book_warning(EntryErr::NotFound(Name::new(ENTRY_POINT)), WarningType::MissingMain)
       }
     }
 
@@ -61,11 +68,10 @@ fn validate_entry_point(entry: &Definition) -> Result<Name, EntryErr> {
 }
 
 impl Book {
-  fn get_possible_entry_points(&self) -> (Option<&Definition>, Option<&Definition>, Option<&Definition>) {
-    let custom = self.entrypoint.as_ref().and_then(|e| self.defs.get(e));
+  fn get_possible_entry_points(&self) -> (Option<&Definition>, Option<&Definition>) {
     let main = self.defs.get(&Name::new(ENTRY_POINT));
     let hvm1_main = self.defs.get(&Name::new(HVM1_ENTRY_POINT));
-    (custom, main, hvm1_main)
+    (main, hvm1_main)
   }
 }
 
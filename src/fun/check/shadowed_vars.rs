@@ -0,0 +1,113 @@
+use crate::{
+  diagnostics::WarningType,
+  fun::{Ctx, FanKind, Name, Pattern, Rule, Source, Term},
+  maybe_grow,
+};
+use std::collections::HashMap;
+
+pub struct ShadowedVarWarn {
+  nam: Name,
+  /// What binds the new variable.
+  inner: &'static str,
+  /// What binds the variable that is shadowed.
+  outer: &'static str,
+}
+
+impl Ctx<'_> {
+  /// Warns about variables that are explicitly bound while another variable
+  /// with the same name is still in scope, like `λx λx x`.
+  ///
+  /// Only lambdas, `let`, `use` and `ask` are checked as the inner binder, since the
+  /// implicit binds of a `match` always shadow its scrutinee. The variables of different
+  /// definitions and of the different branches of a term don't shadow each other.
+  pub fn check_shadowed_vars(&mut self) {
+    for (def_name, def) in self.book.defs.iter() {
+      if matches!(def.source, Source::Builtin | Source::Generated) {
+        continue;
+      }
+      let mut ctx = ShadowedVarsCtx::default();
+      for rule in &def.rules {
+        ctx.check_rule(rule);
+      }
+      for warn in ctx.warns {
+        self.info.add_rule_warning(warn, WarningType::ShadowedVariable, def_name.clone());
+      }
+    }
+  }
+}
+
+#[derive(Default)]
+struct ShadowedVarsCtx<'a> {
+  /// For each variable in scope, what bound it, with the innermost binder last.
+  scope: HashMap<&'a Name, Vec<&'static str>>,
+  warns: Vec<ShadowedVarWarn>,
+}
+
+impl<'a> ShadowedVarsCtx<'a> {
+  fn check_rule(&mut self, rule: &'a Rule) {
+    let binds = rule.pats.iter().flat_map(Pattern::binds).flatten().collect::<Vec<_>>();
+    self.push(&binds, "a rule pattern", true);
+    if let Some(guard) = &rule.guard {
+      self.check_term(guard);
+    }
+    self.check_term(&rule.body);
+    self.pop(&binds);
+  }
+
+  fn check_term(&mut self, term: &'a Term) {
+    maybe_grow(|| {
+      if let Term::Def { def, nxt } = term {
+        for rule in &def.rules {
+          self.check_rule(rule);
+        }
+        return self.check_term(nxt);
+      }
+      let (kind, explicit) = match term {
+        Term::Lam { .. } => ("a lambda", true),
+        Term::Let { pat, .. } if matches!(pat.as_ref(), Pattern::Fan(FanKind::Dup, ..)) => {
+          ("a duplication", true)
+        }
+        Term::Let { .. } => ("a let", true),
+        Term::Use { .. } => ("a use", true),
+        Term::Ask { .. } => ("an ask", true),
+        Term::Swt { .. } => ("a switch", false),
+        Term::Fold { .. } => ("a fold", false),
+        Term::Bend { .. } => ("a bend", false),
+        _ => ("a match", false),
+      };
+      for (child, binds) in term.children_with_binds() {
+        let binds = binds.flatten().collect::<Vec<_>>();
+        self.push(&binds, kind, explicit);
+        self.check_term(child);
+        self.pop(&binds);
+      }
+    })
+  }
+
+  /// Binds the variables, warning about the ones that shadow a variable already in scope.
+  /// Names repeated in the same pattern are not reported, since they're bound at the same place.
+  fn push(&mut self, binds: &[&'a Name], kind: &'static str, explicit: bool) {
+    for bind in binds {
+      if let Some(outer) = self.scope.get(bind).and_then(|kinds| kinds.last()) {
+        if explicit && !bind.is_generated() {
+          self.warns.push(ShadowedVarWarn { nam: (*bind).clone(), inner: kind, outer });
+        }
+      }
+    }
+    for bind in binds {
+      self.scope.entry(bind).or_default().push(kind);
+    }
+  }
+
+  fn pop(&mut self, binds: &[&'a Name]) {
+    for bind in binds {
+      self.scope.get_mut(bind).unwrap().pop();
+    }
+  }
+}
+
+impl std::fmt::Display for ShadowedVarWarn {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Variable '{}' bound by {} shadows the one bound by {}.", self.nam, self.inner, self.outer)
+  }
+}
@@ -1,12 +1,15 @@
-use crate::fun::{Ctx, Name};
+use crate::{
+  diagnostics::CompileError,
+  fun::{Ctx, Name},
+};
 use indexmap::IndexMap;
 use std::fmt::Display;
 
 #[derive(Debug, Clone)]
 pub struct RepeatedTopLevelNameErr {
-  kind_fst: NameKind,
-  kind_snd: NameKind,
-  name: Name,
+  pub(crate) kind_fst: NameKind,
+  pub(crate) kind_snd: NameKind,
+  pub(crate) name: Name,
 }
 
 impl Ctx<'_> {
@@ -28,13 +31,14 @@ impl Ctx<'_> {
     }
 
     for err in names.into_errs() {
-      self.info.add_book_error(err);
+      let kind = CompileError::DuplicateName { name: err.name, previous: err.kind_fst, kind: err.kind_snd };
+      self.info.add_compile_error(kind);
     }
   }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum NameKind {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameKind {
   Adt,
   Def,
   Ctr,
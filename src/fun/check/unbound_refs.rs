@@ -1,5 +1,5 @@
 use crate::{
-  diagnostics::Diagnostics,
+  diagnostics::{CompileError, Diagnostics},
   fun::{Book, Ctx, Name, Term},
   maybe_grow,
 };
@@ -14,7 +14,9 @@ impl Ctx<'_> {
         rule.body.check_unbound_refs(self.book, &mut unbounds);
       }
       for unbound in unbounds {
-        self.info.add_rule_error(format!("Reference to undefined function '{unbound}'"), def.name.clone());
+        let kind =
+          CompileError::UnboundRef { def_name: def.name.clone(), ref_name: unbound, span: def.source.span() };
+        self.info.add_compile_error(kind);
       }
     }
     self.info.fatal(())
@@ -1,13 +1,14 @@
 use crate::{
-  diagnostics::Diagnostics,
+  diagnostics::{CompileError, Diagnostics},
   fun::{transform::desugar_bend, Ctx, Name, Pattern, Term},
   maybe_grow,
+  utils::edit_distance,
 };
 use std::collections::{hash_map::Entry, HashMap};
 
 #[derive(Debug, Clone)]
 pub enum UnboundVarErr {
-  Local(Name),
+  Local { var: Name, similar: Option<Name> },
   Global { var: Name, declared: usize, used: usize },
 }
 
@@ -28,7 +29,13 @@ impl Ctx<'_> {
       }
 
       for err in errs {
-        self.info.add_rule_error(err, def_name.clone());
+        if let UnboundVarErr::Local { var, similar } = err {
+          let kind =
+            CompileError::UnboundVar { def_name: def_name.clone(), var, similar, span: def.source.span() };
+          self.info.add_compile_error(kind);
+        } else {
+          self.info.add_rule_error(err, def_name.clone());
+        }
       }
     }
 
@@ -39,7 +46,6 @@ impl Ctx<'_> {
 impl Term {
   /// Checks that all variables are bound.
   /// Precondition: References have been resolved, implicit binds have been solved.
-
   pub fn check_unbound_vars<'a>(
     &'a mut self,
     scope: &mut HashMap<&'a Name, u64>,
@@ -66,7 +72,7 @@ pub fn check_uses<'a>(
   maybe_grow(move || match term {
     Term::Var { nam } => {
       if !scope.contains_key(nam) {
-        errs.push(UnboundVarErr::Local(nam.clone()));
+        errs.push(UnboundVarErr::Local { var: nam.clone(), similar: similar_name(nam, scope) });
         *term = Term::Err;
       }
     }
@@ -104,6 +110,21 @@ pub fn check_global_binds(pat: &Pattern, globals: &mut HashMap<Name, (usize, usi
   }
 }
 
+/// Finds a variable in scope with a name close enough to the unbound one to be a likely typo.
+fn similar_name(nam: &Name, scope: &HashMap<&Name, u64>) -> Option<Name> {
+  // Short names are too similar to each other for this to be useful.
+  if nam.len() < 3 {
+    return None;
+  }
+  let max_distance = (nam.len() + 1) / 3;
+  scope
+    .keys()
+    .map(|bound| (edit_distance(nam, bound), *bound))
+    .filter(|(distance, _)| *distance <= max_distance)
+    .min()
+    .map(|(_, bound)| bound.clone())
+}
+
 fn push_scope<'a>(nam: Option<&'a Name>, scope: &mut HashMap<&'a Name, u64>) {
   if let Some(nam) = nam {
     *scope.entry(nam).or_default() += 1;
@@ -123,7 +144,7 @@ fn pop_scope<'a>(nam: Option<&'a Name>, scope: &mut HashMap<&'a Name, u64>) {
 impl std::fmt::Display for UnboundVarErr {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
-      UnboundVarErr::Local(var) => {
+      UnboundVarErr::Local { var, similar } => {
         if var == desugar_bend::RECURSIVE_KW {
           write!(
             f,
@@ -137,7 +158,11 @@ impl std::fmt::Display for UnboundVarErr {
             "Unbound variable '{var}'. If you wanted to subtract '{pre}' from '{suf}', you must separate it with spaces ('{pre} - {suf}') since '-' is a valid name character."
           )
         } else {
-          write!(f, "Unbound variable '{var}'.")
+          write!(f, "Unbound variable '{var}'.")?;
+          if let Some(similar) = similar {
+            write!(f, " Did you mean '{similar}'?")?;
+          }
+          Ok(())
         }
       }
       UnboundVarErr::Global { var, declared, used } => match (declared, used) {
@@ -0,0 +1,102 @@
+use crate::{
+  diagnostics::WarningType,
+  fun::{Ctx, Definition, Name, Pattern, Rule, Term},
+  maybe_grow,
+};
+use std::fmt::Display;
+
+pub struct UnreachableRuleWarn {
+  /// The name of the definition, if it's a local definition.
+  local_def: Option<Name>,
+  /// The position of the unreachable rule in its definition.
+  rule: usize,
+  /// The position of the earlier rule that matches everything that the unreachable one does.
+  covered_by: usize,
+}
+
+impl Ctx<'_> {
+  /// Warns about pattern matching rules that can never be reached,
+  /// because an earlier rule matches every value they would match.
+  ///
+  /// Rules are tried from top to bottom, so a rule like `(Foo x) = ...` makes all the rules
+  /// of `Foo` after it unreachable. This only looks at one earlier rule at a time, so rules
+  /// only covered by a combination of the ones before it are not reported.
+  ///
+  /// Must run after the constructor patterns are resolved and before the guards are desugared,
+  /// since a rule with a guard can fall through to the next ones.
+  pub fn check_unreachable_rules(&mut self) {
+    for (def_name, def) in self.book.defs.iter() {
+      if def.is_builtin() {
+        continue;
+      }
+      let mut warns = vec![];
+      def.check_unreachable_rules(None, &mut warns);
+      for warn in warns {
+        self.info.add_rule_warning(warn, WarningType::UnreachableMatch, def_name.clone());
+      }
+    }
+  }
+}
+
+impl Definition {
+  fn check_unreachable_rules(&self, local_def: Option<&Name>, warns: &mut Vec<UnreachableRuleWarn>) {
+    for (idx, rule) in self.rules.iter().enumerate() {
+      let covered_by = self.rules[..idx].iter().position(|prev| prev.covers(rule));
+      if let Some(covered_by) = covered_by {
+        warns.push(UnreachableRuleWarn { local_def: local_def.cloned(), rule: idx, covered_by });
+      }
+      check_local_defs(&rule.body, warns);
+    }
+  }
+}
+
+impl Rule {
+  /// If every value matched by `other` is also matched by `self`.
+  fn covers(&self, other: &Rule) -> bool {
+    self.guard.is_none()
+      && self.pats.len() == other.pats.len()
+      && self.pats.iter().zip(&other.pats).all(|(a, b)| a.covers(b))
+  }
+}
+
+impl Pattern {
+  fn covers(&self, other: &Pattern) -> bool {
+    maybe_grow(|| match (self, other) {
+      (Pattern::Var(_) | Pattern::Chn(_), _) => true,
+      (Pattern::Ctr(a, a_args), Pattern::Ctr(b, b_args)) => a == b && covers_all(a_args, b_args),
+      (Pattern::Fan(a_fan, a_tag, a_els), Pattern::Fan(b_fan, b_tag, b_els)) => {
+        a_fan == b_fan && a_tag == b_tag && covers_all(a_els, b_els)
+      }
+      (Pattern::Lst(a_els), Pattern::Lst(b_els)) => covers_all(a_els, b_els),
+      (Pattern::Num(a), Pattern::Num(b)) => a == b,
+      (Pattern::Str(a), Pattern::Str(b)) => a == b,
+      _ => false,
+    })
+  }
+}
+
+fn covers_all(a: &[Pattern], b: &[Pattern]) -> bool {
+  a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.covers(b))
+}
+
+/// Local definitions are reported in the definition that contains them.
+fn check_local_defs(term: &Term, warns: &mut Vec<UnreachableRuleWarn>) {
+  maybe_grow(|| {
+    if let Term::Def { def, .. } = term {
+      def.check_unreachable_rules(Some(&def.name), warns);
+    }
+    for child in term.children() {
+      check_local_defs(child, warns);
+    }
+  })
+}
+
+impl Display for UnreachableRuleWarn {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Unreachable pattern matching rule {}", self.rule + 1)?;
+    if let Some(local_def) = &self.local_def {
+      write!(f, " of local definition '{local_def}'")?;
+    }
+    write!(f, ". Every value it matches is already matched by rule {}.", self.covered_by + 1)
+  }
+}
@@ -0,0 +1,102 @@
+use crate::{
+  diagnostics::WarningType,
+  fun::{Ctx, Name, Rule, Source, Term},
+  maybe_grow,
+};
+use std::collections::HashMap;
+
+pub struct UnusedVarWarn(Name);
+
+impl Ctx<'_> {
+  /// Warns about variables that are explicitly bound but never used.
+  ///
+  /// Only the variables bound by rule patterns, lambdas, `let`, `use` and `ask`
+  /// are checked, not the ones bound implicitly like the fields of a `match`.
+  /// Using a variable more than once is fine, since it'll be duplicated automatically.
+  /// Variables starting with `_`, like `_unused`, are meant to be unused and are not reported.
+  pub fn check_unused_vars(&mut self) {
+    for (def_name, def) in self.book.defs.iter() {
+      if matches!(def.source, Source::Builtin | Source::Generated) {
+        continue;
+      }
+      let mut ctx = UnusedVarsCtx::default();
+      for rule in &def.rules {
+        ctx.check_rule(rule);
+      }
+      for var in ctx.unused() {
+        self.info.add_rule_warning(UnusedVarWarn(var), WarningType::UnusedVariable, def_name.clone());
+      }
+    }
+  }
+}
+
+#[derive(Default)]
+struct UnusedVarsCtx<'a> {
+  /// For each variable in scope, when it was bound, whether it was bound explicitly and how many times it was used.
+  scope: HashMap<&'a Name, Vec<(usize, bool, usize)>>,
+  bind_count: usize,
+  unused: Vec<(usize, Name)>,
+}
+
+impl<'a> UnusedVarsCtx<'a> {
+  fn check_rule(&mut self, rule: &'a Rule) {
+    let binds = rule.pats.iter().flat_map(|pat| pat.binds()).flatten().collect::<Vec<_>>();
+    self.push(binds.iter().copied(), true);
+    self.check_uses(&rule.body);
+    self.pop(binds.into_iter());
+  }
+
+  fn check_uses(&mut self, term: &'a Term) {
+    maybe_grow(|| match term {
+      Term::Var { nam } => {
+        if let Some((_, _, uses)) = self.scope.get_mut(nam).and_then(|vars| vars.last_mut()) {
+          *uses += 1;
+        }
+      }
+      Term::Def { def, nxt } => {
+        for rule in &def.rules {
+          self.check_rule(rule);
+        }
+        self.check_uses(nxt);
+      }
+      _ => {
+        let explicit =
+          matches!(term, Term::Lam { .. } | Term::Let { .. } | Term::Use { .. } | Term::Ask { .. });
+        for (child, binds) in term.children_with_binds() {
+          self.push(binds.clone().flatten(), explicit);
+          self.check_uses(child);
+          self.pop(binds.flatten());
+        }
+      }
+    })
+  }
+
+  fn push(&mut self, binds: impl Iterator<Item = &'a Name>, explicit: bool) {
+    for bind in binds {
+      self.scope.entry(bind).or_default().push((self.bind_count, explicit, 0));
+      self.bind_count += 1;
+    }
+  }
+
+  fn pop(&mut self, binds: impl DoubleEndedIterator<Item = &'a Name>) {
+    // Popped in reverse, so that a name bound twice by the same pattern is matched with the right entry.
+    for bind in binds.rev() {
+      let (bound_at, explicit, uses) = self.scope.get_mut(bind).and_then(|vars| vars.pop()).unwrap();
+      if explicit && uses == 0 && !bind.is_generated() && !bind.starts_with('_') {
+        self.unused.push((bound_at, bind.clone()));
+      }
+    }
+  }
+
+  /// The unused variables, in the order they were bound.
+  fn unused(mut self) -> impl Iterator<Item = Name> {
+    self.unused.sort_by_key(|(bound_at, _)| *bound_at);
+    self.unused.into_iter().map(|(_, nam)| nam)
+  }
+}
+
+impl std::fmt::Display for UnusedVarWarn {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Variable '{}' is never used.", self.0)
+  }
+}
@@ -0,0 +1,120 @@
+use crate::fun::{Book, Name};
+use std::{collections::HashSet, fmt::Display};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+  /// A definition is stored under a different name than its own.
+  MismatchedDefName { key: Name, name: Name },
+  /// A reference to a function that isn't in the book.
+  DanglingRef { def_name: Name, ref_name: Name },
+  /// A constructor whose type doesn't exist or doesn't have it as a constructor.
+  OrphanCtr { ctr_name: Name, adt_name: Name },
+  /// A constructor of a type that is missing from the constructors map.
+  MissingCtr { ctr_name: Name, adt_name: Name },
+}
+
+impl Book {
+  /// Checks that the book is internally consistent, returning all the problems found.
+  ///
+  /// Unlike the checks done while compiling, this doesn't look at the program itself,
+  /// only at the links between the parts of the book, to catch books that were built
+  /// or modified incorrectly. References are expected to be already resolved.
+  pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+    let mut errs = vec![];
+
+    for (key, def) in &self.defs {
+      if *key != def.name {
+        errs.push(ValidationError::MismatchedDefName { key: key.clone(), name: def.name.clone() });
+      }
+    }
+
+    for (def_name, def) in &self.defs {
+      let mut dangling = HashSet::new();
+      for rule in &def.rules {
+        rule.body.check_unbound_refs(self, &mut dangling);
+        if let Some(guard) = &rule.guard {
+          guard.check_unbound_refs(self, &mut dangling);
+        }
+      }
+      let mut dangling = dangling.into_iter().collect::<Vec<_>>();
+      dangling.sort();
+      for ref_name in dangling {
+        errs.push(ValidationError::DanglingRef { def_name: def_name.clone(), ref_name });
+      }
+    }
+
+    for (ctr_name, adt_name) in &self.ctrs {
+      if !self.adts.get(adt_name).is_some_and(|adt| adt.ctrs.contains_key(ctr_name)) {
+        errs.push(ValidationError::OrphanCtr { ctr_name: ctr_name.clone(), adt_name: adt_name.clone() });
+      }
+    }
+
+    for (adt_name, adt) in &self.adts {
+      for ctr_name in adt.ctrs.keys() {
+        if self.ctrs.get(ctr_name) != Some(adt_name) {
+          errs.push(ValidationError::MissingCtr { ctr_name: ctr_name.clone(), adt_name: adt_name.clone() });
+        }
+      }
+    }
+
+    if errs.is_empty() {
+      Ok(())
+    } else {
+      Err(errs)
+    }
+  }
+}
+
+impl Display for ValidationError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ValidationError::MismatchedDefName { key, name } => {
+        write!(f, "Definition '{name}' is stored with the name '{key}'")
+      }
+      ValidationError::DanglingRef { def_name, ref_name } => {
+        write!(f, "Definition '{def_name}' references undefined function '{ref_name}'")
+      }
+      ValidationError::OrphanCtr { ctr_name, adt_name } => {
+        write!(f, "Constructor '{ctr_name}' belongs to '{adt_name}', which doesn't define it")
+      }
+      ValidationError::MissingCtr { ctr_name, adt_name } => {
+        write!(f, "Constructor '{ctr_name}' of '{adt_name}' is not registered as a constructor")
+      }
+    }
+  }
+}
+
+#[test]
+fn validate_book() {
+  use crate::fun::{load_book::parse_test_book, Term};
+  let code = "
+type Bin = (Node l r) | Leaf
+size (Bin/Node l r) = (+ (size l) (size r))
+size Bin/Leaf = 1
+main = (size Bin/Leaf)
+";
+  let book = parse_test_book(code);
+  assert_eq!(book.validate(), Ok(()));
+
+  // A dangling ref
+  let mut dangling = book.clone();
+  dangling.defs.get_mut(&Name::new("main")).unwrap().rules[0].body = Term::r#ref("missing");
+  assert_eq!(
+    dangling.validate(),
+    Err(vec![ValidationError::DanglingRef { def_name: Name::new("main"), ref_name: Name::new("missing") }])
+  );
+
+  // An orphaned ctr
+  let mut orphan = book.clone();
+  orphan.ctrs.insert(Name::new("Bin/Branch"), Name::new("Bin"));
+  orphan.ctrs.insert(Name::new("Rose/Leaf"), Name::new("Rose"));
+  let errs = orphan.validate().unwrap_err();
+  assert_eq!(
+    errs,
+    [
+      ValidationError::OrphanCtr { ctr_name: Name::new("Bin/Branch"), adt_name: Name::new("Bin") },
+      ValidationError::OrphanCtr { ctr_name: Name::new("Rose/Leaf"), adt_name: Name::new("Rose") },
+    ]
+  );
+  assert_eq!(errs[1].to_string(), "Constructor 'Rose/Leaf' belongs to 'Rose', which doesn't define it");
+}
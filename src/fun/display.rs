@@ -1,5 +1,9 @@
-use super::{Book, Definition, FanKind, Name, Num, Op, Pattern, Rule, Tag, Term};
+use super::{
+  builtins::{SCONS, SNIL},
+  Book, Definition, FanKind, HvmDefinition, Name, Num, Op, Pattern, Rule, Tag, Term,
+};
 use crate::maybe_grow;
+use itertools::Itertools;
 use std::{fmt, ops::Deref, sync::atomic::AtomicU64};
 
 /* Some aux structures for things that are not so simple to display */
@@ -53,6 +57,9 @@ fn namegen_reset() {
 
 impl fmt::Display for Term {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if let Some(str) = self.as_str_literal() {
+      return write!(f, "{str:?}");
+    }
     maybe_grow(|| match self {
       Term::Lam { tag, pat, bod } => match &**pat {
         Pattern::Fan(_, _, _) => {
@@ -162,6 +169,7 @@ impl fmt::Display for Term {
       Term::Num { val: Num::F24(val) } => write!(f, "{val:.3}"),
       Term::Nat { val } => write!(f, "#{val}"),
       Term::Str { val } => write!(f, "{val:?}"),
+      Term::Oper { snd, .. } if self.as_bitwise_not().is_some() => write!(f, "~{snd}"),
       Term::Oper { opr, fst, snd } => {
         write!(f, "({} {} {})", opr, fst, snd)
       }
@@ -203,7 +211,7 @@ impl fmt::Display for Pattern {
       Pattern::Fan(FanKind::Tup, tag, pats) => write!(f, "{}({})", tag, DisplayJoin(|| pats, ", ")),
       Pattern::Fan(FanKind::Dup, tag, pats) => write!(f, "{}{{{}}}", tag, DisplayJoin(|| pats, " ")),
       Pattern::Lst(pats) => write!(f, "[{}]", DisplayJoin(|| pats, ", ")),
-      Pattern::Str(str) => write!(f, "\"{str}\""),
+      Pattern::Str(str) => write!(f, "{str:?}"),
     }
   }
 }
@@ -211,9 +219,10 @@ impl fmt::Display for Pattern {
 impl Rule {
   pub fn display<'a>(&'a self, def_name: &'a Name) -> impl fmt::Display + 'a {
     display!(
-      "({}{}) = {}",
+      "({}{}){} = {}",
       def_name,
       DisplayJoin(|| self.pats.iter().map(|x| display!(" {x}")), ""),
+      DisplayJoin(|| self.guard.iter().map(|x| display!(" | {x}")), ""),
       self.body
     )
   }
@@ -228,14 +237,46 @@ impl fmt::Display for Definition {
 
 impl fmt::Display for Book {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    write!(f, "{}", DisplayJoin(|| self.defs.values(), "\n\n"))?;
-    for def in self.hvm_defs.values() {
-      writeln!(f, "hvm {}:\n{}\n", def.name, def.body.show())?;
-    }
-    Ok(())
+    fmt_book(f, self.defs.values(), self.hvm_defs.values())
+  }
+}
+
+/// Only a summary of the book, since the whole program is usually too big to be read in a log.
+/// To see the full program, use the `Display` implementation.
+impl fmt::Debug for Book {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Book")
+      .field("defs", &self.defs.len())
+      .field("hvm_defs", &self.hvm_defs.len())
+      .field("adts", &self.adts.len())
+      .field("entrypoint", &self.entrypoint)
+      .field("def_names", &format_args!("[{}]", DisplayJoin(|| self.defs.keys(), ", ")))
+      .finish_non_exhaustive()
   }
 }
 
+impl Book {
+  /// Like the `Display` of a book, but with the definitions sorted by name instead of
+  /// in the order they were added, so that books with the same definitions are shown the same.
+  pub fn to_string_sorted(&self) -> String {
+    let defs = self.defs.values().sorted_by(|a, b| a.name.cmp(&b.name));
+    let hvm_defs = self.hvm_defs.values().sorted_by(|a, b| a.name.cmp(&b.name));
+    DisplayFn(|f| fmt_book(f, defs.clone(), hvm_defs.clone())).to_string()
+  }
+}
+
+fn fmt_book<'a>(
+  f: &mut fmt::Formatter<'_>,
+  defs: impl Iterator<Item = &'a Definition> + Clone,
+  hvm_defs: impl Iterator<Item = &'a HvmDefinition>,
+) -> fmt::Result {
+  write!(f, "{}", DisplayJoin(|| defs.clone(), "\n\n"))?;
+  for def in hvm_defs {
+    writeln!(f, "hvm {}:\n{}\n", def.name, def.body.show())?;
+  }
+  Ok(())
+}
+
 impl fmt::Display for Name {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     self.0.fmt(f)
@@ -243,9 +284,34 @@ impl fmt::Display for Name {
 }
 
 impl Term {
+  /// The string held by a desugared string, of `String/Cons` applied to character codes and ending in `String/Nil`.
+  ///
+  /// A `String/Nil` alone is not considered a string, since it's also how the constructor is referenced.
+  fn as_str_literal(&self) -> Option<String> {
+    let mut str = String::new();
+    let mut term = self;
+    loop {
+      match term {
+        Term::Ref { nam } if nam == SNIL && !str.is_empty() => return Some(str),
+        Term::App { tag: Tag::Static, fun, arg: tail } => {
+          let Term::App { tag: Tag::Static, fun: head, arg: chr } = fun.as_ref() else { return None };
+          let (Term::Ref { nam }, Term::Num { val: Num::U24(chr) }) = (head.as_ref(), chr.as_ref()) else {
+            return None;
+          };
+          if nam != SCONS {
+            return None;
+          }
+          str.push(char::from_u32(*chr)?);
+          term = tail;
+        }
+        _ => return None,
+      }
+    }
+  }
+
   fn display_app<'a>(&'a self, tag: &'a Tag) -> impl fmt::Display + 'a {
-    maybe_grow(|| {
-      DisplayFn(move |f| match self {
+    DisplayFn(move |f| {
+      maybe_grow(|| match self {
         Term::App { tag: tag2, fun, arg } if tag2 == tag => {
           write!(f, "{} {}", fun.display_app(tag), arg)
         }
@@ -321,18 +387,20 @@ impl Definition {
 impl Rule {
   pub fn display_pretty<'a>(&'a self, def_name: &'a Name) -> impl fmt::Display + 'a {
     display!(
-      "({}{}) =\n  {}",
+      "({}{}){} =\n  {}",
       def_name,
       DisplayJoin(|| self.pats.iter().map(|x| display!(" {x}")), ""),
+      DisplayJoin(|| self.guard.iter().map(|x| display!(" | {}", x.display_pretty(2))), ""),
       self.body.display_pretty(2)
     )
   }
 
   pub fn display_def_aux<'a>(&'a self, def_name: &'a Name, tab: usize) -> impl fmt::Display + 'a {
     display!(
-      "({}{}) =\n  {:tab$}{}",
+      "({}{}){} =\n  {:tab$}{}",
       def_name,
       DisplayJoin(|| self.pats.iter().map(|x| display!(" {x}")), ""),
+      DisplayJoin(|| self.guard.iter().map(|x| display!(" | {}", x.display_pretty(tab + 2))), ""),
       "",
       self.body.display_pretty(tab + 2)
     )
@@ -341,8 +409,11 @@ impl Rule {
 
 impl Term {
   pub fn display_pretty(&self, tab: usize) -> impl fmt::Display + '_ {
-    maybe_grow(|| {
-      DisplayFn(move |f| match self {
+    DisplayFn(move |f| {
+      if let Some(str) = self.as_str_literal() {
+        return write!(f, "{str:?}");
+      }
+      maybe_grow(|| match self {
         Term::Lam { tag, pat, bod } => match &**pat {
           Pattern::Fan(_, _, _) => {
             let name = gen_fan_pat_name();
@@ -403,6 +474,9 @@ impl Term {
         Term::List { els } => {
           write!(f, "[{}]", DisplayJoin(|| els.iter().map(|e| e.display_pretty(tab)), " "))
         }
+        Term::Oper { snd, .. } if self.as_bitwise_not().is_some() => {
+          write!(f, "~{}", snd.display_pretty(tab))
+        }
         Term::Oper { opr, fst, snd } => {
           write!(f, "({} {} {})", opr, fst.display_pretty(tab), snd.display_pretty(tab))
         }
@@ -518,8 +592,8 @@ impl Term {
   }
 
   fn display_app_pretty<'a>(&'a self, tag: &'a Tag, tab: usize) -> impl fmt::Display + 'a {
-    maybe_grow(|| {
-      DisplayFn(move |f| match self {
+    DisplayFn(move |f| {
+      maybe_grow(|| match self {
         Term::App { tag: tag2, fun, arg } if tag2 == tag => {
           write!(f, "{} {}", fun.display_app_pretty(tag, tab), arg.display_pretty(tab))
         }
@@ -528,3 +602,367 @@ impl Term {
     })
   }
 }
+
+/* Width-aware pretty printing */
+
+impl Term {
+  /// Displays this term broken into multiple lines, trying to keep every line under `width` characters.
+  ///
+  /// Subterms that fit in the rest of their line are displayed in a single line, like with `to_string`.
+  /// Otherwise, blocks like `let` and `match` are split into lines and applications,
+  /// operations, tuples and lists put each of their elements on an indented line.
+  pub fn pretty(&self, width: usize) -> String {
+    let mut printer = PrettyPrinter { out: String::new(), col: 0, width };
+    printer.term(self, 0);
+    printer.out
+  }
+}
+
+struct PrettyPrinter {
+  out: String,
+  /// The column where the next character will be displayed.
+  col: usize,
+  width: usize,
+}
+
+impl PrettyPrinter {
+  fn term(&mut self, term: &Term, tab: usize) {
+    maybe_grow(|| {
+      if self.fits(term) || term.as_str_literal().is_some() {
+        self.push(term);
+        return;
+      }
+      match term {
+        Term::Lam { tag, pat, bod } if !matches!(pat.as_ref(), Pattern::Fan(..)) => {
+          self.push(display!("{}λ{} ", tag.display_padded(), pat));
+          self.term(bod, tab);
+        }
+        Term::Let { pat, val, nxt } => self.binding(display!("let {pat} = "), val, nxt, tab),
+        Term::Ask { pat, val, nxt } => self.binding(display!("ask {pat} = "), val, nxt, tab),
+        Term::Use { nam, val, nxt } => self.binding(display!("use {} = ", var_as_str(nam)), val, nxt, tab),
+        Term::Open { typ, var, bod } => {
+          self.push(display!("open {typ} {var};"));
+          self.newline(tab);
+          self.term(bod, tab);
+        }
+        Term::With { typ, bod } => {
+          self.push(display!("with {typ} {{"));
+          self.newline(tab + 2);
+          self.term(bod, tab + 2);
+          self.newline(tab);
+          self.push("}");
+        }
+        Term::App { tag, .. } => {
+          let mut args = vec![];
+          let mut fun = term;
+          while let Term::App { tag: fun_tag, fun: nxt_fun, arg } = fun {
+            if fun_tag != tag {
+              break;
+            }
+            args.push(arg.as_ref());
+            fun = nxt_fun;
+          }
+          self.push(display!("{}(", tag.display_padded()));
+          self.term(fun, tab + 2);
+          self.elements(args.into_iter().rev(), "", tab);
+          self.push(")");
+        }
+        Term::Oper { snd, .. } if term.as_bitwise_not().is_some() => {
+          self.push("~");
+          self.term(snd, tab);
+        }
+        Term::Oper { opr, fst, snd } => {
+          self.push(display!("({opr}"));
+          self.elements([fst.as_ref(), snd.as_ref()], "", tab);
+          self.push(")");
+        }
+        Term::Fan { fan: FanKind::Tup, tag, els } => {
+          self.push(display!("{tag}("));
+          self.elements(els, ",", tab);
+          self.newline(tab);
+          self.push(")");
+        }
+        Term::Fan { fan: FanKind::Dup, tag, els } => {
+          self.push(display!("{tag}{{"));
+          self.elements(els, "", tab);
+          self.newline(tab);
+          self.push("}");
+        }
+        Term::List { els } => {
+          self.push("[");
+          self.elements(els, ",", tab);
+          self.newline(tab);
+          self.push("]");
+        }
+        Term::Mat { bnd, arg, with_bnd, with_arg, arms }
+        | Term::Fold { bnd, arg, with_bnd, with_arg, arms } => {
+          let keyword = if matches!(term, Term::Mat { .. }) { "match" } else { "fold" };
+          self.match_header(keyword, bnd, arg, with_bnd, with_arg, tab);
+          for (ctr, fields, body) in arms {
+            self.newline(tab + 2);
+            let fields = DisplayJoin(|| fields.iter().map(|field| display!(" {}", var_as_str(field))), "");
+            self.push(display!("{}{}: ", var_as_str(ctr), fields));
+            self.term(body, tab + 4);
+            self.push(";");
+          }
+          self.newline(tab);
+          self.push("}");
+        }
+        Term::Swt { bnd, arg, with_bnd, with_arg, pred, arms } => {
+          self.match_header("switch", bnd, arg, with_bnd, with_arg, tab);
+          for (i, arm) in arms.iter().enumerate() {
+            self.newline(tab + 2);
+            if i == arms.len() - 1 {
+              self.push("_");
+              if let Some(pred) = pred {
+                self.push(display!(" {pred}"));
+              }
+            } else {
+              self.push(display!("{i}"));
+            }
+            self.push(": ");
+            self.term(arm, tab + 4);
+            self.push(";");
+          }
+          self.newline(tab);
+          self.push("}");
+        }
+        Term::Bend { bnd, arg, cond, step, base } => {
+          self.push("bend ");
+          for (bnd, arg) in bnd.iter().zip(arg) {
+            if let Some(bnd) = bnd {
+              self.push(display!("{bnd} = "));
+            }
+            self.term(arg, tab);
+            self.push(", ");
+          }
+          self.push("{");
+          self.newline(tab + 2);
+          self.push("when ");
+          self.term(cond, tab + 2);
+          self.push(":");
+          self.newline(tab + 4);
+          self.term(step, tab + 4);
+          self.push(";");
+          self.newline(tab + 2);
+          self.push("else:");
+          self.newline(tab + 4);
+          self.term(base, tab + 4);
+          self.newline(tab);
+          self.push("}");
+        }
+        Term::Def { .. } => self.push(term.display_pretty(tab)),
+        // Can't be broken
+        Term::Lam { .. }
+        | Term::Var { .. }
+        | Term::Link { .. }
+        | Term::Num { .. }
+        | Term::Nat { .. }
+        | Term::Str { .. }
+        | Term::Ref { .. }
+        | Term::Era
+        | Term::Err => self.push(term),
+      }
+    })
+  }
+
+  /// Displays a `let`-like term, with its next term in a new line.
+  fn binding(&mut self, head: impl fmt::Display, val: &Term, nxt: &Term, tab: usize) {
+    self.push(head);
+    self.term(val, tab);
+    self.push(";");
+    self.newline(tab);
+    self.term(nxt, tab);
+  }
+
+  fn match_header(
+    &mut self,
+    keyword: &str,
+    bnd: &Option<Name>,
+    arg: &Term,
+    with_bnd: &[Option<Name>],
+    with_arg: &[Term],
+    tab: usize,
+  ) {
+    self.push(display!("{keyword} "));
+    if let Some(bnd) = bnd {
+      self.push(display!("{bnd} = "));
+    }
+    self.term(arg, tab + 2);
+    self.push(" ");
+    if !with_bnd.is_empty() {
+      self.push("with ");
+      for (bnd, arg) in with_bnd.iter().zip(with_arg) {
+        self.push(display!("{} = ", var_as_str(bnd)));
+        self.term(arg, tab + 2);
+        self.push(", ");
+      }
+    }
+    self.push("{");
+  }
+
+  /// Displays each element in its own line, indented by one more level.
+  fn elements<'a>(&mut self, els: impl IntoIterator<Item = &'a Term>, sep: &str, tab: usize) {
+    for (i, el) in els.into_iter().enumerate() {
+      if i != 0 {
+        self.push(sep);
+      }
+      self.newline(tab + 2);
+      self.term(el, tab + 2);
+    }
+  }
+
+  /// Checks if the term can be displayed in the rest of the line.
+  /// Stops displaying it as soon as it doesn't fit, so big terms are never displayed in full.
+  fn fits(&self, term: &Term) -> bool {
+    let mut room = LineRoom(self.width.checked_sub(self.col));
+    fmt::write(&mut room, format_args!("{term}")).is_ok() && room.0.is_some()
+  }
+
+  fn push(&mut self, text: impl fmt::Display) {
+    let start = self.out.len();
+    fmt::write(&mut self.out, format_args!("{text}")).unwrap();
+    let pushed = &self.out[start..];
+    self.col = match pushed.rfind('\n') {
+      Some(line_start) => pushed[line_start + 1..].chars().count(),
+      None => self.col + pushed.chars().count(),
+    };
+  }
+
+  fn newline(&mut self, tab: usize) {
+    self.out.push('\n');
+    self.out.extend(std::iter::repeat(' ').take(tab));
+    self.col = tab;
+  }
+}
+
+/// A writer that fails once more characters than the ones left are written to it.
+struct LineRoom(Option<usize>);
+
+impl fmt::Write for LineRoom {
+  fn write_str(&mut self, s: &str) -> fmt::Result {
+    self.0 = self.0.and_then(|left| left.checked_sub(s.chars().count()));
+    self.0.map(|_| ()).ok_or(fmt::Error)
+  }
+}
+
+#[test]
+fn pretty_breaks_at_width() {
+  use crate::fun::load_book::parse_test_term;
+
+  let term = parse_test_term(
+    "let x = (foo (bar 1 2) (baz 3)); match x { List/Nil: 0; List/Cons: (+ x.head (sum x.tail)) }",
+  );
+  assert_eq!(term.pretty(100), term.to_string());
+  assert_eq!(
+    term.pretty(40),
+    "let x = (foo (bar 1 2) (baz 3));
+match x = x {
+  List/Nil: 0;
+  List/Cons: (+ x.head (sum x.tail));
+}"
+  );
+  assert_eq!(
+    term.pretty(16),
+    "let x = (foo
+  (bar 1 2)
+  (baz 3));
+match x = x {
+  List/Nil: 0;
+  List/Cons: (+
+      x.head
+      (sum
+        x.tail));
+}"
+  );
+  // Breaking lines doesn't change the term
+  for width in [0, 10, 20, 40] {
+    assert!(parse_test_term(&term.pretty(width)).alpha_eq(&term));
+  }
+}
+
+#[test]
+fn to_string_sorted_ignores_insertion_order() {
+  use crate::fun::load_book::do_parse_book_default;
+  let parse = |code: &str| do_parse_book_default(code, std::path::Path::new("sorted")).unwrap();
+
+  let a = parse("foo = 1\nbar x = (foo x)\nhvm native:\n  (a a)\nmain = bar");
+  let b = parse("main = bar\nhvm native:\n  (a a)\nbar x = (foo x)\nfoo = 1");
+  assert_ne!(a.to_string(), b.to_string());
+  assert_eq!(a.to_string_sorted(), b.to_string_sorted());
+  let sorted = a.to_string_sorted();
+  let pos = |def: &str| sorted.find(def).unwrap();
+  assert!(pos("(bar x) = (foo x)") < pos("(foo) = 1") && pos("(foo) = 1") < pos("(main) = bar"));
+}
+
+#[test]
+fn to_string_deeply_nested() {
+  use crate::fun::Name;
+  const DEPTH: usize = 100_000;
+
+  // A long `let` chain, nested on the right.
+  let mut lets = Term::var("x");
+  for _ in 0..DEPTH {
+    lets = Term::let_(Name::new("x"), Term::Era, lets);
+  }
+  let printed = lets.to_string();
+  assert!(printed.starts_with("let x = *; let x = *; "));
+  assert!(printed.ends_with("let x = *; x"));
+  assert_eq!(printed.len(), DEPTH * "let x = *; ".len() + 1);
+
+  // A long application, nested on the left and printed without parentheses.
+  let mut app = Term::var("f");
+  for _ in 0..DEPTH {
+    app = Term::app(app, Term::var("x"));
+  }
+  let printed = app.to_string();
+  assert_eq!(printed.len(), "(f)".len() + DEPTH * " x".len());
+  assert!(printed.starts_with("(f x x ") && printed.ends_with(" x x)"));
+  assert_eq!(app.display_pretty(0).to_string(), printed);
+}
+
+#[test]
+fn display_refs_by_name() {
+  use crate::fun::Name;
+  // References keep the name of their definition, so no name table is needed to print them.
+  let term = Term::app(Term::r#ref("List/length"), Term::Var { nam: Name::new("xs") });
+  assert_eq!(term.to_string(), "(List/length xs)");
+  assert_eq!(format!("{}", Term::r#ref("main")), "main");
+}
+
+#[test]
+fn debug_book_is_summarized() {
+  use crate::fun::load_book::do_parse_book;
+  let parse = |code: &str| {
+    let book = do_parse_book(code, std::path::Path::new("debug"), Default::default());
+    book.and_then(|book| book.to_fun()).unwrap()
+  };
+  let small = parse("type Bool = True | False\nmain = (foo 1)\nfoo x = x");
+  let big = parse(&format!(
+    "type Bool = True | False\nmain = (foo {})\nfoo x = x",
+    "(+ 1 ".repeat(1000) + "1" + &")".repeat(1000)
+  ));
+  let debug = format!("{small:?}");
+  assert_eq!(
+    debug,
+    r#"Book { defs: 2, hvm_defs: 0, adts: 1, entrypoint: None, def_names: [main, foo], .. }"#
+  );
+  assert_eq!(format!("{big:?}"), debug);
+}
+
+#[test]
+fn display_desugared_strings() {
+  let str = Term::encode_str("hi \"there\"\n");
+  assert_eq!(str.to_string(), r#""hi \"there\"\n""#);
+  assert_eq!(str.display_pretty(0).to_string(), str.to_string());
+  assert_eq!(
+    Term::app(Term::var("f"), str).pretty(4),
+    r#"(f
+  "hi \"there\"\n")"#
+  );
+
+  // Only fully desugared strings are printed as literals.
+  let open = Term::call(Term::r#ref(SCONS), [Term::num('a' as u32), Term::var("rest")]);
+  assert_eq!(open.to_string(), "(String/Cons 97 rest)");
+  assert_eq!(Term::r#ref(SNIL).to_string(), "String/Nil");
+}
@@ -1,12 +1,12 @@
 use super::{
   parser::{ParseBook, TermParser},
-  Book, Name,
+  Book, Definition, Name, Source, Term,
 };
 use crate::{
   diagnostics::{Diagnostics, DiagnosticsConfig},
   imports::PackageLoader,
 };
-use std::path::Path;
+use std::{collections::HashMap, path::Path};
 
 // TODO: Refactor so that we don't mix the two syntaxes here.
 
@@ -39,6 +39,40 @@ pub fn load_to_book(
   book.load_imports(package_loader, diag)
 }
 
+/// Like [`load_to_book`], but also expands templates once the book is loaded.
+///
+/// Each entry of `templates` turns the definition with that name into a [`Template`] with the
+/// given parameters, removing it from the book. Then, each entry of `instances` instantiates the
+/// template with that name with the given arguments. The names of the instances are returned
+/// in the same order.
+pub fn load_to_book_with_templates(
+  origin: &Path,
+  code: &str,
+  package_loader: impl PackageLoader,
+  diag: DiagnosticsConfig,
+  templates: &[(Name, Vec<Name>)],
+  instances: Vec<(Name, Vec<Term>)>,
+) -> Result<(Book, Vec<Name>), Diagnostics> {
+  let mut book = load_to_book(origin, code, package_loader, diag)?;
+
+  let mut defined = HashMap::new();
+  for (nam, params) in templates {
+    let Some(def) = book.defs.shift_remove(nam) else {
+      return Err(format!("Template '{nam}' is not defined.").into());
+    };
+    defined.insert(nam, Template { def, params: params.clone() });
+  }
+
+  let mut names = vec![];
+  for (nam, args) in instances {
+    let Some(template) = defined.get(&nam) else {
+      return Err(format!("'{nam}' is not a template.").into());
+    };
+    names.push(book.instantiate_template(template, args)?);
+  }
+  Ok((book, names))
+}
+
 pub fn do_parse_book(code: &str, origin: &Path, mut book: ParseBook) -> Result<ParseBook, String> {
   book.source = Name::new(origin.to_string_lossy());
   TermParser::new(code).parse_book(book, false).map_err(|e| format!("In {} :\n{}", origin.display(), e))
@@ -47,3 +81,152 @@ pub fn do_parse_book(code: &str, origin: &Path, mut book: ParseBook) -> Result<P
 pub fn do_parse_book_default(code: &str, origin: &Path) -> Result<Book, String> {
   do_parse_book(code, origin, ParseBook::builtins())?.to_fun()
 }
+
+/// Parses a book for the tests, without the builtins, and then encodes its types and resolves
+/// its references, like the compiler does before the passes that need the definitions to be known.
+#[cfg(test)]
+pub(crate) fn parse_test_book(code: &str) -> Book {
+  let mut book =
+    do_parse_book(code, Path::new("test"), ParseBook::default()).and_then(|b| b.to_fun()).unwrap();
+  let mut ctx = super::Ctx::new(&mut book, DiagnosticsConfig::default());
+  ctx.book.encode_adts(crate::AdtEncoding::NumScott);
+  ctx.resolve_refs().unwrap();
+  book
+}
+
+/// Parses a term for the tests.
+#[cfg(test)]
+pub(crate) fn parse_test_term(code: &str) -> Term {
+  TermParser::new(code).parse_term().unwrap()
+}
+
+/// A definition with parameters that are replaced by concrete terms when it's instantiated,
+/// for generating many similar definitions at load time.
+///
+/// The parameters are free variables of the definition's rules.
+#[derive(Debug, Clone)]
+pub struct Template {
+  pub def: Definition,
+  pub params: Vec<Name>,
+}
+
+impl Book {
+  /// Adds a new definition to the book, that is a copy of the template with each parameter
+  /// replaced by the corresponding argument, and returns its name.
+  ///
+  /// Instances are named `{template}__T{n}`, using the first `n` that's not already defined.
+  /// The substitution is capture-avoiding: variables bound inside the template,
+  /// including the ones in rule patterns, are renamed if they would capture a free variable of an argument.
+  pub fn instantiate_template(&mut self, template: &Template, args: Vec<Term>) -> Result<Name, String> {
+    if args.len() != template.params.len() {
+      return Err(format!(
+        "Template '{}' expects {} arguments, but got {}.",
+        template.def.name,
+        template.params.len(),
+        args.len()
+      ));
+    }
+
+    let name = (0..)
+      .map(|n| Name::new(format!("{}__T{n}", template.def.name)))
+      .find(|name| !self.defs.contains_key(name) && !self.hvm_defs.contains_key(name))
+      .unwrap();
+
+    // The parameters are first replaced by placeholders, so that an argument
+    // with a free variable named like another parameter doesn't get substituted again.
+    let placeholders = (0..args.len()).map(|i| Name::new(format!("%tmpl{i}"))).collect::<Vec<_>>();
+    let mut rules = template.def.rules.clone();
+    for rule in rules.iter_mut() {
+      for (param, placeholder) in template.params.iter().zip(&placeholders) {
+        if rule.pats.iter().flat_map(|pat| pat.binds()).flatten().any(|bind| bind == param) {
+          continue;
+        }
+        let placeholder = Term::Var { nam: placeholder.clone() };
+        rule.body.subst(param, &placeholder);
+        if let Some(guard) = &mut rule.guard {
+          guard.subst(param, &placeholder);
+        }
+      }
+
+      // Pattern variables can't be renamed by `subst`, so rename the ones that would capture here.
+      let free_vars = args.iter().flat_map(|arg| arg.free_vars().into_keys()).collect::<Vec<_>>();
+      for (i, bind) in rule.pats.iter_mut().flat_map(|pat| pat.binds_mut()).enumerate() {
+        let Some(nam) = bind else { continue };
+        if free_vars.contains(nam) {
+          let fresh = Name::new(format!("{nam}%{i}"));
+          let var = Term::Var { nam: fresh.clone() };
+          rule.body.subst(nam, &var);
+          if let Some(guard) = &mut rule.guard {
+            guard.subst(nam, &var);
+          }
+          *nam = fresh;
+        }
+      }
+
+      for (placeholder, arg) in placeholders.iter().zip(&args) {
+        rule.body.subst(placeholder, arg);
+        if let Some(guard) = &mut rule.guard {
+          guard.subst(placeholder, arg);
+        }
+      }
+    }
+
+    let source = if template.def.is_builtin() { Source::Builtin } else { Source::Generated };
+    self.defs.insert(name.clone(), Definition { name: name.clone(), rules, source });
+    Ok(name)
+  }
+}
+
+#[test]
+fn instantiate_template() {
+  let mut book = do_parse_book_default(
+    "
+add_k n = (+ n K)
+(apply_f x) = (F x)
+main = *
+",
+    Path::new("templates"),
+  )
+  .unwrap();
+  let mut template = |nam: &str, param: &str| Template {
+    def: book.defs.shift_remove(&Name::new(nam)).unwrap(),
+    params: vec![Name::new(param)],
+  };
+  let add_k = template("add_k", "K");
+  let apply_f = template("apply_f", "F");
+
+  let fst = book.instantiate_template(&add_k, vec![Term::num(1)]).unwrap();
+  let snd = book.instantiate_template(&add_k, vec![Term::num(2)]).unwrap();
+  assert_eq!((fst.as_ref(), snd.as_ref()), ("add_k__T0", "add_k__T1"));
+  assert_eq!(book.defs[&fst].rules[0].display(&fst).to_string(), "(add_k__T0 n) = (+ n 1)");
+  assert_eq!(book.defs[&snd].rules[0].display(&snd).to_string(), "(add_k__T1 n) = (+ n 2)");
+
+  // The pattern variable `x` is renamed so that it doesn't capture the argument.
+  let name = book.instantiate_template(&apply_f, vec![Term::var("x")]).unwrap();
+  assert_eq!(book.defs[&name].rules[0].display(&name).to_string(), "(apply_f__T0 x%0) = (x x%0)");
+
+  assert!(book.instantiate_template(&apply_f, vec![]).is_err());
+}
+
+#[test]
+fn load_templates() {
+  let code = "
+add_k n = (+ n K)
+main = *
+";
+  let path = Path::new("load_templates");
+  let load = |instances| {
+    let templates = [(Name::new("add_k"), vec![Name::new("K")])];
+    let loader = crate::imports::DefaultLoader::new(path);
+    load_to_book_with_templates(path, code, loader, DiagnosticsConfig::default(), &templates, instances)
+  };
+
+  let add_k = || Name::new("add_k");
+  let (book, names) = load(vec![(add_k(), vec![Term::num(1)]), (add_k(), vec![Term::num(2)])]).unwrap();
+  assert_eq!(names, [Name::new("add_k__T0"), Name::new("add_k__T1")]);
+  assert!(!book.defs.contains_key(&add_k()));
+  assert_eq!(book.defs[&names[0]].rules[0].display(&names[0]).to_string(), "(add_k__T0 n) = (+ n 1)");
+  assert_eq!(book.defs[&names[1]].rules[0].display(&names[1]).to_string(), "(add_k__T1 n) = (+ n 2)");
+
+  assert!(load(vec![(Name::new("main"), vec![])]).is_err());
+}
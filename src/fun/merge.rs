@@ -0,0 +1,161 @@
+use crate::fun::{Book, Name};
+use indexmap::IndexSet;
+use itertools::Itertools;
+
+/// What to do when a top-level name is defined by both of the books being merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+  /// Don't merge anything and return an error with all the conflicting names.
+  Error,
+  /// The definitions of the merged book replace the existing ones.
+  Override,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeError {
+  /// The names defined by both books.
+  Conflicts(Vec<Name>),
+  /// A constructor of the merged book belongs to a datatype that the book doesn't have.
+  MissingAdt { ctr: Name, adt: Name },
+}
+
+impl Book {
+  /// Adds the definitions, datatypes and constructors of another book to this one.
+  ///
+  /// Functions, native HVM definitions, datatypes and constructors share the same namespace,
+  /// so a name defined by both books is a conflict, handled according to `policy`.
+  /// Builtins are not conflicts, since every book has them.
+  ///
+  /// References are by name, so the terms of the merged book don't need to be changed.
+  /// If there's an error, this book is left unchanged.
+  pub fn merge(&mut self, other: Book, policy: MergePolicy) -> Result<(), MergeError> {
+    let conflicts = self.merge_conflicts(&other)?;
+    if !conflicts.is_empty() {
+      match policy {
+        MergePolicy::Error => return Err(MergeError::Conflicts(conflicts.into_iter().collect())),
+        MergePolicy::Override => {
+          for name in &conflicts {
+            self.remove_top_level(name);
+          }
+        }
+      }
+    }
+
+    let Book { defs, hvm_defs, adts, ctrs, entrypoint, imports } = other;
+    for (name, def) in defs {
+      if !(def.is_builtin() && self.defs.contains_key(&name)) {
+        self.defs.insert(name, def);
+      }
+    }
+    for (name, def) in hvm_defs {
+      if !(def.source.is_builtin() && self.hvm_defs.contains_key(&name)) {
+        self.hvm_defs.insert(name, def);
+      }
+    }
+    for (name, adt) in adts {
+      if !(adt.source.is_builtin() && self.adts.contains_key(&name)) {
+        self.adts.insert(name, adt);
+      }
+    }
+    for (ctr, adt) in ctrs {
+      self.ctrs.entry(ctr).or_insert(adt);
+    }
+    if self.entrypoint.is_none() {
+      self.entrypoint = entrypoint;
+    }
+    self.imports.extend(imports);
+    Ok(())
+  }
+
+  fn merge_conflicts(&self, other: &Book) -> Result<IndexSet<Name>, MergeError> {
+    let mut ctrs = vec![];
+    for (ctr, adt_name) in &other.ctrs {
+      let Some(adt) = other.adts.get(adt_name) else {
+        return Err(MergeError::MissingAdt { ctr: ctr.clone(), adt: adt_name.clone() });
+      };
+      if !adt.source.is_builtin() {
+        ctrs.push(ctr);
+      }
+    }
+    let defs = other.defs.values().filter(|def| !def.is_builtin()).map(|def| &def.name);
+    let hvm_defs = other.hvm_defs.values().filter(|def| !def.source.is_builtin()).map(|def| &def.name);
+    let adts = other.adts.iter().filter(|(_, adt)| !adt.source.is_builtin()).map(|(name, _)| name);
+    let names = defs.chain(hvm_defs).chain(adts).chain(ctrs);
+    Ok(names.filter(|name| self.has_top_level(name)).cloned().collect())
+  }
+
+  fn has_top_level(&self, name: &Name) -> bool {
+    self.defs.contains_key(name)
+      || self.hvm_defs.contains_key(name)
+      || self.adts.contains_key(name)
+      || self.ctrs.contains_key(name)
+  }
+
+  /// Removes everything with the given name.
+  /// Removing a datatype or one of its constructors removes the datatype with all of its constructors.
+  fn remove_top_level(&mut self, name: &Name) {
+    self.defs.shift_remove(name);
+    self.hvm_defs.shift_remove(name);
+    let adt_name =
+      if self.adts.contains_key(name) { Some(name.clone()) } else { self.ctrs.get(name).cloned() };
+    if let Some(adt) = adt_name.and_then(|adt_name| self.adts.shift_remove(&adt_name)) {
+      for ctr in adt.ctrs.keys() {
+        self.ctrs.shift_remove(ctr);
+        self.defs.shift_remove(ctr);
+      }
+    }
+  }
+}
+
+impl std::fmt::Display for MergeError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      MergeError::Conflicts(conflicts) => {
+        let names = conflicts.iter().map(|name| format!("'{name}'")).join(", ");
+        if conflicts.len() == 1 {
+          write!(f, "Can't merge books, {names} is defined in both of them.")
+        } else {
+          write!(f, "Can't merge books, {names} are defined in both of them.")
+        }
+      }
+      MergeError::MissingAdt { ctr, adt } => {
+        write!(f, "Can't merge books, the constructor '{ctr}' belongs to the missing datatype '{adt}'.")
+      }
+    }
+  }
+}
+
+impl std::error::Error for MergeError {}
+
+#[test]
+fn merge_books() {
+  use crate::fun::load_book::do_parse_book_default;
+  let parse = |code: &str| do_parse_book_default(code, std::path::Path::new("merge")).unwrap();
+
+  let mut book = parse("type Color = Red | Green\nmain = (double 2)");
+  let lib = parse("double x = (* x 2)\ntype Shape = (Circle r)");
+  book.merge(lib, MergePolicy::Error).unwrap();
+  assert!(book.defs.contains_key(&Name::new("double")) && book.defs.contains_key(&Name::new("main")));
+  assert_eq!(book.ctrs.get(&Name::new("Shape/Circle")), Some(&Name::new("Shape")));
+  assert_eq!(book.ctrs.get(&Name::new("Color/Red")), Some(&Name::new("Color")));
+
+  // Conflicting definitions are an error by default, leaving the book unchanged.
+  let other = parse("double x = (+ x x)\ntype Color = Blue\nunique = 1");
+  let err = book.merge(other.clone(), MergePolicy::Error).unwrap_err();
+  assert_eq!(err, MergeError::Conflicts(vec![Name::new("double"), Name::new("Color")]));
+  assert!(!book.defs.contains_key(&Name::new("unique")));
+
+  // A constructor without its datatype is an error instead of a panic.
+  let mut broken = parse("type Rose = (Leaf v)");
+  broken.adts.shift_remove(&Name::new("Rose"));
+  let err = book.merge(broken, MergePolicy::Error).unwrap_err();
+  assert_eq!(err, MergeError::MissingAdt { ctr: Name::new("Rose/Leaf"), adt: Name::new("Rose") });
+  assert!(!book.ctrs.contains_key(&Name::new("Rose/Leaf")));
+
+  // Or replace the existing ones.
+  book.merge(other, MergePolicy::Override).unwrap();
+  assert_eq!(book.defs[&Name::new("double")].rules[0].body.to_string(), "(+ x x)");
+  assert_eq!(book.adts[&Name::new("Color")].ctrs.keys().collect::<Vec<_>>(), [&Name::new("Color/Blue")]);
+  assert!(!book.ctrs.contains_key(&Name::new("Color/Red")));
+  assert!(book.defs.contains_key(&Name::new("unique")));
+}
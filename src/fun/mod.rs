@@ -8,21 +8,32 @@ use interner::global::{GlobalPool, GlobalString};
 use itertools::Itertools;
 use std::{
   borrow::Cow,
+  collections::{BTreeMap, HashMap, HashSet},
   hash::Hash,
   ops::{Deref, Range},
 };
 
+pub mod alpha_eq;
+pub mod alpha_hash;
 pub mod builtins;
+pub mod call_graph;
 pub mod check;
 pub mod display;
 pub mod load_book;
+pub mod merge;
 pub mod net_to_term;
 pub mod parser;
+pub mod reduce;
+pub mod rename;
+#[cfg(test)]
+mod round_trip;
 pub mod term_to_net;
 pub mod transform;
 
+use transform::unique_names::UniqueNameGenerator;
+
 pub use net_to_term::{net_to_term, ReadbackError};
-pub use term_to_net::{book_to_hvm, term_to_hvm};
+pub use term_to_net::{book_to_hvm, book_to_hvm_incremental, term_to_hvm};
 
 pub static STRINGS: GlobalPool<String> = GlobalPool::new();
 #[derive(Debug)]
@@ -32,13 +43,13 @@ pub struct Ctx<'book> {
 }
 
 impl Ctx<'_> {
-  pub fn new(book: &mut Book, diagnostics_cfg: DiagnosticsConfig) -> Ctx {
+  pub fn new(book: &mut Book, diagnostics_cfg: DiagnosticsConfig) -> Ctx<'_> {
     Ctx { book, info: Diagnostics::new(diagnostics_cfg) }
   }
 }
 
 /// The representation of a program.
-#[derive(Debug, Clone, Default)]
+#[derive(Clone, Default)]
 pub struct Book {
   /// Function definitions.
   pub defs: Definitions,
@@ -96,6 +107,9 @@ pub struct HvmDefinition {
 pub struct Rule {
   pub pats: Vec<Pattern>,
   pub body: Term,
+  /// A condition that must be true for this rule to be chosen, like in `(Foo x) | (> x 0) = ...`.
+  /// Removed by [transform::desugar_guards].
+  pub guard: Option<Term>,
 }
 
 #[derive(Debug, Default, PartialEq, Eq, Hash)]
@@ -453,14 +467,49 @@ impl From<Option<Name>> for Pattern {
 impl Term {
   /* Common construction patterns */
 
-  /// Lambda with a static tag
-  pub fn lam(pat: Pattern, bod: Term) -> Self {
+  /// Lambda with a static tag.
+  ///
+  /// Together with the other constructors below, terms can be built without boxing each child.
+  /// Anywhere a pattern is expected, a name can be given to bind a variable.
+  ///
+  /// ```rust
+  /// use bend::fun::{Name, Op, Term};
+  ///
+  /// let x = || Term::var("x");
+  /// let term = Term::lam(
+  ///   Name::new("x"),
+  ///   Term::let_(Name::new("y"), Term::oper(Op::ADD, x(), Term::num(1)), Term::tup([x(), Term::var("y")])),
+  /// );
+  /// assert_eq!(term.to_string(), "λx let y = (+ x 1); (x, y)");
+  /// ```
+  pub fn lam(pat: impl Into<Pattern>, bod: Term) -> Self {
     Self::tagged_lam(Tag::Static, pat, bod)
   }
 
   /// Lambda with any tag
-  pub fn tagged_lam(tag: Tag, pat: Pattern, bod: Term) -> Self {
-    Term::Lam { tag, pat: Box::new(pat), bod: Box::new(bod) }
+  pub fn tagged_lam(tag: Tag, pat: impl Into<Pattern>, bod: Term) -> Self {
+    Term::Lam { tag, pat: Box::new(pat.into()), bod: Box::new(bod) }
+  }
+
+  pub fn var(nam: impl Into<Name>) -> Self {
+    Term::Var { nam: nam.into() }
+  }
+
+  /// An unsigned 24-bit number.
+  pub fn num(val: u32) -> Self {
+    Term::Num { val: Num::U24(val) }
+  }
+
+  pub fn let_(pat: impl Into<Pattern>, val: Term, nxt: Term) -> Self {
+    Term::Let { pat: Box::new(pat.into()), val: Box::new(val), nxt: Box::new(nxt) }
+  }
+
+  pub fn oper(opr: Op, fst: Term, snd: Term) -> Self {
+    Term::Oper { opr, fst: Box::new(fst), snd: Box::new(snd) }
+  }
+
+  pub fn tup(els: impl IntoIterator<Item = Term>) -> Self {
+    Term::Fan { fan: FanKind::Tup, tag: Tag::Static, els: els.into_iter().collect() }
   }
 
   /// Wraps a term in lambdas, so that the outermost lambda is the first given element.
@@ -516,6 +565,57 @@ impl Term {
     }
   }
 
+  /// Negates a term by subtracting it from zero.
+  /// The result has the type of `arg`, so negating an unsigned number wraps around.
+  ///
+  /// Signed and float literals are negated directly, and terms that are known to be
+  /// floats are subtracted from a float zero, so that the operation doesn't mix number types.
+  pub fn negate(arg: Term) -> Term {
+    match arg {
+      Term::Num { val: Num::I24(val) } if val != -0x00800000 => Term::Num { val: Num::I24(-val) },
+      Term::Num { val: Num::F24(val) } => Term::Num { val: Num::F24(-val) },
+      arg => {
+        let zero = if arg.is_float() { Num::F24(0.0) } else { Num::U24(0) };
+        Term::Oper { opr: Op::SUB, fst: Box::new(Term::Num { val: zero }), snd: Box::new(arg) }
+      }
+    }
+  }
+
+  /// Checks if the term is known to be a float: a float literal, or an arithmetic operation on one.
+  /// Comparisons are never floats, since they give an integer.
+  pub fn is_float(&self) -> bool {
+    match self {
+      Term::Num { val } => matches!(val, Num::F24(_)),
+      Term::Oper { opr, fst, snd } => !opr.is_comparison() && (fst.is_float() || snd.is_float()),
+      _ => false,
+    }
+  }
+
+  /// Flips all the bits of an integer, by xor-ing it with a number with all 24 bits set.
+  /// Integer literals are flipped directly.
+  pub fn bitwise_not(arg: Term) -> Term {
+    match arg {
+      Term::Num { val: Num::U24(val) } => Term::Num { val: Num::U24(!val & 0xFFFFFF) },
+      Term::Num { val: Num::I24(val) } => Term::Num { val: Num::I24(!val) },
+      // Like in `negate`, the argument is the second operand, since it decides the type of the result.
+      arg => {
+        Term::Oper { opr: Op::XOR, fst: Box::new(Term::Num { val: Num::U24(0xFFFFFF) }), snd: Box::new(arg) }
+      }
+    }
+  }
+
+  /// If this term is a bitwise not built by [`Term::bitwise_not`], returns its argument.
+  pub fn as_bitwise_not(&self) -> Option<&Term> {
+    match self {
+      Term::Oper { opr: Op::XOR, fst, snd }
+        if matches!(fst.as_ref(), Term::Num { val: Num::U24(0xFFFFFF) }) =>
+      {
+        Some(snd)
+      }
+      _ => None,
+    }
+  }
+
   pub fn add_num(arg: Term, val: Num) -> Term {
     if val.is_zero() {
       arg
@@ -539,6 +639,10 @@ impl Term {
   }
 
   /* Iterators */
+
+  /// The direct subterms of this term, in the order they appear in the source.
+  ///
+  /// The rules of a local definition are not included, only its next term.
   pub fn children(&self) -> impl DoubleEndedIterator<Item = &Term> + Clone {
     multi_iterator!(ChildrenIter { Zero, One, Two, Vec, Mat, Swt, Bend, Fold });
     match self {
@@ -611,6 +715,14 @@ impl Term {
     }
   }
 
+  /// Rebuilds this term with each of its direct subterms replaced by the result of `f`.
+  pub fn map_children(mut self, mut f: impl FnMut(Term) -> Term) -> Term {
+    for child in self.children_mut() {
+      *child = f(std::mem::take(child));
+    }
+    self
+  }
+
   /// An iterator over the subterms with an iterator over the binds
   /// introduced by the current term for each subterm.
   ///
@@ -747,8 +859,8 @@ impl Term {
 
   /// Substitute the occurrences of a variable in a term with the given term.
   ///
-  /// Caution: can cause invalid shadowing of variables if used incorrectly.
-  /// Ex: Using subst to beta-reduce `(@a @b a b)` converting it into `@b b`.
+  /// Binders that would capture a free variable of `to` are renamed to fresh names first.
+  /// Ex: Using subst to beta-reduce `(@a @b a b)` gives `@c b` instead of `@b b`.
   ///
   /// NOTE: Expects var bind information to be properly stored in match expressions,
   /// so it must run AFTER `fix_match_terms`.
@@ -756,19 +868,64 @@ impl Term {
   /// NOTE: Since it doesn't (can't) handle `with` clauses in match terms,
   /// it must be run only AFTER `with` linearization.
   pub fn subst(&mut self, from: &Name, to: &Term) {
-    maybe_grow(|| {
-      for (child, binds) in self.children_mut_with_binds() {
+    let to_free_vars = to.free_vars();
+    if self.would_capture(from, &to_free_vars) {
+      let mut used = self.var_names();
+      used.extend(to_free_vars.keys().cloned());
+      let to_rename = to_free_vars.into_keys().collect();
+      UniqueNameGenerator::renaming(to_rename, used).unique_names_in_term(self);
+    }
+    self.subst_unchecked(from, to);
+  }
+
+  /// Substitute the occurrences of a variable in a term with the given term,
+  /// without checking if any variables of `to` get captured.
+  pub fn subst_unchecked(&mut self, from: &Name, to: &Term) {
+    // Like `visit_mut`, but skipping the children where `from` is shadowed.
+    // Variables have no children, so the substituted terms are never visited again.
+    let mut to_visit = vec![self];
+    while let Some(term) = to_visit.pop() {
+      if let Term::Var { nam } = term {
+        if nam == from {
+          *term = to.clone();
+        }
+        continue;
+      }
+      for (child, binds) in term.children_mut_with_binds().rev() {
         if !binds.flat_map(|b| b.as_ref()).contains(from) {
-          child.subst(from, to);
+          to_visit.push(child);
         }
       }
-    });
+    }
+  }
 
-    if let Term::Var { nam } = self {
-      if nam == from {
-        *self = to.clone();
-      }
+  /// Checks if any free occurrence of `from` is under a binder with one of the given names.
+  fn would_capture(&self, from: &Name, names: &IndexMap<Name, u64>) -> bool {
+    let mut would_capture = false;
+    if !names.is_empty() {
+      self.visit_with_binds(|term, scope| {
+        if let Term::Var { nam } = term {
+          if nam == from && !scope.contains_key(from) {
+            would_capture |= names.keys().any(|nam| scope.contains_key(nam));
+          }
+        }
+      });
     }
+    would_capture
+  }
+
+  /// Collects the names of all the variables used or bound in a term.
+  fn var_names(&self) -> HashSet<Name> {
+    let mut names = HashSet::new();
+    self.visit(|term| {
+      if let Term::Var { nam } = term {
+        names.insert(nam.clone());
+      }
+      for (_, binds) in term.children_with_binds() {
+        names.extend(binds.flatten().cloned());
+      }
+    });
+    names
   }
 
   /// Substitute the occurrences of a constructor name with the given name.
@@ -789,10 +946,8 @@ impl Term {
           }
         }
       }
-      Term::Open { typ, .. } => {
-        if typ == from {
-          *typ = to.clone();
-        }
+      Term::Open { typ, .. } if typ == from => {
+        *typ = to.clone();
       }
       _ => (),
     }
@@ -816,30 +971,45 @@ impl Term {
   }
 
   /// Collects all the free variables that a term has
-  /// and the number of times each var is used
+  /// and the number of times each var is used.
+  ///
+  /// Unscoped variables are not included, since they're not bound by scope.
+  /// Use `unscoped_vars` for them instead.
   pub fn free_vars(&self) -> IndexMap<Name, u64> {
-    fn go_term(term: &Term, free_vars: &mut IndexMap<Name, u64>) {
-      maybe_grow(|| {
-        if let Term::Var { nam } = term {
+    let mut free_vars = IndexMap::new();
+    self.visit_with_binds(|term, scope| {
+      if let Term::Var { nam } = term {
+        if !scope.contains_key(nam) {
           *free_vars.entry(nam.clone()).or_default() += 1;
         }
+      }
+    });
+    free_vars
+  }
 
-        for (child, binds) in term.children_with_binds() {
-          let mut new_scope = Default::default();
-          go_term(child, &mut new_scope);
-
-          for nam in binds.flatten() {
-            new_scope.shift_remove(nam);
-          }
-
-          free_vars.extend(new_scope);
-        }
-      })
-    }
+  /// The number of free occurrences of the variable `nam` in this term.
+  ///
+  /// Occurrences under a binder of the same name, like a lambda, a `let` or a match arm,
+  /// are not counted, since they refer to that binder instead.
+  /// Unscoped variables are counted separately, with `count_link_occurrences`.
+  pub fn count_occurrences(&self, nam: &Name) -> usize {
+    let mut count = 0;
+    self.visit_with_binds(|term, scope| {
+      if let Term::Var { nam: var } = term {
+        count += (var == nam && !scope.contains_key(nam)) as usize;
+      }
+    });
+    count
+  }
 
-    let mut free_vars = Default::default();
-    go_term(self, &mut free_vars);
-    free_vars
+  /// The number of uses of the unscoped variable `nam` in this term.
+  ///
+  /// Unlike [`Term::count_occurrences`], there's no shadowing, since unscoped variables are global.
+  pub fn count_link_occurrences(&self, nam: &Name) -> usize {
+    maybe_grow(|| match self {
+      Term::Link { nam: var } => (var == nam) as usize,
+      _ => self.children().map(|child| child.count_link_occurrences(nam)).sum(),
+    })
   }
 
   /// Returns the set of declared and the set of used unscoped variables
@@ -892,6 +1062,234 @@ impl Term {
       has_unscoped
     })
   }
+
+  /// The number of nodes in this term, including the ones in the rules of local definitions.
+  pub fn size(&self) -> usize {
+    maybe_grow(|| match self {
+      Term::Def { def, nxt } => 1 + def.size() + nxt.size(),
+      _ => 1 + self.children().map(Term::size).sum::<usize>(),
+    })
+  }
+
+  /// A rough estimate of how many interactions it takes to reduce this term,
+  /// counting its nodes weighted by how costly they usually are at runtime.
+  ///
+  /// This is only a heuristic, useful to compare definitions but not a guarantee.
+  /// The actual number of interactions depends on the arguments, on how many times the
+  /// recursive calls and the loops of `fold` and `bend` are unrolled, and on the order of reduction.
+  pub fn estimate_interactions(&self) -> usize {
+    maybe_grow(|| {
+      let own = match self {
+        Term::Var { .. } | Term::Link { .. } | Term::Num { .. } | Term::Era | Term::Err => 0,
+        Term::Lam { .. } | Term::Use { .. } | Term::With { .. } | Term::Open { .. } | Term::Def { .. } => 0,
+        Term::Let { pat, .. } => match pat.as_ref() {
+          Pattern::Fan(FanKind::Dup, ..) => 3,
+          Pattern::Var(_) | Pattern::Chn(_) => 0,
+          _ => 1,
+        },
+        // Superpositions and duplications commute through whatever they meet, so they're the most expensive.
+        Term::Fan { fan: FanKind::Dup, .. } => 3,
+        Term::Fan { fan: FanKind::Tup, .. } | Term::App { .. } | Term::Ref { .. } => 1,
+        Term::Ask { .. } | Term::Oper { .. } => 2,
+        Term::Nat { val } => *val as usize,
+        Term::Str { val } => val.chars().count(),
+        Term::List { els } => els.len(),
+        Term::Mat { arms, .. } | Term::Fold { arms, .. } => 2 + arms.len(),
+        Term::Swt { arms, .. } => 2 + arms.len(),
+        Term::Bend { .. } => 4,
+      };
+      match self {
+        Term::Def { def, nxt } => own + def.estimate_interactions() + nxt.estimate_interactions(),
+        _ => own + self.children().map(Term::estimate_interactions).sum::<usize>(),
+      }
+    })
+  }
+
+  /// The number of nodes in the longest path from this term to one of its leaves.
+  pub fn depth(&self) -> usize {
+    maybe_grow(|| match self {
+      Term::Def { def, nxt } => {
+        let rules_depth = def.rules.iter().map(|rule| rule.body.depth()).max().unwrap_or(0);
+        1 + rules_depth.max(nxt.depth())
+      }
+      _ => 1 + self.children().map(Term::depth).max().unwrap_or(0),
+    })
+  }
+
+  /// Computes a value for this term from the values of its children, bottom-up.
+  ///
+  /// `f` receives each term together with the results of its children,
+  /// in the same order as [`Term::children`]. Like it, doesn't go inside local definitions.
+  ///
+  /// ```rust
+  /// use bend::fun::{Name, Op, Term};
+  ///
+  /// let x = || Term::var("x");
+  /// let term = Term::lam(Name::new("x"), Term::oper(Op::MUL, x(), Term::oper(Op::ADD, x(), Term::num(1))));
+  /// let count = term.fold(&mut |_, children: Vec<usize>| 1 + children.iter().sum::<usize>());
+  /// assert_eq!(count, 6);
+  /// ```
+  pub fn fold<T>(&self, f: &mut impl FnMut(&Term, Vec<T>) -> T) -> T {
+    // Like in `visit_mut_post`, each entry has a term, its children that still must be folded,
+    // in reverse order, and the results of the ones that already were.
+    fn new_entry<T>(term: &Term) -> (&Term, Vec<&Term>, Vec<T>) {
+      (term, term.children().rev().collect(), vec![])
+    }
+    let mut stack = vec![new_entry(self)];
+    loop {
+      let (_, to_fold, _) = stack.last_mut().unwrap();
+      if let Some(child) = to_fold.pop() {
+        stack.push(new_entry(child));
+        continue;
+      }
+      let (term, _, children) = stack.pop().unwrap();
+      let res = f(term, children);
+      match stack.last_mut() {
+        Some((_, _, children)) => children.push(res),
+        None => return res,
+      }
+    }
+  }
+
+  /// Calls `f` on this term and each of its subterms, parents before their children,
+  /// in the same order as [`Term::children`].
+  ///
+  /// Uses an explicit stack instead of recursion, so it works for arbitrarily deep terms.
+  /// Like [`Term::children`], doesn't go inside local definitions.
+  pub fn visit(&self, mut f: impl FnMut(&Term)) {
+    let mut to_visit = vec![self];
+    while let Some(term) = to_visit.pop() {
+      f(term);
+      to_visit.extend(term.children().rev());
+    }
+  }
+
+  /// Like [`Term::visit`], but also passes to `f` the variables bound around each term,
+  /// with how many of the enclosing binders have each name.
+  ///
+  /// A variable is free in the visited term exactly when its name isn't in the scope.
+  pub fn visit_with_binds<'a>(&'a self, mut f: impl FnMut(&'a Term, &HashMap<&'a Name, usize>)) {
+    enum Step<'a> {
+      Visit(&'a Term),
+      Bind(Vec<&'a Name>),
+      Unbind(Vec<&'a Name>),
+    }
+    let mut scope = HashMap::new();
+    let mut steps = vec![Step::Visit(self)];
+    while let Some(step) = steps.pop() {
+      match step {
+        Step::Visit(term) => {
+          f(term, &scope);
+          for (child, binds) in term.children_with_binds().rev() {
+            let binds = binds.flatten().collect::<Vec<_>>();
+            steps.push(Step::Unbind(binds.clone()));
+            steps.push(Step::Visit(child));
+            steps.push(Step::Bind(binds));
+          }
+        }
+        Step::Bind(binds) => {
+          for nam in binds {
+            *scope.entry(nam).or_default() += 1;
+          }
+        }
+        Step::Unbind(binds) => {
+          for nam in binds {
+            let count = scope.get_mut(nam).unwrap();
+            *count -= 1;
+            if *count == 0 {
+              scope.remove(nam);
+            }
+          }
+        }
+      }
+    }
+  }
+
+  /// Like [`Term::visit`], but with mutable access to each term.
+  ///
+  /// Since parents are visited first, the children of a term are the ones it has after `f` returns.
+  pub fn visit_mut(&mut self, mut f: impl FnMut(&mut Term)) {
+    let mut to_visit = vec![self];
+    while let Some(term) = to_visit.pop() {
+      f(term);
+      to_visit.extend(term.children_mut().rev());
+    }
+  }
+
+  /// Like [`Term::visit_mut`], but calls `f` on the children of a term before the term itself.
+  ///
+  /// To keep `&mut` access without recursion, the subterms are temporarily taken out of
+  /// their parents while being visited, so `f` doesn't see them inside the original term.
+  pub fn visit_mut_post(&mut self, mut f: impl FnMut(&mut Term)) {
+    // Each entry has a term, its children that still must be visited, in reverse order,
+    // and the ones that were already visited.
+    let new_entry = |mut term: Term| {
+      let mut to_visit = term.children_mut().map(std::mem::take).collect::<Vec<_>>();
+      to_visit.reverse();
+      (term, to_visit, vec![])
+    };
+    let mut stack = vec![new_entry(std::mem::take(self))];
+    while let Some((_, to_visit, _)) = stack.last_mut() {
+      if let Some(child) = to_visit.pop() {
+        stack.push(new_entry(child));
+        continue;
+      }
+      let (mut term, _, visited) = stack.pop().unwrap();
+      for (child, visited) in term.children_mut().zip(visited) {
+        *child = visited;
+      }
+      f(&mut term);
+      match stack.last_mut() {
+        Some((_, _, visited)) => visited.push(term),
+        None => *self = term,
+      }
+    }
+  }
+}
+
+impl Op {
+  /// Whether the operation compares its operands, giving `0` or `1`.
+  pub fn is_comparison(&self) -> bool {
+    matches!(self, Op::EQ | Op::NEQ | Op::LT | Op::GT | Op::LE | Op::GE)
+  }
+}
+
+impl TryFrom<&str> for Op {
+  type Error = String;
+
+  /// Reads an operator as it's written by its `Display`, like `+` or `<<`.
+  ///
+  /// `LOG` and `ATN` have no textual form, so they can't be read back.
+  fn try_from(opr: &str) -> Result<Self, Self::Error> {
+    match opr {
+      "+" => Ok(Op::ADD),
+      "-" => Ok(Op::SUB),
+      "*" => Ok(Op::MUL),
+      "/" => Ok(Op::DIV),
+      "%" => Ok(Op::REM),
+      "==" => Ok(Op::EQ),
+      "!=" => Ok(Op::NEQ),
+      "<" => Ok(Op::LT),
+      ">" => Ok(Op::GT),
+      "&" => Ok(Op::AND),
+      "|" => Ok(Op::OR),
+      "^" => Ok(Op::XOR),
+      "**" => Ok(Op::POW),
+      ">>" => Ok(Op::SHR),
+      "<<" => Ok(Op::SHL),
+      "<=" => Ok(Op::LE),
+      ">=" => Ok(Op::GE),
+      _ => Err(format!("Unknown operator '{opr}'.")),
+    }
+  }
+}
+
+impl std::str::FromStr for Op {
+  type Err = String;
+
+  fn from_str(opr: &str) -> Result<Self, Self::Err> {
+    Op::try_from(opr)
+  }
 }
 
 impl Num {
@@ -935,6 +1333,13 @@ impl PartialEq for Num {
 
 impl Eq for Num {}
 
+/// A pattern that binds a variable with the given name.
+impl From<Name> for Pattern {
+  fn from(nam: Name) -> Self {
+    Pattern::Var(Some(nam))
+  }
+}
+
 impl Pattern {
   pub fn binds(&self) -> impl DoubleEndedIterator<Item = &Option<Name>> + Clone {
     self.iter().filter_map(|pat| match pat {
@@ -1001,7 +1406,8 @@ impl Pattern {
       Pattern::Fan(fan, tag, args) => {
         Term::Fan { fan: *fan, tag: tag.clone(), els: args.iter().map(|p| p.to_term()).collect() }
       }
-      Pattern::Lst(_) | Pattern::Str(_) => todo!(),
+      Pattern::Lst(els) => Term::List { els: els.iter().map(|p| p.to_term()).collect() },
+      Pattern::Str(val) => Term::Str { val: val.clone() },
     }
   }
 
@@ -1044,12 +1450,42 @@ impl Definition {
     assert!(self.rules[0].pats.is_empty(), "Definition args should have been removed in an earlier pass");
   }
 
+  /// Like [`Definition::assert_no_pattern_matching_rules`], but returns an error
+  /// instead of panicking, for the definitions that come from outside the compiler.
+  pub fn check_no_pattern_matching_rules(&self) -> Result<&Rule, String> {
+    match &self.rules[..] {
+      [rule] if rule.pats.is_empty() => Ok(rule),
+      _ => Err(format!(
+        "Definition '{}' has pattern matching rules, which should have been desugared before compiling it.",
+        self.name
+      )),
+    }
+  }
+
   #[track_caller]
   pub fn rule(&self) -> &Rule {
     self.assert_no_pattern_matching_rules();
     &self.rules[0]
   }
 
+  /// The total number of nodes in the bodies and guards of the rules of this definition.
+  pub fn size(&self) -> usize {
+    self.rules.iter().map(|rule| rule.body.size() + rule.guard.as_ref().map_or(0, Term::size)).sum()
+  }
+
+  /// The estimated interactions of the bodies and guards of the rules of this definition, as given by [`Term::estimate_interactions`].
+  /// Rules that pattern match are counted like the `match` they become.
+  pub fn estimate_interactions(&self) -> usize {
+    let guard = |rule: &Rule| rule.guard.as_ref().map_or(0, Term::estimate_interactions);
+    let rules = self.rules.iter().map(|rule| rule.body.estimate_interactions() + guard(rule)).sum::<usize>();
+    let is_match = |pat: &Pattern| !matches!(pat, Pattern::Var(_) | Pattern::Chn(_));
+    if self.rules.len() > 1 || self.rules.iter().any(|rule| rule.pats.iter().any(is_match)) {
+      rules + 2 + self.rules.len()
+    } else {
+      rules
+    }
+  }
+
   #[track_caller]
   pub fn rule_mut(&mut self) -> &mut Rule {
     self.assert_no_pattern_matching_rules();
@@ -1096,6 +1532,18 @@ impl From<u32> for Name {
   }
 }
 
+impl From<&str> for Name {
+  fn from(value: &str) -> Self {
+    Name::new(value)
+  }
+}
+
+impl From<String> for Name {
+  fn from(value: String) -> Self {
+    Name::new(value)
+  }
+}
+
 impl Deref for Name {
   type Target = str;
 
@@ -1117,6 +1565,31 @@ impl Book {
       Some(nam) => nam,
     }
   }
+
+  /// The size of each definition, as given by [`Definition::size`].
+  pub fn size_report(&self) -> BTreeMap<Name, usize> {
+    self.defs.iter().map(|(name, def)| (name.clone(), def.size())).collect()
+  }
+
+  /// The estimated interactions of each definition, as given by [`Definition::estimate_interactions`].
+  ///
+  /// Like the estimate itself, this is only meant to spot the definitions that are likely to be expensive.
+  pub fn estimate_interactions(&self) -> BTreeMap<Name, usize> {
+    self.defs.iter().map(|(name, def)| (name.clone(), def.estimate_interactions())).collect()
+  }
+
+  /// Iterates over the definitions in alphabetical order of their names,
+  /// instead of the order they were added to the book.
+  pub fn defs_by_name(&self) -> impl Iterator<Item = (&Name, &Definition)> {
+    self.defs.iter().sorted_by(|(a, _), (b, _)| a.cmp(b))
+  }
+
+  /// Like [`Book::defs_by_name`], but with mutable access to the definitions.
+  ///
+  /// Since the book is borrowed, definitions can't be added, removed or renamed while iterating.
+  pub fn defs_by_name_mut(&mut self) -> impl Iterator<Item = (&Name, &mut Definition)> {
+    self.defs.iter_mut().sorted_by(|(a, _), (b, _)| a.cmp(b))
+  }
 }
 
 impl Source {
@@ -1127,6 +1600,14 @@ impl Source {
   pub fn is_local(&self) -> bool {
     matches!(self, Source::Local(..))
   }
+
+  /// The location in the source file, for the items that have one.
+  pub fn span(&self) -> Option<Range<usize>> {
+    match self {
+      Source::Local(span) => Some(span.clone()),
+      _ => None,
+    }
+  }
 }
 
 #[test]
@@ -1160,3 +1641,329 @@ fn num_to_from_bits() {
     assert_eq!(b, Num::from_bits(Num::to_bits(&b)));
   }
 }
+
+#[test]
+fn subst_avoids_capture() {
+  use crate::fun::load_book::parse_test_term;
+
+  // @b (a b) [a := b]
+  let mut term = parse_test_term("λb (a b)");
+  term.subst(&Name::new("a"), &Term::var("b"));
+  assert_eq!(term.to_string(), "λc (b c)");
+
+  // @b @a (a b) [a := b], `a` is shadowed so nothing changes
+  let mut term = parse_test_term("λb λa (a b)");
+  term.subst(&Name::new("a"), &Term::var("b"));
+  assert_eq!(term.to_string(), "λb λa (a b)");
+
+  // let (b, c) = x; (a b c) [a := (b c)]
+  let mut term = parse_test_term("let (b, c) = x; (a b c)");
+  term.subst(&Name::new("a"), &parse_test_term("(b c)"));
+  assert_eq!(term.to_string(), "let (d, e) = x; (b c d e)");
+}
+
+#[test]
+fn free_vars_counts_uses() {
+  use crate::fun::load_book::parse_test_term;
+
+  let term = parse_test_term("(x y λy (x y $z) λ$z *)");
+  let free_vars = term.free_vars();
+  assert_eq!(free_vars.into_iter().collect::<Vec<_>>(), [(Name::new("x"), 2), (Name::new("y"), 1)]);
+}
+
+#[test]
+fn children_in_source_order() {
+  use crate::fun::load_book::parse_test_term;
+
+  let term = parse_test_term("(f (g a) (+ 1 2))");
+  let children = term.children().map(|child| child.to_string()).collect::<Vec<_>>();
+  assert_eq!(children, ["(f (g a))", "(+ 1 2)"]);
+
+  let term = parse_test_term("switch x = (h y) { 0: a; _: b }");
+  let children = term.children().map(|child| child.to_string()).collect::<Vec<_>>();
+  assert_eq!(children, ["(h y)", "a", "b"]);
+
+  // Wraps every variable in a call to `f`
+  fn wrap_vars(term: Term) -> Term {
+    match term {
+      Term::Var { .. } => Term::app(Term::Var { nam: Name::new("f") }, term),
+      _ => term.map_children(wrap_vars),
+    }
+  }
+  assert_eq!(wrap_vars(parse_test_term("λx (x (y, 1))")).to_string(), "λx (f x ((f y), 1))");
+}
+
+#[test]
+fn term_size_and_depth() {
+  // λx (+ (f x) 1): lam, oper, app, ref, var, num
+  let term =
+    Term::lam(Name::new("x"), Term::oper(Op::ADD, Term::app(Term::r#ref("f"), Term::var("x")), Term::num(1)));
+  assert_eq!(term.size(), 6);
+  assert_eq!(term.depth(), 4);
+
+  let mut book = Book::default();
+  let rule = Rule { pats: vec![], body: term, guard: None };
+  book.defs.insert(Name::new("g"), Definition::new(Name::new("g"), vec![rule], Source::Generated));
+  assert_eq!(book.size_report().into_iter().collect::<Vec<_>>(), [(Name::new("g"), 6)]);
+}
+
+#[test]
+fn estimate_interactions() {
+  use crate::fun::load_book::do_parse_book_default;
+  let code = "
+id x = x
+num = (+ 1 2)
+dups x = let {a b} = x; let {c d} = a; {(b c) d}
+";
+  let book = do_parse_book_default(code, std::path::Path::new("estimate")).unwrap();
+  let estimates = book.estimate_interactions();
+  let estimate = |name: &str| estimates[&Name::new(name)];
+  assert_eq!(estimate("id"), 0);
+  assert!(estimate("id") < estimate("num"));
+  assert!(estimate("num") < estimate("dups"));
+
+  // Rule patterns count as the matches they become.
+  let code = "type T = (A x) | B\nf (T/A x) = x\nf T/B = 2\ng x = match x { T/A: x.x; T/B: 2 }";
+  let book = do_parse_book_default(code, std::path::Path::new("estimate")).unwrap();
+  let estimates = book.estimate_interactions();
+  assert_eq!(estimates[&Name::new("f")], 4);
+  assert_eq!(estimates[&Name::new("g")], 4);
+}
+
+#[test]
+fn bitwise_not_round_trip() {
+  use crate::fun::load_book::parse_test_term;
+
+  assert_eq!(parse_test_term("~5").to_string(), "16777210");
+  assert_eq!(parse_test_term("~~5").to_string(), "5");
+  for code in ["~x", "~(+ x 1)", "(~x y)", "~λx x"] {
+    let term = parse_test_term(code);
+    assert_eq!(term.to_string(), code);
+    assert_eq!(parse_test_term(&term.to_string()), term);
+  }
+}
+
+#[test]
+fn negate_floats() {
+  use crate::fun::load_book::parse_test_term;
+
+  let negated = |code: &str| {
+    let term = parse_test_term(code);
+    // Negating a float never mixes an integer and a float.
+    let mut errs = vec![];
+    term.check_num_operands(&mut errs);
+    assert!(errs.is_empty(), "{code}");
+    term.to_string()
+  };
+  assert_eq!(negated("- -1.5"), "1.500");
+  assert_eq!(negated("- -2"), "+2");
+  assert_eq!(negated("- (+ 1.0 2.0)"), "(- 0.000 (+ 1.000 2.000))");
+  assert_eq!(negated("- (+ x 1.0)"), "(- 0.000 (+ x 1.000))");
+  // Comparisons give integers, even when comparing floats.
+  assert_eq!(negated("- (< 1.0 2.0)"), "(- 0 (< 1.000 2.000))");
+  assert_eq!(negated("- x"), "(- 0 x)");
+}
+
+#[test]
+fn name_from_str() {
+  let name: Name = "foo".into();
+  assert_eq!(name, Name::new("foo"));
+  assert_eq!(Name::from(String::from("foo")), name);
+  // Still derefs to the string.
+  assert_eq!(name.len(), 3);
+  assert!(name.starts_with("fo"));
+}
+
+#[test]
+fn fold_terms() {
+  use crate::fun::load_book::parse_test_term;
+  let term = parse_test_term("λx let y = (+ x 1); (f x (y, z))");
+
+  let size = term.fold(&mut |_, children: Vec<usize>| 1 + children.iter().sum::<usize>());
+  assert_eq!(size, term.size());
+  let depth = term.fold(&mut |_, children: Vec<usize>| 1 + children.into_iter().max().unwrap_or(0));
+  assert_eq!(depth, term.depth());
+
+  // The results of the children are in the same order as `Term::children`.
+  let vars = term.fold(&mut |term, children: Vec<Vec<String>>| match term {
+    Term::Var { nam } => vec![nam.to_string()],
+    _ => children.concat(),
+  });
+  assert_eq!(vars, ["x", "f", "x", "y", "z"]);
+}
+
+#[test]
+fn visit_terms_in_order() {
+  use crate::fun::load_book::parse_test_term;
+  let mut term = parse_test_term("λx let y = (+ x 1); (f x (y, z))");
+
+  let mut vars = vec![];
+  term.visit(|term| {
+    if let Term::Var { nam } = term {
+      vars.push(nam.to_string());
+    }
+  });
+  assert_eq!(vars, ["x", "f", "x", "y", "z"]);
+
+  // Parents are visited first, so the new children are visited too.
+  term.visit_mut(|term| {
+    if let Term::Var { nam } = term {
+      if nam == "z" {
+        *term = Term::app(Term::Var { nam: Name::new("g") }, Term::Var { nam: Name::new("w") });
+      } else if nam == "w" {
+        *term = Term::Era;
+      }
+    }
+  });
+  assert_eq!(term.to_string(), "λx let y = (+ x 1); (f x (y, (g *)))");
+
+  // Children are visited first, so the operation already has numbers when it gets visited.
+  let mut term = parse_test_term("(+ (* 2 3) (- 10 4))");
+  let mut visited = vec![];
+  term.visit_mut_post(|term| {
+    visited.push(term.to_string());
+    if let Term::Oper { opr, fst, snd } = term {
+      if let (Term::Num { val: fst }, Term::Num { val: snd }) = (fst.as_ref(), snd.as_ref()) {
+        *term = Term::Num { val: opr.compute(*fst, *snd).unwrap() };
+      }
+    }
+  });
+  assert_eq!(term.to_string(), "12");
+  assert_eq!(visited, ["2", "3", "(* 2 3)", "10", "4", "(- 10 4)", "(+ 6 6)"]);
+}
+
+#[test]
+fn visit_deep_terms() {
+  const DEPTH: usize = 200_000;
+  let mut term = Term::Var { nam: Name::new("f") };
+  for i in 0..DEPTH {
+    term = Term::app(term, Term::Num { val: Num::U24(i as u32 % 2) });
+  }
+
+  let mut count = 0;
+  term.visit(|_| count += 1);
+  assert_eq!(count, 2 * DEPTH + 1);
+
+  term.visit_mut(|term| {
+    if let Term::Num { val: Num::U24(val) } = term {
+      *val = 1 - *val;
+    }
+  });
+  let mut ones = 0;
+  term.visit(|term| {
+    if let Term::Num { val: Num::U24(1) } = term {
+      ones += 1;
+    }
+  });
+  assert_eq!(ones, DEPTH / 2);
+
+  let mut apps = 0;
+  term.visit_mut_post(|term| {
+    if let Term::App { fun, arg, .. } = term {
+      assert!(matches!(fun.as_ref(), Term::App { .. } | Term::Var { .. }));
+      assert!(matches!(arg.as_ref(), Term::Num { .. }));
+      apps += 1;
+    }
+  });
+  assert_eq!(apps, DEPTH);
+  assert_eq!(term.size(), 2 * DEPTH + 1);
+
+  // The passes built on these traversals also work for deep terms.
+  assert_eq!(term.fold(&mut |_, children: Vec<usize>| 1 + children.iter().sum::<usize>()), 2 * DEPTH + 1);
+  assert_eq!(term.free_vars().into_iter().collect::<Vec<_>>(), [(Name::new("f"), 1)]);
+  term.subst(&Name::new("f"), &Term::Var { nam: Name::new("g") });
+  assert_eq!(term.count_occurrences(&Name::new("g")), 1);
+}
+
+#[test]
+fn visit_terms_with_binds() {
+  use crate::fun::load_book::parse_test_term;
+  let term = parse_test_term("λx (x y λy (x y) let x = y; (x z))");
+
+  let mut vars = vec![];
+  term.visit_with_binds(|term, scope| {
+    if let Term::Var { nam } = term {
+      vars.push((nam.to_string(), scope.get(nam).copied().unwrap_or(0)));
+    }
+  });
+  let expected = [("x", 1), ("y", 0), ("x", 1), ("y", 1), ("y", 0), ("x", 2), ("z", 0)];
+  assert_eq!(vars, expected.map(|(nam, binds)| (nam.to_string(), binds)));
+
+  let free_vars = term.free_vars().into_iter().map(|(nam, uses)| (nam.to_string(), uses)).collect::<Vec<_>>();
+  assert_eq!(free_vars, [("y".to_string(), 2), ("z".to_string(), 1)]);
+  assert_eq!(term.count_occurrences(&Name::new("y")), 2);
+}
+
+#[test]
+fn adt_field_names() {
+  use crate::fun::load_book::do_parse_book_default;
+  let code = "
+object Point { x, y }
+type Shape = (Circle center radius) | (Group ~first ~rest) | Empty
+type Pair:
+  Pair { fst, snd }
+main = *
+";
+  let book = do_parse_book_default(code, std::path::Path::new("adt_field_names")).unwrap();
+  let fields = |adt: &str, ctr: &str| {
+    let ctr = &book.adts[&Name::new(adt)].ctrs[&Name::new(ctr)];
+    ctr.iter().map(|field| (field.nam.to_string(), field.rec)).collect::<Vec<_>>()
+  };
+  let field = |nam: &str, rec| (nam.to_string(), rec);
+
+  assert_eq!(fields("Point", "Point"), [field("x", false), field("y", false)]);
+  assert_eq!(fields("Shape", "Shape/Circle"), [field("center", false), field("radius", false)]);
+  assert_eq!(fields("Shape", "Shape/Group"), [field("first", true), field("rest", true)]);
+  assert_eq!(fields("Shape", "Shape/Empty"), []);
+  assert_eq!(fields("Pair", "Pair/Pair"), [field("fst", false), field("snd", false)]);
+}
+
+#[test]
+fn count_var_occurrences() {
+  use crate::fun::load_book::parse_test_term;
+  let x = Name::new("x");
+
+  assert_eq!(parse_test_term("(f y)").count_occurrences(&x), 0);
+  assert_eq!(parse_test_term("(f x)").count_occurrences(&x), 1);
+  assert_eq!(parse_test_term("(x (x, [x]) λy x)").count_occurrences(&x), 4);
+
+  // Rebinding the same name hides the outer variable.
+  assert_eq!(parse_test_term("(x λx x)").count_occurrences(&x), 1);
+  assert_eq!(parse_test_term("let x = x; (x x)").count_occurrences(&x), 1);
+  assert_eq!(parse_test_term("let {x y} = x; x").count_occurrences(&x), 1);
+  assert_eq!(parse_test_term("match y { List/Cons: (x y.head); List/Nil: x }").count_occurrences(&x), 2);
+  // `match x` binds `x` again in the arms.
+  assert_eq!(parse_test_term("match x { List/Cons: x.head; List/Nil: x }").count_occurrences(&x), 1);
+
+  // Unscoped variables are counted separately.
+  let term = parse_test_term("λ$x (x $x $x)");
+  assert_eq!(term.count_occurrences(&x), 1);
+  assert_eq!(term.count_link_occurrences(&x), 2);
+}
+
+#[test]
+fn op_from_str_round_trip() {
+  use Op::*;
+  let ops = [ADD, SUB, MUL, DIV, REM, EQ, NEQ, LT, GT, AND, OR, XOR, SHL, SHR, POW, LE, GE];
+  for op in ops {
+    assert_eq!(Op::try_from(op.to_string().as_str()), Ok(op));
+    assert_eq!(op.to_string().parse(), Ok(op));
+  }
+  assert_eq!(Op::try_from("<>"), Err("Unknown operator '<>'.".to_string()));
+  assert!(Op::try_from(" +").is_err());
+}
+
+#[test]
+fn defs_by_name_order() {
+  let mut book = load_book::parse_test_book("zip = 0\nmain = (zip add)\nadd = 1\nB = 2");
+  let order = |book: &Book| book.defs_by_name().map(|(nam, _)| nam.to_string()).collect::<Vec<_>>();
+  assert_eq!(order(&book), ["B", "add", "main", "zip"]);
+  assert!(book.defs_by_name().all(|(nam, def)| *nam == def.name));
+
+  for (nam, def) in book.defs_by_name_mut() {
+    def.rules[0].body = Term::app(Term::r#ref(nam), Term::Era);
+  }
+  assert_eq!(book.defs[&Name::new("add")].rules[0].body.to_string(), "(add *)");
+  // The book keeps its own order.
+  assert_eq!(book.defs.keys().next().unwrap(), "zip");
+}
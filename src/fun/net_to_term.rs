@@ -14,6 +14,35 @@ pub fn net_to_term(
   labels: &Labels,
   linear: bool,
   diagnostics: &mut Diagnostics,
+) -> Term {
+  net_to_term_with_hints(net, book, labels, linear, HashMap::new(), diagnostics)
+}
+
+/// Like [`net_to_term`], but naming the variables bound at the ports in `hints` with the given names.
+///
+/// The hints are only meaningful for nets that weren't reduced since they were compiled,
+/// see [`crate::fun::term_to_net::term_to_hvm_with_hints`].
+/// If a name was already given to another variable, a generated name is used instead.
+pub fn net_to_term_with_hints(
+  net: &INet,
+  book: &Book,
+  labels: &Labels,
+  linear: bool,
+  hints: HashMap<Port, Name>,
+  diagnostics: &mut Diagnostics,
+) -> Term {
+  net_to_term_with_names(net, book, labels, linear, NameGen::with_hints(hints), diagnostics)
+}
+
+/// Like [`net_to_term`], but naming the variables with the given [`NameGen`],
+/// which sets the hinted names and the scheme of the generated ones.
+pub fn net_to_term_with_names(
+  net: &INet,
+  book: &Book,
+  labels: &Labels,
+  linear: bool,
+  namegen: NameGen,
+  diagnostics: &mut Diagnostics,
 ) -> Term {
   let mut reader = Reader {
     net,
@@ -23,7 +52,7 @@ pub fn net_to_term(
     dup_paths: if linear { None } else { Some(Default::default()) },
     scope: Default::default(),
     seen_fans: Default::default(),
-    namegen: Default::default(),
+    namegen,
     seen: Default::default(),
     errors: Default::default(),
   };
@@ -509,6 +538,7 @@ impl Reader<'_> {
   /// (a a)
   /// ```
   ///
+  #[allow(clippy::result_large_err)]
   fn decay_or_get_ports(&mut self, node: NodeId) -> Result<Term, (Term, Term)> {
     let fst_port = self.net.enter_port(Port(node, 1));
     let snd_port = self.net.enter_port(Port(node, 2));
@@ -519,14 +549,14 @@ impl Reader<'_> {
     // This is not valid for all kinds of nodes, only CON/TUP/DUP, due to their interaction rules.
     if matches!(node_kind, NodeKind::Ctr(_)) {
       match (fst_port, snd_port) {
-        (Port(fst_node, 1), Port(snd_node, 2)) if fst_node == snd_node => {
-          if self.net.node(fst_node).kind == *node_kind {
-            self.scope.remove(&fst_node);
-
-            let port_zero = self.net.enter_port(Port(fst_node, 0));
-            let term = self.read_term(port_zero);
-            return Ok(term);
-          }
+        (Port(fst_node, 1), Port(snd_node, 2))
+          if fst_node == snd_node && self.net.node(fst_node).kind == *node_kind =>
+        {
+          self.scope.remove(&fst_node);
+
+          let port_zero = self.net.enter_port(Port(fst_node, 0));
+          let term = self.read_term(port_zero);
+          return Ok(term);
         }
         _ => {}
       }
@@ -667,21 +697,79 @@ impl Term {
 
 /* Variable name generation */
 
+/// How the variables created by the readback are named.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum VarNaming {
+  /// `a`, `b`, ..., `z`, `ab`, `bb`, ...
+  #[default]
+  Letters,
+  /// The prefix followed by a number, like `v0`, `v1`, ...
+  /// The prefix must start with a letter, so that the names aren't read as numbers,
+  /// which [`VarNaming::numbered`] checks.
+  Numbered(String),
+}
+
+impl VarNaming {
+  /// Numbered names with the given prefix, if it's not empty and starts with a letter.
+  pub fn numbered(prefix: impl Into<String>) -> Result<Self, String> {
+    let prefix = prefix.into();
+    match prefix.chars().next() {
+      None => Err("The prefix of numbered variable names can't be empty.".to_string()),
+      Some(c) if !c.is_ascii_alphabetic() => {
+        Err(format!("The prefix of numbered variable names must start with a letter, found '{prefix}'."))
+      }
+      Some(_) => Ok(VarNaming::Numbered(prefix)),
+    }
+  }
+
+  /// The name of the variable with this id. Different ids always get different names.
+  pub fn name(&self, id: u64) -> Name {
+    match self {
+      VarNaming::Letters => Name::from(id),
+      VarNaming::Numbered(prefix) => Name::new(format!("{prefix}{id}")),
+    }
+  }
+}
+
 #[derive(Default)]
 pub struct NameGen {
-  pub var_port_to_id: HashMap<Port, u64>,
+  pub var_port_to_name: HashMap<Port, Name>,
   pub id_counter: u64,
+  pub naming: VarNaming,
+  /// The names to give to the variables bound at some ports, if they're still free.
+  hints: HashMap<Port, Name>,
+  /// All the hinted names, which generated names must avoid.
+  hinted: HashSet<Name>,
+  used_hints: HashSet<Name>,
 }
 
 impl NameGen {
+  /// Names the variables bound at the hinted ports with their hints,
+  /// and generates the names of the other ones with `naming`.
+  pub fn new(hints: HashMap<Port, Name>, naming: VarNaming) -> Self {
+    let hinted = hints.values().cloned().collect();
+    NameGen { hints, hinted, naming, ..Default::default() }
+  }
+
+  pub fn with_hints(hints: HashMap<Port, Name>) -> Self {
+    NameGen::new(hints, VarNaming::default())
+  }
+
+  pub fn with_naming(naming: VarNaming) -> Self {
+    NameGen::new(HashMap::new(), naming)
+  }
+
   // Given a port, returns its name, or assigns one if it wasn't named yet.
   fn var_name(&mut self, var_port: Port) -> Name {
-    let id = self.var_port_to_id.entry(var_port).or_insert_with(|| {
-      let id = self.id_counter;
-      self.id_counter += 1;
-      id
-    });
-    Name::from(*id)
+    if let Some(nam) = self.var_port_to_name.get(&var_port) {
+      return nam.clone();
+    }
+    let nam = match self.hints.get(&var_port) {
+      Some(hint) if self.used_hints.insert(hint.clone()) => hint.clone(),
+      _ => self.unique(),
+    };
+    self.var_port_to_name.insert(var_port, nam.clone());
+    nam
   }
 
   fn decl_name(&mut self, net: &INet, var_port: Port) -> Option<Name> {
@@ -692,9 +780,13 @@ impl NameGen {
   }
 
   pub fn unique(&mut self) -> Name {
-    let id = self.id_counter;
-    self.id_counter += 1;
-    Name::from(id)
+    loop {
+      let nam = self.naming.name(self.id_counter);
+      self.id_counter += 1;
+      if !self.hinted.contains(&nam) {
+        return nam;
+      }
+    }
   }
 }
 
@@ -805,3 +897,74 @@ impl Pattern {
     })
   }
 }
+
+/// Compiles a term to a net and reads it back, using the names of the variables of the term
+/// that `keep_hint` accepts and naming the other ones with `naming`.
+#[cfg(test)]
+fn read_back_compiled(term: &Term, naming: VarNaming, keep_hint: impl Fn(&Name) -> bool) -> Term {
+  use crate::{fun::term_to_net::term_to_hvm_with_hints, net::hvm_to_net::hvm_to_net_with_hints};
+  let (net, mut hints) = term_to_hvm_with_hints(term, &mut Labels::default()).unwrap();
+  hints.retain(|_, nam| keep_hint(nam));
+  let (net, hints) = hvm_to_net_with_hints(&net, &hints);
+  let mut diags = Diagnostics::default();
+  net_to_term_with_names(
+    &net,
+    &Book::default(),
+    &Labels::default(),
+    true,
+    NameGen::new(hints, naming),
+    &mut diags,
+  )
+}
+
+#[test]
+fn readback_with_hints() {
+  use crate::fun::load_book::parse_test_term;
+  let readback = |code: &str, use_hints: bool| {
+    read_back_compiled(&parse_test_term(code), VarNaming::Letters, |_| use_hints).to_string()
+  };
+  assert_eq!(readback("λfoo λbar (foo bar)", true), "λfoo λbar (foo bar)");
+  assert_eq!(readback("λfoo λbar (foo bar)", false), "λa λb (a b)");
+  assert_eq!(readback("λb λfst λsnd (b fst snd)", true), "λb λfst λsnd (b fst snd)");
+  assert_eq!(readback("λx let {x1 x2} = x; (x1 x2)", true), "λx let {x1 x2} = x; (x1 x2)");
+  // Unused variables don't have a hint, and generated names don't clash with the hinted ones.
+  assert_eq!(readback("λa λ* λc (a c)", true), "λa λ* λc (a c)");
+  assert_eq!(readback("λb λc (λd d b c)", true), "λb λc (λd d b c)");
+}
+
+#[test]
+fn readback_var_naming() {
+  use crate::fun::load_book::parse_test_term;
+  let numbered = || VarNaming::numbered("v").unwrap();
+
+  // More variables than letters in the alphabet, so that the default scheme also needs longer names.
+  let vars = (0..60).map(|i| format!("x{i}")).collect::<Vec<_>>();
+  let lams = vars.iter().map(|var| format!("λ{var}")).collect::<Vec<_>>();
+  let code = format!("{} ({})", lams.join(" "), vars.join(" "));
+  let term = parse_test_term(&code);
+  for naming in [VarNaming::Letters, numbered()] {
+    let read = read_back_compiled(&term, naming.clone(), |_| false);
+    assert!(read.alpha_eq(&term));
+    let mut names = vec![];
+    let mut lam = &read;
+    while let Term::Lam { pat, bod, .. } = lam {
+      names.extend(pat.binds().flatten().cloned());
+      lam = bod;
+    }
+    assert_eq!(names.iter().collect::<HashSet<_>>().len(), vars.len());
+    assert_eq!(names, (0..vars.len() as u64).map(|id| naming.name(id)).collect::<Vec<_>>());
+  }
+
+  // Generated names skip the ones used by the hints.
+  let term = parse_test_term("λv0 λa (a v0)");
+  assert_eq!(read_back_compiled(&term, numbered(), |nam| nam != "a").to_string(), "λv0 λv1 (v1 v0)");
+  assert_eq!(read_back_compiled(&term, VarNaming::Letters, |nam| nam != "a").to_string(), "λv0 λa (a v0)");
+}
+
+#[test]
+fn numbered_var_naming_prefix() {
+  assert_eq!(VarNaming::numbered("tmp"), Ok(VarNaming::Numbered("tmp".to_string())));
+  assert!(VarNaming::numbered("").is_err());
+  assert!(VarNaming::numbered("1x").is_err());
+  assert!(VarNaming::numbered("_x").is_err());
+}
@@ -55,24 +55,37 @@ impl ParseBook {
       .or_else(|| self.imp_defs.get(name).map(|d| d.source.is_builtin()))
       .or_else(|| self.hvm_defs.get(name).map(|d| d.source.is_builtin()))
   }
+
+  /// Where the function or native HVM definition with the given name came from.
+  pub fn def_source(&self, name: &Name) -> Option<&Source> {
+    self
+      .fun_defs
+      .get(name)
+      .map(|d| &d.source)
+      .or_else(|| self.imp_defs.get(name).map(|d| &d.source))
+      .or_else(|| self.hvm_defs.get(name).map(|d| &d.source))
+  }
 }
 
 // Bend grammar description:
 // <Book>       ::= (<Data> | <Rule>)*
 // <ADT>        ::= "type" <Name> "=" ( <Name> | "(" <Name> (<Name>)* ")" )+
-// <Rule>       ::= ("(" <Name> <Pattern>* ")" | <Name> <Pattern>*) "=" <Term>
+// <Rule>       ::= ("(" <Name> <Pattern>* ")" | <Name> <Pattern>*) ("|" <Term>)? "=" <Term>
 // <Pattern>    ::= "(" <Name> <Pattern>* ")" | <NameEra> | <Number> | "(" <Pattern> ("," <Pattern>)+ ")"
 // <Term>       ::=
-//   <Number> | <NumOp> | <Tup> | <App> | <Group> | <Nat> | <Lam> | <UnscopedLam> | <Bend> | <Fold> |
+//   <Number> | <Neg> | <Not> | <NumOp> | <Tup> | <App> | <Group> | <Nat> | <Lam> | <UnscopedLam> | <Bend> | <Fold> |
 //   <Use> | <Dup> | <LetTup> | <Let> | <With> | <Match> | <Switch> | <Era> | <UnscopedVar> | <Var>
 // <Lam>        ::= <Tag>? ("λ"|"@") <NameEra> <Term>
 // <UnscopedLam>::= <Tag>? ("λ"|"@") "$" <Name> <Term>
 // <NumOp>      ::= "(" <Operator> <Term> <Term> ")"
+// <Neg>        ::= "-" <Term>
+// <Not>        ::= "~" <Term>
 // <Tup>        ::= "(" <Term> ("," <Term>)+ ")"
 // <App>        ::= <Tag>? "(" <Term> (<Term>)+ ")"
 // <Group>      ::= "(" <Term> ")"
 // <Use>        ::= "use" <Name> "=" <Term> ";"? <Term>
-// <Let>        ::= "let" <NameEra> "=" <Term> ";"? <Term>
+// <Let>        ::= "let" <NameEra> "=" <Term> ("," <NameEra> "=" <Term>)* ";"? <Term>
+// <LetRec>     ::= "letrec" <Name> "=" <Term> ("," <Name> "=" <Term>)* ";"? <Term>
 // <With>       ::= "with" <Name> "{" <Ask> "}"
 // <Ask>        ::= "ask" <Pattern> "=" <Term> ";" <Term> | <Term>
 // <LetTup>     ::= "let" "(" <NameEra> ("," <NameEra>)+ ")" "=" <Term> ";"? <Term>
@@ -81,6 +94,8 @@ impl ParseBook {
 // <String>     ::= "\"" (escape sequence | [^"])* "\""
 // <Char>       ::= "'" (escape sequence | [^']) "'"
 // <Match>      ::= "match" <MatchArg> <WithClause>? "{" <MatchArm>+ "}"
+// <MultiMatch> ::= "match" <MatchArg> <MatchArg>+ "{" <MultiArm>+ "}"
+// <MultiArm>   ::= "|"? <Pattern>+ ":" <Term> ";"?
 // <Fold>       ::= "fold" <MatchArg> <WithClause>? "{" <MatchArm>+ "}"
 // <MatchArg>   ::= (<Name> "=" <Term>) | <Term>
 // <WithClause> ::= "with" (<Name> ("=" <Term>)? ","?)+
@@ -94,7 +109,7 @@ impl ParseBook {
 // <Era>        ::= "*"
 // <Tag>        ::= "#" <Name>
 // <Name>       ::= [_\-./a-zA-Z0-9]+
-// <Number>     ::= ([0-9]+ | "0x"[0-9a-fA-F]+ | "0b"[01]+)
+// <Number>     ::= ("+" | "-")? ([0-9]+ | "0x"[0-9a-fA-F]+ | "0b"[01]+)
 // <Operator>   ::= ( "+" | "-" | "*" | "/" | "%" | "==" | "!=" | "<<" | ">>" | "<" | ">" | "&" | "|" | "^" | "**" )
 
 pub type ParseResult<T> = std::result::Result<T, String>;
@@ -112,114 +127,178 @@ impl<'a> TermParser<'a> {
   /* AST parsing functions */
 
   pub fn parse_book(&mut self, default_book: ParseBook, builtin: bool) -> ParseResult<ParseBook> {
+    let (book, errs) = self.parse_book_recovering(default_book, builtin);
+    if errs.is_empty() {
+      Ok(book)
+    } else {
+      Err(errs.join("\n"))
+    }
+  }
+
+  /// Parses a book, collecting every error instead of stopping at the first one.
+  ///
+  /// When a top-level item fails to parse, the parser skips to the start of the next
+  /// line that isn't indented and continues from there, so the definitions that parsed
+  /// correctly are still added to the returned book.
+  pub fn parse_book_recovering(
+    &mut self,
+    default_book: ParseBook,
+    builtin: bool,
+  ) -> (ParseBook, Vec<String>) {
     let mut book = default_book;
-    let mut indent = self.advance_newlines()?;
+    let mut errs = vec![];
+    let mut indent = match self.advance_newlines() {
+      Ok(indent) => indent,
+      Err(err) => return (book, vec![err]),
+    };
     let mut last_rule = None;
     while !self.is_eof() {
       let ini_idx = *self.index();
-
-      // Record type definition
-      if self.try_parse_keyword("object") {
-        let mut prs = PyParser { input: self.input, index: *self.index() };
-        let (obj, nxt_indent) = prs.parse_object(indent)?;
-        self.index = prs.index;
-        let end_idx = *self.index();
-        self.add_object(obj, &mut book, ini_idx..end_idx, builtin)?;
-        indent = nxt_indent;
-        last_rule = None;
-        continue;
+      if let Err(err) = self.parse_top_level(&mut book, &mut indent, &mut last_rule, builtin) {
+        errs.push(err);
+        // Later rules of the same function shouldn't be reported as redefinitions.
+        self.index = ini_idx;
+        last_rule = self.try_parse_rule_name();
+        self.skip_to_next_top_level(ini_idx);
+        indent = self.advance_newlines().unwrap_or(Indent::Eof);
       }
+    }
+    (book, errs)
+  }
 
-      // Imp function definition
-      if self.try_parse_keyword("def") {
-        let mut prs = PyParser { input: self.input, index: *self.index() };
-        let (def, nxt_indent) = prs.parse_def(indent)?;
-        self.index = prs.index;
-        let end_idx = *self.index();
-        self.add_imp_def(def, &mut book, ini_idx..end_idx, builtin)?;
-        indent = nxt_indent;
-        last_rule = None;
-        continue;
-      }
+  fn parse_top_level(
+    &mut self,
+    book: &mut ParseBook,
+    indent: &mut Indent,
+    last_rule: &mut Option<Name>,
+    builtin: bool,
+  ) -> ParseResult<()> {
+    let ini_idx = *self.index();
 
-      // Fun/Imp type definition
-      if self.try_parse_keyword("type") {
-        self.skip_trivia();
-        let rewind_index = self.index;
-
-        let _ = self.labelled(|p| p.parse_top_level_name(), "datatype name")?;
-
-        // Imp type definition
-        if self.starts_with(":") {
-          let mut prs = PyParser { input: self.input, index: rewind_index };
-          let (r#enum, nxt_indent) = prs.parse_type(indent)?;
-          self.index = prs.index;
-          let end_idx = *self.index();
-          self.add_imp_type(r#enum, &mut book, ini_idx..end_idx, builtin)?;
-          indent = nxt_indent;
-          last_rule = None;
-          continue;
-        // Fun type definition
-        } else {
-          self.index = rewind_index;
-          let (nam, ctrs) = self.parse_datatype()?;
-          let end_idx = *self.index();
-          let source = if builtin { Source::Builtin } else { Source::Local(ini_idx..end_idx) };
-          let adt = Adt { ctrs, source };
-          self.add_fun_type(&mut book, nam, adt, ini_idx..end_idx)?;
-          indent = self.advance_newlines()?;
-          last_rule = None;
-          continue;
-        }
-      }
+    // Record type definition
+    if self.try_parse_keyword("object") {
+      let mut prs = PyParser { input: self.input, index: *self.index() };
+      let (obj, nxt_indent) = prs.parse_object(*indent)?;
+      self.index = prs.index;
+      let end_idx = *self.index();
+      self.add_object(obj, book, ini_idx..end_idx, builtin)?;
+      *indent = nxt_indent;
+      *last_rule = None;
+      return Ok(());
+    }
 
-      // HVM native function definition
-      if self.try_parse_keyword("hvm") {
-        let def = self.parse_hvm(builtin)?;
-        let end_idx = *self.index();
-        self.add_hvm(def, &mut book, ini_idx..end_idx)?;
-        indent = self.advance_newlines()?;
-        last_rule = None;
-        continue;
-      }
+    // Imp function definition
+    if self.try_parse_keyword("def") {
+      let mut prs = PyParser { input: self.input, index: *self.index() };
+      let (def, nxt_indent) = prs.parse_def(*indent)?;
+      self.index = prs.index;
+      let end_idx = *self.index();
+      self.add_imp_def(def, book, ini_idx..end_idx, builtin)?;
+      *indent = nxt_indent;
+      *last_rule = None;
+      return Ok(());
+    }
 
-      // Import declaration
-      if self.try_parse_keyword("from") {
-        self.skip_trivia();
-        let import = self.parse_from_import()?;
-        book.import_ctx.add_import(import);
-        indent = self.advance_newlines()?;
-        last_rule = None;
-        continue;
-      }
+    // Fun/Imp type definition
+    if self.try_parse_keyword("type") {
+      self.skip_trivia();
+      let rewind_index = self.index;
 
-      if self.try_parse_keyword("import") {
-        self.skip_trivia();
-        let imports = self.parse_import()?;
-        for imp in imports {
-          book.import_ctx.add_import(imp);
-        }
-        indent = self.advance_newlines()?;
-        last_rule = None;
-        continue;
+      let _ = self.labelled(|p| p.parse_top_level_name(), "datatype name")?;
+
+      // Imp type definition
+      if self.starts_with(":") {
+        let mut prs = PyParser { input: self.input, index: rewind_index };
+        let (r#enum, nxt_indent) = prs.parse_type(*indent)?;
+        self.index = prs.index;
+        let end_idx = *self.index();
+        self.add_imp_type(r#enum, book, ini_idx..end_idx, builtin)?;
+        *indent = nxt_indent;
+        *last_rule = None;
+        return Ok(());
+      // Fun type definition
+      } else {
+        self.index = rewind_index;
+        let (nam, ctrs) = self.parse_datatype()?;
+        let end_idx = *self.index();
+        let source = if builtin { Source::Builtin } else { Source::Local(ini_idx..end_idx) };
+        let adt = Adt { ctrs, source };
+        self.add_fun_type(book, nam, adt, ini_idx..end_idx)?;
+        *indent = self.advance_newlines()?;
+        *last_rule = None;
+        return Ok(());
       }
+    }
 
-      // Fun function definition
-      let ini_idx = *self.index();
-      let (name, rule) = self.parse_rule()?;
+    // HVM native function definition
+    if self.try_parse_keyword("hvm") {
+      let def = self.parse_hvm(builtin)?;
       let end_idx = *self.index();
+      self.add_hvm(def, book, ini_idx..end_idx)?;
+      *indent = self.advance_newlines()?;
+      *last_rule = None;
+      return Ok(());
+    }
+
+    // Import declaration
+    if self.try_parse_keyword("from") {
+      self.skip_trivia();
+      let import = self.parse_from_import()?;
+      book.import_ctx.add_import(import);
+      *indent = self.advance_newlines()?;
+      *last_rule = None;
+      return Ok(());
+    }
 
-      if let Some(def) = book.imp_defs.get(&name) {
-        let msg = Self::redefinition_of_function_msg(def.source.is_builtin(), &name);
-        return self.with_ctx(Err(msg), ini_idx..end_idx);
+    if self.try_parse_keyword("import") {
+      self.skip_trivia();
+      let imports = self.parse_import()?;
+      for imp in imports {
+        book.import_ctx.add_import(imp);
       }
+      *indent = self.advance_newlines()?;
+      *last_rule = None;
+      return Ok(());
+    }
 
-      self.add_fun_def(&name, rule, builtin, &last_rule, &mut book, ini_idx..end_idx)?;
-      indent = self.advance_newlines()?;
-      last_rule = Some(name);
+    // Fun function definition
+    let ini_idx = *self.index();
+    let (name, rule) = self.parse_rule_with_where()?;
+    let end_idx = *self.index();
+
+    if let Some(def) = book.imp_defs.get(&name) {
+      let msg = Self::redefinition_of_function_msg(def.source.is_builtin(), &name);
+      let prev = def.source.clone();
+      return self.redefinition_err(msg, ini_idx..end_idx, &prev);
     }
 
-    Ok(book)
+    self.add_fun_def(&name, rule, builtin, last_rule, book, ini_idx..end_idx)?;
+    *indent = self.advance_newlines()?;
+    *last_rule = Some(name);
+    Ok(())
+  }
+
+  /// The name of the function defined by the rule starting at the current position, if any.
+  fn try_parse_rule_name(&mut self) -> Option<Name> {
+    self.try_consume_exactly("(");
+    self.skip_trivia();
+    self.parse_top_level_name().ok()
+  }
+
+  /// Moves to the start of the next line after `ini_idx` that is not indented, not a comment
+  /// and doesn't start with a closing bracket, which is where the next top-level item can start.
+  fn skip_to_next_top_level(&mut self, ini_idx: usize) {
+    let input = self.input;
+    let mut idx = ini_idx;
+    loop {
+      let at_line_start = idx > ini_idx && input[..idx].ends_with('\n');
+      match input[idx..].chars().next() {
+        None => break,
+        Some(c) if at_line_start && !c.is_whitespace() && !"#)]}".contains(c) => break,
+        _ => idx += input[idx..].find('\n').map_or(input.len() - idx, |nl| nl + 1),
+      }
+    }
+    self.index = idx;
   }
 
   fn parse_datatype(&mut self) -> ParseResult<(Name, IndexMap<Name, Vec<CtrField>>)> {
@@ -336,7 +415,7 @@ impl<'a> TermParser<'a> {
       let name = self.labelled(|p| p.parse_top_level_name(), "top-level definition")?;
       let mut pats = vec![];
       self.skip_trivia();
-      while !self.starts_with("=") {
+      while !self.starts_with("=") && !self.starts_with("|") {
         pats.push(self.parse_pattern(false)?);
         self.skip_trivia();
       }
@@ -344,14 +423,54 @@ impl<'a> TermParser<'a> {
     }
   }
 
+  /// Parses a top-level rule followed by its `where` clauses.
+  ///
+  /// The definitions in the `where` clauses become local definitions around the rule body,
+  /// so they're only visible in that rule. Each one can use the ones defined before it,
+  /// and consecutive clauses with the same name are rules of the same definition.
+  ///
+  /// A clause must be indented or in the same line as the end of the rule,
+  /// so that a `where` at the start of a line is a new top-level definition with that name.
+  fn parse_rule_with_where(&mut self) -> ParseResult<(Name, Rule)> {
+    let (name, mut rule) = self.parse_rule()?;
+    let mut local_defs: Vec<FunDefinition> = vec![];
+    loop {
+      let rewind_index = *self.index();
+      self.skip_trivia();
+      let ini_idx = *self.index();
+      let at_line_start = self.input()[..ini_idx].ends_with('\n');
+      if at_line_start || !self.try_parse_keyword("where") {
+        self.index = rewind_index;
+        break;
+      }
+      self.skip_trivia();
+      let (def_name, def_rule) = self.parse_rule()?;
+      let end_idx = *self.index();
+      match local_defs.last_mut() {
+        Some(def) if def.name == def_name => {
+          def.rules.push(def_rule);
+          if let Source::Local(s) = &mut def.source {
+            s.end = end_idx;
+          }
+        }
+        _ => local_defs.push(FunDefinition::new(def_name, vec![def_rule], Source::Local(ini_idx..end_idx))),
+      }
+    }
+    let body = std::mem::take(&mut rule.body);
+    rule.body = local_defs.into_iter().rfold(body, |nxt, def| Term::Def { def, nxt: Box::new(nxt) });
+    Ok((name, rule))
+  }
+
   fn parse_rule(&mut self) -> ParseResult<(Name, Rule)> {
     let (name, pats) = self.parse_rule_lhs()?;
 
+    let guard = if self.try_consume("|") { Some(self.parse_term()?) } else { None };
+
     self.consume("=")?;
 
     let body = self.parse_term()?;
 
-    let rule = Rule { pats, body };
+    let rule = Rule { pats, body, guard };
     Ok((name, rule))
   }
 
@@ -371,6 +490,7 @@ impl<'a> TermParser<'a> {
         self.skip_trivia();
         if self.starts_with(",") || simple {
           self.consume(",")?;
+          self.check_not_single_tuple(1)?;
           let mut els = self.list_like(|p| p.parse_pattern(simple), "", ")", ",", true, 1)?;
           els.insert(0, head);
           return Ok(Pattern::Fan(FanKind::Tup, tag.unwrap_or(Tag::Static), els));
@@ -408,12 +528,12 @@ impl<'a> TermParser<'a> {
       // Char
       if self.starts_with("'") {
         unexpected_tag(self)?;
-        let char = self.parse_quoted_char()?;
+        let char = self.parse_char_literal()?;
         return Ok(Pattern::Num(char as u32));
       }
 
       // Number
-      if self.peek_one().map_or(false, |c| c.is_ascii_digit()) {
+      if self.peek_one().is_some_and(|c| c.is_ascii_digit()) {
         unexpected_tag(self)?;
         let num = self.parse_u32()?;
         return Ok(Pattern::Num(num));
@@ -469,13 +589,17 @@ impl<'a> TermParser<'a> {
 
         // Opr but maybe a tup
         self.skip_trivia();
+        let opr_idx = *self.index();
         if let Some(opr) = self.try_parse_oper() {
+          let is_signed_num =
+            matches!(opr, Op::ADD | Op::SUB) && self.peek_one().is_some_and(|c| c.is_ascii_digit());
           self.skip_trivia();
 
           // jk, actually a tuple
           if self.starts_with(",") && opr == Op::MUL {
             let mut els = vec![Term::Era];
             while self.try_consume(",") {
+              self.check_not_single_tuple(els.len())?;
               els.push(self.parse_term()?);
             }
             self.consume(")")?;
@@ -488,7 +612,26 @@ impl<'a> TermParser<'a> {
 
           // Opr
           unexpected_tag(self)?;
+
+          // Section of both operands, `(+)`
+          if self.try_consume(")") {
+            return Ok(oper_section(opr, None, None));
+          }
+
           let fst = self.parse_term()?;
+
+          // Right section, `(+ 1)`
+          if self.try_consume(")") {
+            // Actually a signed number, `(-1)`
+            if is_signed_num {
+              self.index = opr_idx;
+              let num = self.parse_term()?;
+              self.consume(")")?;
+              return Ok(num);
+            }
+            return Ok(oper_section(opr, None, Some(fst)));
+          }
+
           let snd = self.parse_term()?;
           self.consume(")")?;
           return Ok(Term::Oper { opr, fst: Box::new(fst), snd: Box::new(snd) });
@@ -502,12 +645,24 @@ impl<'a> TermParser<'a> {
         if self.starts_with(",") {
           let mut els = vec![head];
           while self.try_consume(",") {
+            self.check_not_single_tuple(els.len())?;
             els.push(self.parse_term()?);
           }
           self.consume(")")?;
           return Ok(Term::Fan { fan: FanKind::Tup, tag: tag.unwrap_or(Tag::Static), els });
         }
 
+        // Left section, `(1 +)`.
+        // `(x *)` is the application of `x` to an eraser, so there's no left section of `*`.
+        let opr_idx = *self.index();
+        if let Some(opr) = self.try_parse_oper() {
+          if opr != Op::MUL && self.try_consume(")") {
+            unexpected_tag(self)?;
+            return Ok(oper_section(opr, Some(head), None));
+          }
+          self.index = opr_idx;
+        }
+
         // App
         let els = self.list_like(|p| p.parse_term(), "", ")", "", false, 0)?;
         let term = els.into_iter().fold(head, |fun, arg| Term::App {
@@ -585,7 +740,7 @@ impl<'a> TermParser<'a> {
       // Char
       if self.starts_with("'") {
         unexpected_tag(self)?;
-        let char = self.parse_quoted_char()?;
+        let char = self.parse_char_literal()?;
         return Ok(Term::Num { val: Num::U24(char as u32 & 0x00ff_ffff) });
       }
 
@@ -596,8 +751,31 @@ impl<'a> TermParser<'a> {
         return Ok(Term::Num { val: Num::U24(val) });
       }
 
+      // Unary negation
+      if self.starts_with_unary_neg() {
+        unexpected_tag(self)?;
+        if let Some(num) = self.parse_neg_number()? {
+          return Ok(Term::Num { val: num });
+        }
+        let val = self.parse_term()?;
+        return Ok(Term::negate(val));
+      }
+
+      // Bitwise not
+      if self.starts_with("~") {
+        unexpected_tag(self)?;
+        let ini_idx = *self.index();
+        self.advance_one();
+        let val = self.parse_term()?;
+        let end_idx = *self.index();
+        if let Term::Num { val: Num::F24(_) } = val {
+          return self.with_ctx(Err("Bitwise not is only defined for integers."), ini_idx..end_idx);
+        }
+        return Ok(Term::bitwise_not(val));
+      }
+
       // Native Number
-      if self.peek_one().map_or(false, is_num_char) {
+      if self.peek_one().is_some_and(is_num_char) {
         unexpected_tag(self)?;
         let num = self.parse_number()?;
         return Ok(Term::Num { val: num });
@@ -615,15 +793,55 @@ impl<'a> TermParser<'a> {
         return Ok(Term::Use { nam: Some(nam), val: Box::new(val), nxt: Box::new(nxt) });
       }
 
+      // Letrec
+      let ini_idx = *self.index();
+      if self.try_parse_keyword("letrec") {
+        unexpected_tag(self)?;
+        let mut binds: Vec<(Name, Term)> = vec![];
+        loop {
+          self.skip_trivia();
+          let nam_idx = *self.index();
+          let nam = self.parse_bend_name()?;
+          if binds.iter().any(|(other, _)| *other == nam) {
+            let msg = format!("Found a repeated name '{nam}' in 'letrec'.");
+            let end_idx = *self.index();
+            return self.with_ctx(Err(msg), nam_idx..end_idx);
+          }
+          self.consume("=")?;
+          let val = self.parse_term()?;
+          binds.push((nam, val));
+          if !self.try_consume(",") {
+            break;
+          }
+        }
+        let end_idx = *self.index();
+        self.try_consume(";");
+        let nxt = self.parse_term()?;
+        return Ok(letrec(binds, nxt, Source::Local(ini_idx..end_idx)));
+      }
+
       // Let
       if self.try_parse_keyword("let") {
         unexpected_tag(self)?;
-        let pat = self.parse_pattern(true)?;
-        self.consume("=")?;
-        let val = self.parse_term()?;
+        // `let a = x, b = y; nxt` is the same as `let a = x; let b = y; nxt`
+        let mut binds = vec![];
+        loop {
+          let pat = self.parse_pattern(true)?;
+          self.consume("=")?;
+          let val = self.parse_term()?;
+          binds.push((pat, val));
+          if !self.try_consume(",") {
+            break;
+          }
+        }
         self.try_consume(";");
         let nxt = self.parse_term()?;
-        return Ok(Term::Let { pat: Box::new(pat), val: Box::new(val), nxt: Box::new(nxt) });
+        let term = binds.into_iter().rfold(nxt, |nxt, (pat, val)| Term::Let {
+          pat: Box::new(pat),
+          val: Box::new(val),
+          nxt: Box::new(nxt),
+        });
+        return Ok(term);
       }
 
       // Ask (monadic operation)
@@ -685,20 +903,25 @@ impl<'a> TermParser<'a> {
 
         chain.push((cnd, thn));
 
-        self.skip_trivia_inline()?;
-        while self.try_parse_keyword("elif") {
+        // `else if` is the same as `elif`
+        let els = loop {
+          self.skip_trivia_inline()?;
+          if !self.try_parse_keyword("elif") {
+            self.consume("else")?;
+            self.skip_trivia();
+            if !self.try_parse_keyword("if") {
+              self.consume("{")?;
+              let els = self.parse_term()?;
+              self.consume("}")?;
+              break els;
+            }
+          }
           let cnd = self.parse_term()?;
           self.consume("{")?;
           let thn = self.parse_term()?;
           self.consume("}")?;
-          self.skip_trivia_inline()?;
           chain.push((cnd, thn));
-        }
-
-        self.consume("else")?;
-        self.consume("{")?;
-        let els = self.parse_term()?;
-        self.consume("}")?;
+        };
         let els = chain.into_iter().rfold(els, |acc, (cnd, thn)| Term::Swt {
           bnd: Some(Name::new("%cond")),
           arg: Box::new(cnd),
@@ -711,9 +934,14 @@ impl<'a> TermParser<'a> {
       }
 
       // Match
+      let ini_idx = *self.index();
       if self.try_parse_keyword("match") {
         unexpected_tag(self)?;
         let (bnd, arg) = self.parse_match_arg()?;
+        self.skip_trivia();
+        if !self.starts_with("{") && !self.starts_with_keyword("with") {
+          return self.parse_multi_match((bnd, arg), ini_idx);
+        }
         let (with_bnd, with_arg) = self.parse_with_clause()?;
         let arms = self.list_like(|p| p.parse_match_arm(), "", "}", ";", false, 1)?;
         return Ok(Term::Mat { arg: Box::new(arg), bnd, with_bnd, with_arg, arms });
@@ -848,6 +1076,7 @@ impl<'a> TermParser<'a> {
   /// Parses a tag where it may or may not be valid.
   ///
   /// If it is not valid, the returned callback can be used to issue an error.
+  #[allow(clippy::type_complexity)]
   fn parse_tag(&mut self) -> ParseResult<(Option<Tag>, impl FnOnce(&mut Self) -> Result<(), String>)> {
     let index = self.index;
     self.skip_trivia();
@@ -923,6 +1152,55 @@ impl<'a> TermParser<'a> {
     Ok((nam, vec![], bod))
   }
 
+  /// Parses the rest of a `match` on several values, after the first one.
+  fn parse_multi_match(&mut self, fst: (Option<Name>, Term), ini_idx: usize) -> ParseResult<Term> {
+    let mut args = vec![fst];
+    while !self.starts_with("{") {
+      if self.starts_with_keyword("with") {
+        let msg = "'with' clauses are not supported when matching on more than one value.";
+        let with_idx = *self.index();
+        return self.with_ctx(Err(msg), with_idx..with_idx + 4);
+      }
+      args.push(self.parse_match_arg()?);
+      self.skip_trivia();
+    }
+    let end_idx = *self.index();
+    self.consume("{")?;
+    let arms = self.list_like(|p| p.parse_multi_match_arm(args.len()), "", "}", ";", false, 1)?;
+    Ok(multi_match(args, arms, Source::Local(ini_idx..end_idx)))
+  }
+
+  /// An arm of a `match` on several values, with one pattern for each value.
+  fn parse_multi_match_arm(&mut self, arity: usize) -> ParseResult<Rule> {
+    self.try_consume("|");
+    self.skip_trivia();
+    let ini_idx = *self.index();
+    let mut pats = vec![];
+    while !self.starts_with(":") {
+      pats.push(self.parse_pattern(false)?);
+      self.skip_trivia();
+    }
+    if pats.len() != arity {
+      let msg = format!("Expected {arity} patterns, one for each matched value, but found {}.", pats.len());
+      let end_idx = *self.index();
+      return self.with_ctx(Err(msg), ini_idx..end_idx);
+    }
+    self.consume(":")?;
+    let body = self.parse_term()?;
+    Ok(Rule { pats, body, guard: None })
+  }
+
+  /// Errors on a 1-tuple like `(a,)`, called right after consuming the comma that follows the element `n_els`.
+  fn check_not_single_tuple(&mut self, n_els: usize) -> ParseResult<()> {
+    self.skip_trivia();
+    if n_els == 1 && self.starts_with(")") {
+      let ini_idx = *self.index();
+      let msg = "Tuples must have at least two elements.";
+      return self.with_ctx(Err(msg), ini_idx..ini_idx + 1);
+    }
+    Ok(())
+  }
+
   fn add_fun_def(
     &mut self,
     name: &Name,
@@ -943,12 +1221,14 @@ impl<'a> TermParser<'a> {
       // Trying to add a new rule to a previous definition, coming from a different rule.
       (Some(def), Some(_)) => {
         let msg = Self::redefinition_of_function_msg(def.is_builtin(), name);
-        return self.with_ctx(Err(msg), span);
+        let prev = def.source.clone();
+        return self.redefinition_err(msg, span, &prev);
       }
       // Trying to add a new rule to a previous definition, coming from another kind of top-level.
       (Some(def), None) => {
         let msg = Self::redefinition_of_function_msg(def.is_builtin(), name);
-        return self.with_ctx(Err(msg), span);
+        let prev = def.source.clone();
+        return self.redefinition_err(msg, span, &prev);
       }
       // Adding the first rule of a new definition
       (None, _) => {
@@ -1012,7 +1292,8 @@ impl<'a> TermParser<'a> {
     } else {
       for ctr in adt.ctrs.keys() {
         if let Some(builtin) = book.contains_builtin_def(ctr) {
-          return Err(TermParser::redefinition_of_function_msg(builtin, ctr));
+          let msg = TermParser::redefinition_of_function_msg(builtin, ctr);
+          return self.with_ctx(Err(msg), span);
         }
         match book.ctrs.entry(ctr.clone()) {
           indexmap::map::Entry::Vacant(e) => _ = e.insert(nam.clone()),
@@ -1050,9 +1331,10 @@ impl<'a> TermParser<'a> {
     book: &mut ParseBook,
     span: Range<usize>,
   ) -> ParseResult<()> {
-    if let Some(builtin) = book.contains_builtin_def(name) {
-      let msg = Self::redefinition_of_function_msg(builtin, name);
-      return self.with_ctx(Err(msg), span);
+    if let Some(prev) = book.def_source(name) {
+      let msg = Self::redefinition_of_function_msg(prev.is_builtin(), name);
+      let prev = prev.clone();
+      return self.redefinition_err(msg, span, &prev);
     }
     if book.ctrs.contains_key(name) {
       let msg = Self::redefinition_of_constructor_msg(name);
@@ -1117,28 +1399,17 @@ impl<'a> Parser<'a> for TermParser<'a> {
         continue;
       }
       if c == '#' {
-        self.advance_one();
-        if let Some(c) = self.peek_one() {
-          if c == '{' {
-            self.advance_one();
-            while let Some(c) = self.peek_one() {
+        if self.starts_with_multi_line_comment() {
+          // If the comment is not closed, stop at it so that the next error reports it.
+          if self.skip_multi_line_comment().is_none() {
+            break;
+          }
+        } else {
+          while let Some(c) = self.peek_one() {
+            if c != '\n' {
               self.advance_one();
-              if c == '#' {
-                if let Some('}') = self.peek_one() {
-                  self.advance_one();
-                  break;
-                } else {
-                  self.advance_one();
-                }
-              }
-            }
-          } else {
-            while let Some(c) = self.peek_one() {
-              if c != '\n' {
-                self.advance_one();
-              } else {
-                break;
-              }
+            } else {
+              break;
             }
           }
         }
@@ -1149,6 +1420,101 @@ impl<'a> Parser<'a> for TermParser<'a> {
   }
 }
 
+/// Builds the lambda of an operator section, with a new variable for each missing operand.
+fn oper_section(opr: Op, fst: Option<Term>, snd: Option<Term>) -> Term {
+  let mut vars = vec![];
+  let mut operand = |term: Option<Term>, nam: &str| {
+    term.unwrap_or_else(|| {
+      vars.push(Name::new(nam));
+      Term::Var { nam: Name::new(nam) }
+    })
+  };
+  let fst = operand(fst, "%fst");
+  let snd = operand(snd, "%snd");
+  let bod = Term::Oper { opr, fst: Box::new(fst), snd: Box::new(snd) };
+  vars.into_iter().rfold(bod, |bod, nam| Term::lam(Pattern::Var(Some(nam)), bod))
+}
+
+/// Builds the local definition of a `letrec`, where the bound values can refer to themselves.
+///
+/// A single binding is a local definition with no arguments.
+/// Mutually recursive bindings share a definition that returns one of the values given its index:
+/// ```bend
+/// letrec a = A, b = B; nxt
+///
+/// // Becomes
+/// def a__b__letrec %sel =
+///   use a = (a__b__letrec 0); use b = (a__b__letrec 1)
+///   switch %sel { 0: A; _: B }
+/// use a = (a__b__letrec 0); use b = (a__b__letrec 1)
+/// nxt
+/// ```
+fn letrec(binds: Vec<(Name, Term)>, nxt: Term, source: Source) -> Term {
+  let (nams, mut vals): (Vec<_>, Vec<_>) = binds.into_iter().unzip();
+  if let [nam] = &nams[..] {
+    let rule = Rule { pats: vec![], body: vals.pop().unwrap(), guard: None };
+    let def = FunDefinition::new(nam.clone(), vec![rule], source);
+    return Term::Def { def, nxt: Box::new(nxt) };
+  }
+
+  let def_nam = Name::new(format!("{}__letrec", nams.iter().join("__")));
+  let sel = Name::new("%sel");
+  let uses = |nxt: Term| {
+    nams.iter().enumerate().rfold(nxt, |nxt, (i, nam)| Term::Use {
+      nam: Some(nam.clone()),
+      val: Box::new(Term::app(Term::Var { nam: def_nam.clone() }, Term::Num { val: Num::U24(i as u32) })),
+      nxt: Box::new(nxt),
+    })
+  };
+  let body = Term::Swt {
+    arg: Box::new(Term::Var { nam: sel.clone() }),
+    bnd: Some(sel.clone()),
+    with_bnd: vec![],
+    with_arg: vec![],
+    pred: Some(Name::new(format!("{sel}-{}", vals.len() - 1))),
+    arms: vals,
+  };
+  let rule = Rule { pats: vec![Pattern::Var(Some(sel))], body: uses(body), guard: None };
+  let def = FunDefinition::new(def_nam.clone(), vec![rule], source);
+  Term::Def { def, nxt: Box::new(uses(nxt)) }
+}
+
+/// Builds a `match` on several values as a local definition with a rule for each arm,
+/// so that it's compiled and checked for exhaustiveness like the rules of a function.
+///
+/// The values bound to a name, like `match a = (f x) b`, are bound with a `let` outside,
+/// so that the arms can still use them.
+/// ```bend
+/// match a = (f x) b { T/A T/A: 1; _ _: 0 }
+///
+/// // Becomes
+/// let a = (f x)
+/// def match__multi T/A T/A = 1
+///     match__multi _ _ = 0
+/// (match__multi a b)
+/// ```
+fn multi_match(args: Vec<(Option<Name>, Term)>, rules: Vec<Rule>, source: Source) -> Term {
+  let def_nam = Name::new("match__multi");
+  let mut lets = vec![];
+  let mut vals = vec![];
+  for (bnd, arg) in args {
+    match bnd {
+      Some(bnd) if bnd != "%arg" && !matches!(&arg, Term::Var { nam } if *nam == bnd) => {
+        lets.push((bnd.clone(), arg));
+        vals.push(Term::Var { nam: bnd });
+      }
+      _ => vals.push(arg),
+    }
+  }
+  let def = FunDefinition::new(def_nam.clone(), rules, source);
+  let term = Term::Def { def, nxt: Box::new(Term::call(Term::Var { nam: def_nam }, vals)) };
+  lets.into_iter().rfold(term, |nxt, (nam, val)| Term::Let {
+    pat: Box::new(Pattern::Var(Some(nam))),
+    val: Box::new(val),
+    nxt: Box::new(nxt),
+  })
+}
+
 pub fn is_name_char(c: char) -> bool {
   c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-' || c == '/'
 }
@@ -1157,6 +1523,16 @@ pub fn is_num_char(c: char) -> bool {
   "0123456789+-".contains(c)
 }
 
+/// The line and column of a byte index in the input, both starting at 1.
+/// Columns are counted in characters, not bytes.
+pub fn line_col(input: &str, idx: usize) -> (usize, usize) {
+  let before = &input[..idx.min(input.len())];
+  let line = before.matches('\n').count() + 1;
+  let line_start = before.rfind('\n').map_or(0, |nl| nl + 1);
+  let col = before[line_start..].chars().count() + 1;
+  (line, col)
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Indent {
   Val(isize),
@@ -1284,34 +1660,18 @@ pub trait ParserCommons<'a>: Parser<'a> {
         continue;
       }
       if c == '#' {
-        self.advance_one();
-        char_count += 1;
-        if let Some(c) = self.peek_one() {
-          if c == '{' {
-            self.advance_one();
-            char_count += 1;
-            while let Some(c) = self.peek_one() {
+        if self.starts_with_multi_line_comment() {
+          match self.skip_multi_line_comment() {
+            Some(skipped) => char_count += skipped,
+            None => return self.unterminated_comment_err(),
+          }
+        } else {
+          while let Some(c) = self.peek_one() {
+            if c != '\n' {
               self.advance_one();
               char_count += 1;
-              if c == '#' {
-                if let Some('}') = self.peek_one() {
-                  self.advance_one();
-                  char_count += 1;
-                  break;
-                } else {
-                  self.advance_one();
-                  char_count += 1;
-                }
-              }
-            }
-          } else {
-            while let Some(c) = self.peek_one() {
-              if c != '\n' {
-                self.advance_one();
-                char_count += 1;
-              } else {
-                break;
-              }
+            } else {
+              break;
             }
           }
         }
@@ -1328,7 +1688,52 @@ pub trait ParserCommons<'a>: Parser<'a> {
     Ok(())
   }
 
+  fn starts_with_multi_line_comment(&mut self) -> bool {
+    let idx = *self.index();
+    self.input()[idx..].starts_with("#{")
+  }
+
+  /// Skips a multi-line comment `#{ ... #}` starting at the current index.
+  /// Comments can be nested, so each `#{` must have its own `#}`.
+  ///
+  /// Returns how many characters were skipped,
+  /// or `None` without advancing if the comment is never closed.
+  fn skip_multi_line_comment(&mut self) -> Option<isize> {
+    let ini_idx = *self.index();
+    let mut depth = 0;
+    let mut char_count = 0;
+    loop {
+      let rest = &self.input()[*self.index()..];
+      if rest.starts_with("#{") {
+        depth += 1;
+      } else if rest.starts_with("#}") {
+        depth -= 1;
+      } else if self.advance_one().is_some() {
+        char_count += 1;
+        continue;
+      } else {
+        *self.index() = ini_idx;
+        return None;
+      }
+      *self.index() += 2;
+      char_count += 2;
+      if depth == 0 {
+        return Some(char_count);
+      }
+    }
+  }
+
+  fn unterminated_comment_err<T>(&mut self) -> ParseResult<T> {
+    let idx = *self.index();
+    let msg = "Multi-line comment is never closed, expected a matching '#}'.".to_string();
+    self.with_ctx(Err(msg), idx..idx + 2)
+  }
+
   fn expected_spanned<T>(&mut self, exp: &str, span: Range<usize>) -> ParseResult<T> {
+    // Skipping trivia stops at unclosed comments, which is the actual error.
+    if self.starts_with_multi_line_comment() {
+      return self.unterminated_comment_err();
+    }
     let is_eof = self.is_eof();
     let detected = DisplayFn(|f| if is_eof { write!(f, " end of input") } else { Ok(()) });
     let msg = format!("\x1b[1m- expected:\x1b[0m {}\n\x1b[1m- detected:\x1b[0m{}", exp, detected);
@@ -1337,11 +1742,26 @@ pub trait ParserCommons<'a>: Parser<'a> {
 
   fn with_ctx<T>(&mut self, res: Result<T, impl std::fmt::Display>, span: Range<usize>) -> ParseResult<T> {
     res.map_err(|msg| {
+      let (line, col) = line_col(self.input(), span.start);
       let ctx = highlight_error(span.start, span.end, self.input());
-      format!("{msg}\n{ctx}")
+      format!("{msg}\nAt line {line}, column {col}:\n{ctx}")
     })
   }
 
+  /// Like `with_ctx`, but also highlights the previous definition when it's in the same file.
+  fn redefinition_err<T>(&mut self, msg: String, span: Range<usize>, prev: &Source) -> ParseResult<T> {
+    let err = self.with_ctx(Err(msg), span);
+    match prev {
+      Source::Local(prev) => err.map_err(|msg| {
+        // Imp definitions span until the next top-level item.
+        let end = prev.start + self.input()[prev.clone()].trim_end().len();
+        let ctx = highlight_error(prev.start, end, self.input());
+        format!("{msg}\nPreviously defined here:\n{ctx}")
+      }),
+      _ => err,
+    }
+  }
+
   /// Consumes text if the input starts with it or trivia. Otherwise, do nothing.
   fn try_consume(&mut self, text: &str) -> bool {
     self.skip_trivia();
@@ -1364,12 +1784,7 @@ pub trait ParserCommons<'a>: Parser<'a> {
   }
 
   fn try_parse_keyword(&mut self, keyword: &str) -> bool {
-    if !self.starts_with(keyword) {
-      return false;
-    }
-    let input = &self.input()[*self.index() + keyword.len()..];
-    let next_is_name = input.chars().next().map_or(false, is_name_char);
-    if !next_is_name {
+    if self.starts_with_keyword(keyword) {
       self.consume_exactly(keyword).unwrap();
       true
     } else {
@@ -1377,12 +1792,21 @@ pub trait ParserCommons<'a>: Parser<'a> {
     }
   }
 
+  /// Checks if the input starts with the keyword, and not just with a name that begins like it.
+  fn starts_with_keyword(&mut self, keyword: &str) -> bool {
+    if !self.starts_with(keyword) {
+      return false;
+    }
+    let input = &self.input()[*self.index() + keyword.len()..];
+    !input.chars().next().is_some_and(is_name_char)
+  }
+
   fn parse_keyword(&mut self, keyword: &str) -> ParseResult<()> {
     let ini_idx = *self.index();
     self.consume_exactly(keyword)?;
     let end_idx = *self.index();
     let input = &self.input()[*self.index()..];
-    let next_is_name = input.chars().next().map_or(false, is_name_char);
+    let next_is_name = input.chars().next().is_some_and(is_name_char);
     if !next_is_name {
       Ok(())
     } else {
@@ -1519,6 +1943,7 @@ pub trait ParserCommons<'a>: Parser<'a> {
   }
 
   fn parse_u32(&mut self) -> ParseResult<u32> {
+    let ini_idx = *self.index();
     let radix = match self.peek_many(2) {
       Some("0x") => {
         self.advance_many(2);
@@ -1533,22 +1958,26 @@ pub trait ParserCommons<'a>: Parser<'a> {
     let num_str = self.take_while(move |c| c.is_digit(radix as u32) || c == '_');
     let num_str = num_str.chars().filter(|c| *c != '_').collect::<String>();
 
-    let next_is_hex = self.peek_one().map_or(false, |c| "0123456789abcdefABCDEF".contains(c));
+    let next_is_hex = self.peek_one().is_some_and(|c| "0123456789abcdefABCDEF".contains(c));
     if next_is_hex || num_str.is_empty() {
       self.expected(format!("valid {radix} digit").as_str())
     } else {
-      u32::from_str_radix(&num_str, radix as u32).map_err(|e| e.to_string())
+      match u32::from_str_radix(&num_str, radix as u32) {
+        Ok(num) => Ok(num),
+        Err(_) => self.num_range_err(ini_idx, "U32"),
+      }
     }
   }
 
-  fn u32_with_radix(&mut self, radix: Radix) -> ParseResult<u32> {
+  /// Parses the digits of a number, returning `None` if they don't fit in a u32.
+  fn u32_with_radix(&mut self, radix: Radix) -> ParseResult<Option<u32>> {
     let num_str = self.take_while(move |c| c.is_digit(radix as u32) || c == '_');
     let num_str = num_str.chars().filter(|c| *c != '_').collect::<String>();
-    let next_is_hex = self.peek_one().map_or(false, |c| "0123456789abcdefABCDEF".contains(c));
+    let next_is_hex = self.peek_one().is_some_and(|c| "0123456789abcdefABCDEF".contains(c));
     if next_is_hex || num_str.is_empty() {
       self.expected(format!("valid {radix} digit").as_str())
     } else {
-      u32::from_str_radix(&num_str, radix as u32).map_err(|e| e.to_string())
+      Ok(u32::from_str_radix(&num_str, radix as u32).ok())
     }
   }
 
@@ -1585,16 +2014,20 @@ pub trait ParserCommons<'a>: Parser<'a> {
     };
 
     if let Some(frac) = frac {
+      let Some(num) = num else { return self.num_range_err(ini_idx, "F24") };
       let sign = sign.unwrap_or(1);
       return Ok(Num::F24(sign as f32 * (num as f32 + frac)));
     }
 
+    let Some(num) = num else {
+      return self.num_range_err(ini_idx, if sign.is_some() { "I24" } else { "U24" });
+    };
     if let Some(sign) = sign {
-      let num = sign * num as i32;
+      let num = sign * num as i64;
       if !(-0x00800000..=0x007fffff).contains(&num) {
         return self.num_range_err(ini_idx, "I24");
       }
-      Ok(Num::I24(num))
+      Ok(Num::I24(num as i32))
     } else {
       if num >= 1 << 24 {
         return self.num_range_err(ini_idx, "U24");
@@ -1603,12 +2036,62 @@ pub trait ParserCommons<'a>: Parser<'a> {
     }
   }
 
+  /// Checks if the input starts with a "-" that is not directly followed by a digit,
+  /// which means it's a unary negation instead of part of a signed number literal.
+  fn starts_with_unary_neg(&mut self) -> bool {
+    self.starts_with("-") && !self.peek_many(2).is_some_and(|s| s.ends_with(|c: char| c.is_ascii_digit()))
+  }
+
+  /// Consumes the "-" of a unary negation.
+  /// If it's followed by a number literal, like `- 5`, parses it and returns the negated number.
+  fn parse_neg_number(&mut self) -> ParseResult<Option<Num>> {
+    let ini_idx = *self.index();
+    self.consume_exactly("-")?;
+    self.skip_trivia_inline()?;
+    if !self.peek_one().is_some_and(|c| c.is_ascii_digit()) {
+      return Ok(None);
+    }
+    match self.parse_number()? {
+      Num::U24(num) if num <= 0x00800000 => Ok(Some(Num::I24(-(num as i32)))),
+      Num::F24(num) => Ok(Some(Num::F24(-num))),
+      _ => self.num_range_err(ini_idx, "I24"),
+    }
+  }
+
   fn num_range_err<T>(&mut self, ini_idx: usize, typ: &str) -> ParseResult<T> {
     let msg = format!("\x1b[1mNumber literal outside of range for {}.\x1b[0m", typ);
     let end_idx = *self.index();
     self.with_ctx(Err(msg), ini_idx..end_idx)
   }
 
+  /// Parses a single character surrounded by "'", like 'a' or '\n'.
+  /// Unlike `parse_quoted_char`, reports a clear error for literals with more than one character.
+  fn parse_char_literal(&mut self) -> ParseResult<char> {
+    self.skip_trivia();
+    let ini_idx = *self.index();
+    self.consume_exactly("'")?;
+    if self.is_eof() {
+      return self.with_ctx(Err("Unterminated character literal."), ini_idx..ini_idx + 1);
+    }
+    let chr = self.parse_char()?;
+    if self.starts_with("'") {
+      self.advance_one();
+      return Ok(chr);
+    }
+    // A literal can't span lines, so it's only closed if there's a "'" before the end of the line.
+    let rest = &self.input()[*self.index()..];
+    match rest.find(['\'', '\n']) {
+      Some(len) if rest[len..].starts_with('\'') => {
+        let end_idx = *self.index() + len + 1;
+        self.with_ctx(Err("Character literal must contain exactly one character."), ini_idx..end_idx)
+      }
+      _ => {
+        let end_idx = *self.index();
+        self.with_ctx(Err("Unterminated character literal."), ini_idx..end_idx)
+      }
+    }
+  }
+
   /// Parses up to 4 base64 characters surrounded by "`".
   /// Joins the characters into a u24 and returns it.
   fn parse_quoted_symbol(&mut self) -> ParseResult<u32> {
@@ -1695,3 +2178,39 @@ impl std::fmt::Display for Radix {
     }
   }
 }
+
+#[test]
+fn parse_book_recovers_from_errors() {
+  let code = "
+foo = (+ 1
+bar = 2
+(baz 0) = )
+(baz n) = n
+main = (bar (baz 1))
+";
+  let (book, errs) = TermParser::new(code).parse_book_recovering(ParseBook::default(), false);
+  assert_eq!(errs.len(), 2);
+  assert!(errs[0].contains("At line 3, column 5"));
+  assert!(errs[1].contains("At line 4, column 11"));
+  // The other rules of `baz` are not redefinitions.
+  let defs = book.fun_defs.keys().map(|nam| nam.to_string()).collect::<Vec<_>>();
+  assert_eq!(defs, ["bar", "baz", "main"]);
+}
+
+#[test]
+fn parse_datatype_decls() {
+  let parse = |code: &str| TermParser::new(code).parse_book(ParseBook::default(), false);
+  let book = parse("type Maybe = None | (Some ~x val)").unwrap();
+  let ctrs = &book.adts[&Name::new("Maybe")].ctrs;
+  let arities = ctrs.iter().map(|(nam, fields)| (nam.to_string(), fields.len())).collect::<Vec<_>>();
+  assert_eq!(arities, [("Maybe/None".to_string(), 0), ("Maybe/Some".to_string(), 2)]);
+  assert!(ctrs[&Name::new("Maybe/Some")][0].rec);
+  let types = book.ctrs.iter().map(|(ctr, adt)| format!("{ctr}: {adt}")).collect::<Vec<_>>();
+  assert_eq!(types, ["Maybe/None: Maybe", "Maybe/Some: Maybe"]);
+
+  let err = parse("type Maybe = None\ntype Maybe = (Some x)").unwrap_err();
+  assert!(err.contains("Redefinition of type 'Maybe'."));
+  // Constructor names are prefixed by their type, but they can still end up reused.
+  let err = parse("type Foo/Bar = Baz\ntype Foo = (Bar/Baz x)").unwrap_err();
+  assert!(err.contains("Redefinition of constructor 'Foo/Bar/Baz'."));
+}
@@ -0,0 +1,332 @@
+use crate::{
+  fun::{Book, FanKind, Name, Num, Pattern, Tag, Term},
+  maybe_grow,
+};
+
+impl Term {
+  /// Reduces a term to its normal form by rewriting it directly,
+  /// instead of compiling it to an interaction net and running it on HVM.
+  ///
+  /// Expects a term of the core language, like the bodies of a desugared book,
+  /// where references to the definitions of `book` are expanded when they're used.
+  /// Like in HVM, a reference that is not used, for example one inside a lambda, is left as is.
+  ///
+  /// Duplications and superpositions follow HVM's rules: a duplication and a superposition
+  /// with the same label annihilate, while different labels commute.
+  /// Other values are copied once they're in weak head normal form, which gives different results
+  /// than HVM if the copied value contains a superposition with the same label as the duplication.
+  /// Unscoped variables and native hvm definitions are not reduced.
+  ///
+  /// Returns an error if the term doesn't reach a normal form in `max_steps` rewrites.
+  pub fn reduce(&self, book: &Book, max_steps: usize) -> Result<Term, String> {
+    let mut reducer = Reducer { book, steps: 0, max_steps, name_count: 0 };
+    let mut term = reducer.copy(self);
+    reducer.head(&mut term)?;
+    reducer.normalize(&mut term)?;
+    term.make_var_names_unique();
+    Ok(term)
+  }
+}
+
+struct Reducer<'a> {
+  book: &'a Book,
+  steps: usize,
+  max_steps: usize,
+  name_count: usize,
+}
+
+impl Reducer<'_> {
+  fn step(&mut self) -> Result<(), String> {
+    if self.steps == self.max_steps {
+      return Err(format!("Term didn't reach a normal form after {} steps.", self.max_steps));
+    }
+    self.steps += 1;
+    Ok(())
+  }
+
+  fn normalize(&mut self, term: &mut Term) -> Result<(), String> {
+    maybe_grow(|| {
+      self.whnf(term)?;
+      for child in term.children_mut() {
+        self.normalize(child)?;
+      }
+      Ok(())
+    })
+  }
+
+  /// Reduces a term that is being eliminated, expanding it if it's a reference.
+  fn head(&mut self, term: &mut Term) -> Result<(), String> {
+    loop {
+      self.whnf(term)?;
+      let Term::Ref { nam } = term else { return Ok(()) };
+      let Some(def) = self.book.defs.get(nam) else { return Ok(()) };
+      *term = self.copy(&def.rule().body);
+      self.step()?;
+    }
+  }
+
+  /// Reduces a term to weak head normal form.
+  fn whnf(&mut self, term: &mut Term) -> Result<(), String> {
+    maybe_grow(|| loop {
+      let new = match term {
+        Term::App { tag, fun, arg } => {
+          self.head(fun)?;
+          match fun.as_mut() {
+            Term::Lam { pat, bod, .. } => Term::Let { pat: take_pat(pat), val: take(arg), nxt: take(bod) },
+            Term::Era => Term::Era,
+            Term::Fan { fan: FanKind::Dup, tag: sup_tag, els } => {
+              let tag = tag.clone();
+              let (sup_tag, els) = (sup_tag.clone(), std::mem::take(els));
+              self.distribute(sup_tag, els, vec![*take(arg)], |fun, mut args| Term::App {
+                tag: tag.clone(),
+                fun: Box::new(fun),
+                arg: Box::new(args.pop().unwrap()),
+              })
+            }
+            _ => return Ok(()),
+          }
+        }
+        Term::Let { pat, val, nxt } => {
+          match pat.as_ref() {
+            Pattern::Var(_) => {}
+            // Duplicating a reference copies it, like in HVM.
+            Pattern::Fan(FanKind::Dup, ..) => self.whnf(val)?,
+            Pattern::Fan(FanKind::Tup, ..) => self.head(val)?,
+            _ => return Ok(()),
+          }
+          match (pat.as_mut(), val.as_mut()) {
+            (Pattern::Var(None), _) => *take(nxt),
+            (Pattern::Var(Some(nam)), val) => {
+              let mut nxt = *take(nxt);
+              self.subst(&mut nxt, nam, val);
+              nxt
+            }
+            // Annihilation
+            (Pattern::Fan(fan, tag, pats), Term::Fan { fan: val_fan, tag: val_tag, els })
+              if fan == val_fan && tag == val_tag && pats.len() == els.len() =>
+            {
+              let (pats, els) = (std::mem::take(pats), std::mem::take(els));
+              pats.into_iter().zip(els).rfold(*take(nxt), |nxt, (pat, val)| Term::Let {
+                pat: Box::new(pat),
+                val: Box::new(val),
+                nxt: Box::new(nxt),
+              })
+            }
+            // Commutation
+            (Pattern::Fan(fan, tag, pats), Term::Fan { fan: FanKind::Dup, tag: sup_tag, els })
+              if pats.iter().all(|pat| matches!(pat, Pattern::Var(_))) =>
+            {
+              let (pats, els) = (std::mem::take(pats), std::mem::take(els));
+              self.commute(*fan, tag.clone(), pats, sup_tag.clone(), els, *take(nxt))
+            }
+            (Pattern::Fan(FanKind::Dup, _, pats), val) => {
+              let pats = std::mem::take(pats);
+              pats.into_iter().rfold(*take(nxt), |nxt, pat| Term::Let {
+                pat: Box::new(pat),
+                val: Box::new(self.copy(val)),
+                nxt: Box::new(nxt),
+              })
+            }
+            (Pattern::Fan(FanKind::Tup, _, pats), Term::Era) => {
+              let pats = std::mem::take(pats);
+              pats.into_iter().rfold(*take(nxt), |nxt, pat| Term::Let {
+                pat: Box::new(pat),
+                val: Box::new(Term::Era),
+                nxt: Box::new(nxt),
+              })
+            }
+            _ => return Ok(()),
+          }
+        }
+        Term::Oper { opr, fst, snd } => {
+          self.head(fst)?;
+          self.head(snd)?;
+          let opr = *opr;
+          match (fst.as_mut(), snd.as_mut()) {
+            (Term::Num { val: fst }, Term::Num { val: snd }) => match opr.compute(*fst, *snd) {
+              Some(val) => Term::Num { val },
+              None => return Ok(()),
+            },
+            (Term::Fan { fan: FanKind::Dup, tag, els }, snd) => {
+              let (tag, els, snd) = (tag.clone(), std::mem::take(els), std::mem::take(snd));
+              self.distribute(tag, els, vec![snd], |fst, mut snd| Term::Oper {
+                opr,
+                fst: Box::new(fst),
+                snd: Box::new(snd.pop().unwrap()),
+              })
+            }
+            (fst, Term::Fan { fan: FanKind::Dup, tag, els }) => {
+              let (tag, els, fst) = (tag.clone(), std::mem::take(els), std::mem::take(fst));
+              self.distribute(tag, els, vec![fst], |snd, mut fst| Term::Oper {
+                opr,
+                fst: Box::new(fst.pop().unwrap()),
+                snd: Box::new(snd),
+              })
+            }
+            _ => return Ok(()),
+          }
+        }
+        Term::Swt { arg, arms, .. } if arms.len() == 2 => {
+          self.head(arg)?;
+          match arg.as_mut() {
+            Term::Num { val: Num::U24(0) } => std::mem::take(&mut arms[0]),
+            Term::Num { val: Num::U24(val) } => {
+              Term::app(std::mem::take(&mut arms[1]), Term::Num { val: Num::U24(*val - 1) })
+            }
+            Term::Fan { fan: FanKind::Dup, tag, els } => {
+              let (tag, els, arms) = (tag.clone(), std::mem::take(els), std::mem::take(arms));
+              self.distribute(tag, els, arms, |arg, arms| Term::Swt {
+                arg: Box::new(arg),
+                bnd: None,
+                with_bnd: vec![],
+                with_arg: vec![],
+                pred: None,
+                arms,
+              })
+            }
+            _ => return Ok(()),
+          }
+        }
+        _ => return Ok(()),
+      };
+      *term = new;
+      self.step()?;
+    })
+  }
+
+  /// Applies an eliminator to each element of a superposition,
+  /// duplicating the other terms of the eliminator with the label of the superposition.
+  ///
+  /// `{a b}` eliminated by `build` with `others` becomes
+  /// `let {x0 x1} = others[0]; ...; {build(a, [x0, ...]) build(b, [x1, ...])}`.
+  fn distribute(
+    &mut self,
+    tag: Tag,
+    els: Vec<Term>,
+    others: Vec<Term>,
+    build: impl Fn(Term, Vec<Term>) -> Term,
+  ) -> Term {
+    let dups =
+      others.into_iter().map(|other| (els.iter().map(|_| self.fresh("d")).collect::<Vec<_>>(), other));
+    let dups = dups.collect::<Vec<_>>();
+    let els = els.into_iter().enumerate().map(|(i, el)| {
+      let others = dups.iter().map(|(nams, _)| Term::Var { nam: nams[i].clone() }).collect();
+      build(el, others)
+    });
+    let sup = Term::Fan { fan: FanKind::Dup, tag: tag.clone(), els: els.collect() };
+    dups.into_iter().rfold(sup, |nxt, (nams, val)| Term::Let {
+      pat: Box::new(Pattern::Fan(
+        FanKind::Dup,
+        tag.clone(),
+        nams.into_iter().map(|n| Pattern::Var(Some(n))).collect(),
+      )),
+      val: Box::new(val),
+      nxt: Box::new(nxt),
+    })
+  }
+
+  /// `let #A{a b} = #B{x y}; nxt` becomes
+  /// `let #A{a0 b0} = x; let #A{a1 b1} = y; let a = #B{a0 a1}; let b = #B{b0 b1}; nxt`.
+  fn commute(
+    &mut self,
+    fan: FanKind,
+    tag: Tag,
+    pats: Vec<Pattern>,
+    sup_tag: Tag,
+    els: Vec<Term>,
+    nxt: Term,
+  ) -> Term {
+    let nams =
+      els.iter().map(|_| pats.iter().map(|_| self.fresh("c")).collect::<Vec<_>>()).collect::<Vec<_>>();
+    let binds = pats.into_iter().enumerate().rfold(nxt, |nxt, (j, pat)| Term::Let {
+      pat: Box::new(pat),
+      val: Box::new(Term::Fan {
+        fan: FanKind::Dup,
+        tag: sup_tag.clone(),
+        els: nams.iter().map(|nams| Term::Var { nam: nams[j].clone() }).collect(),
+      }),
+      nxt: Box::new(nxt),
+    });
+    nams.into_iter().zip(els).rfold(binds, |nxt, (nams, el)| Term::Let {
+      pat: Box::new(Pattern::Fan(
+        fan,
+        tag.clone(),
+        nams.into_iter().map(|n| Pattern::Var(Some(n))).collect(),
+      )),
+      val: Box::new(el),
+      nxt: Box::new(nxt),
+    })
+  }
+
+  /// Replaces the variable `nam` with a copy of `val` in each place it's used.
+  fn subst(&mut self, term: &mut Term, nam: &Name, val: &Term) {
+    maybe_grow(|| {
+      if let Term::Var { nam: var } = term {
+        if var == nam {
+          *term = self.copy(val);
+        }
+        return;
+      }
+      for (child, mut binds) in term.children_mut_with_binds() {
+        if !binds.any(|bind| bind.as_ref() == Some(nam)) {
+          self.subst(child, nam, val);
+        }
+      }
+    })
+  }
+
+  /// Copies a term, giving new names to the variables it binds so that they don't clash with other copies.
+  fn copy(&mut self, term: &Term) -> Term {
+    let mut term = term.clone();
+    self.rename_binds(&mut term, &mut vec![]);
+    term
+  }
+
+  fn rename_binds(&mut self, term: &mut Term, renames: &mut Vec<(Name, Name)>) {
+    maybe_grow(|| {
+      if let Term::Var { nam } = term {
+        if let Some((_, new)) = renames.iter().rev().find(|(old, _)| old == nam) {
+          *nam = new.clone();
+        }
+        return;
+      }
+      for (child, binds) in term.children_mut_with_binds_mut() {
+        let len = renames.len();
+        for bind in binds.flatten() {
+          let new = self.fresh(bind);
+          renames.push((std::mem::replace(bind, new.clone()), new));
+        }
+        self.rename_binds(child, renames);
+        renames.truncate(len);
+      }
+    })
+  }
+
+  fn fresh(&mut self, nam: &str) -> Name {
+    self.name_count += 1;
+    let nam = nam.split('%').next().unwrap();
+    Name::new(format!("{nam}%{}", self.name_count))
+  }
+}
+
+fn take(term: &mut Box<Term>) -> Box<Term> {
+  Box::new(std::mem::take(term.as_mut()))
+}
+
+fn take_pat(pat: &mut Box<Pattern>) -> Box<Pattern> {
+  Box::new(std::mem::replace(pat.as_mut(), Pattern::Var(None)))
+}
+
+#[test]
+fn reduce_step_limit() {
+  use crate::fun::load_book::parse_test_book;
+  let code = "loop = λx (loop x)\nlazy = λx loop\nmain = (loop 0)";
+  let book = parse_test_book(code);
+
+  // References that are not used are not expanded.
+  assert_eq!(Term::r#ref("lazy").reduce(&book, 10).unwrap().to_string(), "λa loop");
+  assert_eq!(
+    Term::r#ref("main").reduce(&book, 1000).unwrap_err(),
+    "Term didn't reach a normal form after 1000 steps."
+  );
+}
@@ -0,0 +1,284 @@
+use crate::{
+  fun::{Book, Name, Pattern, Term},
+  hvm::{net_trees_mut, tree_children_mut},
+  maybe_grow,
+};
+use hvm::ast::Tree;
+use indexmap::IndexMap;
+
+impl Book {
+  /// Renames a definition, updating every reference to it.
+  ///
+  /// References can be either already resolved `Ref`s or free variables with the definition's name.
+  /// If the definition is a constructor, it's also renamed in its datatype,
+  /// in the patterns of rules and in the arms of `match` and `fold` terms.
+  ///
+  /// Fails if `new` is already the name of a definition, a native HVM definition, a constructor or a datatype.
+  pub fn rename_def(&mut self, old: &Name, new: Name) -> Result<(), String> {
+    if !self.defs.contains_key(old) && !self.ctrs.contains_key(old) {
+      return Err(format!("Definition '{old}' doesn't exist."));
+    }
+    if self.defs.contains_key(&new)
+      || self.hvm_defs.contains_key(&new)
+      || self.ctrs.contains_key(&new)
+      || self.adts.contains_key(&new)
+    {
+      return Err(format!("Can't rename '{old}' to '{new}', since '{new}' is already defined."));
+    }
+
+    // Constructors only have a definition after the datatypes are encoded.
+    if let Some(def) = rename_key(&mut self.defs, old, new.clone()) {
+      def.name = new.clone();
+    }
+    let is_ctr = self.ctrs.contains_key(old);
+    if let Some(adt) = self.ctrs.get(old) {
+      rename_key(&mut self.adts.get_mut(adt).unwrap().ctrs, old, new.clone());
+      rename_key(&mut self.ctrs, old, new.clone());
+    }
+
+    for def in self.defs.values_mut() {
+      for rule in def.rules.iter_mut() {
+        for pat in rule.pats.iter_mut() {
+          pat.rename_ctr(old, &new, is_ctr);
+        }
+        if !rule.pats.iter().flat_map(Pattern::binds).any(|bind| bind == old) {
+          if let Some(guard) = &mut rule.guard {
+            guard.rename_ref(old, &new, is_ctr);
+          }
+          rule.body.rename_ref(old, &new, is_ctr);
+        }
+      }
+    }
+    for def in self.hvm_defs.values_mut() {
+      for tree in net_trees_mut(&mut def.body) {
+        rename_tree_ref(tree, old, &new);
+      }
+    }
+    if self.entrypoint.as_ref() == Some(old) {
+      self.entrypoint = Some(new);
+    }
+    Ok(())
+  }
+}
+
+impl Term {
+  /// Renames the free occurrences of the variable `from` to `to`.
+  ///
+  /// If this term is the one that binds `from`, like `λfrom ...`, the binder is also renamed.
+  /// Inner binders with the same name shadow the variable, so they and
+  /// their occurrences are kept as they are.
+  ///
+  /// Unscoped variables aren't affected by scopes, so every `$from` and `λ$from` are renamed together.
+  ///
+  /// Unlike [`Term::subst`], this doesn't check if `to` gets captured by a binder of the term.
+  pub fn rename_var(&mut self, from: &Name, to: &Name) {
+    self.rename_links(from, to);
+    if self.children_with_binds().any(|(_, mut binds)| binds.any(|bind| bind.as_ref() == Some(from))) {
+      self.rename_binders(from, to);
+      for child in self.children_mut() {
+        child.rename_free_var(from, to);
+      }
+    } else {
+      self.rename_free_var(from, to);
+    }
+  }
+
+  /// Renames the variables bound by this term, but not its children.
+  fn rename_binders(&mut self, from: &Name, to: &Name) {
+    let binds: Vec<&mut Option<Name>> = match self {
+      Term::Lam { pat, .. } | Term::Let { pat, .. } | Term::Ask { pat, .. } => pat.binds_mut().collect(),
+      Term::Use { nam, .. } => vec![nam],
+      Term::Mat { bnd, with_bnd, arms, .. } | Term::Fold { bnd, with_bnd, arms, .. } => {
+        [bnd].into_iter().chain(with_bnd).chain(arms.iter_mut().flat_map(|arm| &mut arm.1)).collect()
+      }
+      Term::Swt { bnd, with_bnd, pred, .. } => [bnd, pred].into_iter().chain(with_bnd).collect(),
+      Term::Bend { bnd, .. } => bnd.iter_mut().collect(),
+      _ => vec![],
+    };
+    for bind in binds.into_iter().flatten() {
+      if bind == from {
+        *bind = to.clone();
+      }
+    }
+  }
+
+  fn rename_free_var(&mut self, from: &Name, to: &Name) {
+    maybe_grow(|| match self {
+      Term::Var { nam } if nam == from => *nam = to.clone(),
+      Term::Def { def, nxt } => {
+        for rule in def.rules.iter_mut() {
+          if !rule.pats.iter().flat_map(Pattern::binds).any(|bind| bind == from) {
+            rule.body.rename_free_var(from, to);
+          }
+        }
+        nxt.rename_free_var(from, to);
+      }
+      _ => {
+        for (child, mut binds) in self.children_mut_with_binds() {
+          if !binds.any(|bind| bind == from) {
+            child.rename_free_var(from, to);
+          }
+        }
+      }
+    })
+  }
+
+  fn rename_links(&mut self, from: &Name, to: &Name) {
+    maybe_grow(|| {
+      match self {
+        Term::Link { nam } if nam == from => *nam = to.clone(),
+        Term::Lam { pat, .. } | Term::Let { pat, .. } | Term::Ask { pat, .. } => pat.rename_chn(from, to),
+        Term::Def { def, .. } => {
+          for rule in def.rules.iter_mut() {
+            rule.body.rename_links(from, to);
+          }
+        }
+        _ => (),
+      }
+      for child in self.children_mut() {
+        child.rename_links(from, to);
+      }
+    })
+  }
+
+  fn rename_ref(&mut self, old: &Name, new: &Name, is_ctr: bool) {
+    maybe_grow(|| match self {
+      Term::Ref { nam } | Term::Var { nam } if nam == old => *nam = new.clone(),
+      Term::Def { def, nxt } => {
+        for rule in def.rules.iter_mut() {
+          for pat in rule.pats.iter_mut() {
+            pat.rename_ctr(old, new, is_ctr);
+          }
+          if !rule.pats.iter().flat_map(Pattern::binds).any(|bind| bind == old) {
+            rule.body.rename_ref(old, new, is_ctr);
+          }
+        }
+        if def.name != *old {
+          nxt.rename_ref(old, new, is_ctr);
+        }
+      }
+      _ => {
+        if let Term::Mat { arms, .. } | Term::Fold { arms, .. } = self {
+          for (ctr, _, _) in arms.iter_mut() {
+            if ctr.as_ref() == Some(old) {
+              *ctr = Some(new.clone());
+            }
+          }
+        }
+        for (child, mut binds) in self.children_mut_with_binds() {
+          // Variables bound with the same name shadow the definition.
+          if !binds.any(|bind| bind == old) {
+            child.rename_ref(old, new, is_ctr);
+          }
+        }
+      }
+    })
+  }
+}
+
+impl Pattern {
+  fn rename_chn(&mut self, from: &Name, to: &Name) {
+    maybe_grow(|| {
+      if let Pattern::Chn(nam) = self {
+        if nam == from {
+          *nam = to.clone();
+        }
+      }
+      for child in self.children_mut() {
+        child.rename_chn(from, to);
+      }
+    })
+  }
+
+  /// Constructors without fields can also be var patterns, if the book wasn't desugared yet.
+  fn rename_ctr(&mut self, old: &Name, new: &Name, is_ctr: bool) {
+    maybe_grow(|| {
+      match self {
+        Pattern::Ctr(ctr, _) if ctr == old => *ctr = new.clone(),
+        Pattern::Var(Some(nam)) if is_ctr && nam == old => *nam = new.clone(),
+        _ => (),
+      }
+      for child in self.children_mut() {
+        child.rename_ctr(old, new, is_ctr);
+      }
+    })
+  }
+}
+
+fn rename_tree_ref(tree: &mut Tree, old: &Name, new: &Name) {
+  maybe_grow(|| {
+    if let Tree::Ref { nam } = tree {
+      if nam == old.as_ref() {
+        *nam = new.to_string();
+      }
+    }
+    for child in tree_children_mut(tree) {
+      rename_tree_ref(child, old, new);
+    }
+  })
+}
+
+/// Changes the key of an entry without changing its position.
+fn rename_key<'a, V>(map: &'a mut IndexMap<Name, V>, old: &Name, new: Name) -> Option<&'a mut V> {
+  let (idx, _, val) = map.shift_remove_full(old)?;
+  map.shift_insert(idx, new, val);
+  map.get_index_mut(idx).map(|(_, val)| val)
+}
+
+#[test]
+fn rename_def() {
+  use crate::fun::load_book::do_parse_book_default;
+  let code = "
+type Shape = (Circle r) | Square
+area (Shape/Circle r) = (* r r)
+area Shape/Square = 1
+main = let area2 = area; (area2 (Shape/Circle 2))
+";
+  let mut book = do_parse_book_default(code, std::path::Path::new("rename")).unwrap();
+
+  book.rename_def(&Name::new("area"), Name::new("surface")).unwrap();
+  book.rename_def(&Name::new("Shape/Square"), Name::new("Shape/Rect")).unwrap();
+  assert!(!book.defs.contains_key(&Name::new("area")));
+  assert_eq!(
+    book.defs[&Name::new("surface")].to_string(),
+    "(surface (Shape/Circle r)) = (* r r)\n(surface Shape/Rect) = 1"
+  );
+  assert_eq!(
+    book.defs[&Name::new("main")].to_string(),
+    "(main) = let area2 = surface; (area2 (Shape/Circle 2))"
+  );
+  assert_eq!(book.ctrs[&Name::new("Shape/Rect")], Name::new("Shape"));
+  assert!(book.adts[&Name::new("Shape")].ctrs.contains_key(&Name::new("Shape/Rect")));
+
+  // Can't rename to an existing name
+  let err = book.rename_def(&Name::new("surface"), Name::new("main")).unwrap_err();
+  assert_eq!(err, "Can't rename 'surface' to 'main', since 'main' is already defined.");
+  // Or to the name of a datatype
+  let err = book.rename_def(&Name::new("surface"), Name::new("Shape")).unwrap_err();
+  assert_eq!(err, "Can't rename 'surface' to 'Shape', since 'Shape' is already defined.");
+  assert!(book.defs.contains_key(&Name::new("surface")));
+}
+
+#[test]
+fn rename_var() {
+  use crate::fun::load_book::parse_test_term;
+  let rename = |code: &str, from: &str, to: &str| {
+    let mut term = parse_test_term(code);
+    term.rename_var(&Name::new(from), &Name::new(to));
+    term.to_string()
+  };
+  // Free occurrences
+  assert_eq!(rename("(x λy (x y))", "x", "z"), "(z λy (z y))");
+  // The binder of the term and what it binds
+  assert_eq!(rename("λx (x λy (x y))", "x", "z"), "λz (z λy (z y))");
+  assert_eq!(rename("let (x, y) = (y, x); (x y)", "x", "z"), "let (z, y) = (y, z); (z y)");
+  // Shadowed inner binders are kept
+  assert_eq!(rename("λx (x λx (x 1))", "x", "z"), "λz (z λx (x 1))");
+  assert_eq!(rename("(x let x = 1; x)", "x", "z"), "(z let x = 1; x)");
+  assert_eq!(
+    rename("(x match x { Nat/Zero: x; Nat/Succ: x.pred })", "x", "z"),
+    "(z match x = z { Nat/Zero: x; Nat/Succ: x.pred; })"
+  );
+  // Unscoped variables are renamed in pairs
+  assert_eq!(rename("(λ$x 1 λy (y $x))", "x", "z"), "(λ$z 1 λy (y $z))");
+}
@@ -0,0 +1,218 @@
+//! Randomized round trip tests between the term printer and the parser.
+//!
+//! Generates random terms that are valid fun syntax, prints them,
+//! parses the result back and checks that it's alpha-equivalent to the original.
+//!
+//! Terms that are printed in a desugared form, like switches with their predecessor variable
+//! or lambdas with fan patterns, and the ones the parser can't read, like tags and natural numbers,
+//! are not generated.
+
+use crate::fun::{parser::TermParser, FanKind, Name, Num, Op, Pattern, Tag, Term, STRINGS};
+
+/// A small xorshift pseudo-random generator, so that failures are reproducible from the seed.
+struct Rng(u64);
+
+impl Rng {
+  fn new(seed: u64) -> Self {
+    Rng(seed.wrapping_mul(0x9E3779B97F4A7C15) | 1)
+  }
+
+  fn next(&mut self) -> u64 {
+    self.0 ^= self.0 << 13;
+    self.0 ^= self.0 >> 7;
+    self.0 ^= self.0 << 17;
+    self.0
+  }
+
+  fn below(&mut self, n: usize) -> usize {
+    (self.next() % n as u64) as usize
+  }
+
+  fn chance(&mut self, percent: usize) -> bool {
+    self.below(100) < percent
+  }
+}
+
+/// Operators that can be printed and parsed back.
+const OPS: &[Op] = &[
+  Op::ADD,
+  Op::SUB,
+  Op::MUL,
+  Op::DIV,
+  Op::REM,
+  Op::EQ,
+  Op::NEQ,
+  Op::LT,
+  Op::GT,
+  Op::LE,
+  Op::GE,
+  Op::AND,
+  Op::OR,
+  Op::XOR,
+  Op::SHL,
+  Op::SHR,
+  Op::POW,
+];
+
+#[derive(Default)]
+struct TermGen {
+  /// The variables in scope, with the innermost last.
+  scope: Vec<Name>,
+  /// The unscoped variables that were bound but not used yet.
+  unused_chns: Vec<Name>,
+  name_count: usize,
+}
+
+impl TermGen {
+  fn fresh(&mut self, prefix: &str) -> Name {
+    self.name_count += 1;
+    Name::new(format!("{prefix}{}", self.name_count))
+  }
+
+  fn term(&mut self, rng: &mut Rng, depth: usize) -> Term {
+    if depth == 0 || rng.chance(20) {
+      return self.leaf(rng);
+    }
+    match rng.below(11) {
+      0 | 1 => {
+        // Lambdas with fan patterns are printed as a lambda followed by a `let`.
+        let (pat, binds) = self.pattern(rng, 0);
+        let bod = self.with_binds(binds, |gen| gen.term(rng, depth - 1));
+        Term::lam(pat, bod)
+      }
+      2 | 3 => {
+        let mut fun = self.term(rng, depth - 1);
+        // `(* x)` and `(-1 x)` are parsed as numeric operations, so these heads are replaced.
+        if matches!(fun, Term::Era) || is_signed_num(&fun) {
+          fun = Term::Var { nam: Name::new("free0") };
+        }
+        let arg = self.term(rng, depth - 1);
+        Term::app(fun, arg)
+      }
+      4 => {
+        let val = self.term(rng, depth - 1);
+        let (pat, binds) = self.pattern(rng, 2);
+        let nxt = self.with_binds(binds, |gen| gen.term(rng, depth - 1));
+        Term::Let { pat: Box::new(pat), val: Box::new(val), nxt: Box::new(nxt) }
+      }
+      5 => {
+        let nam = self.fresh("u");
+        let val = self.term(rng, depth - 1);
+        let nxt = self.with_binds(vec![nam.clone()], |gen| gen.term(rng, depth - 1));
+        Term::Use { nam: Some(nam), val: Box::new(val), nxt: Box::new(nxt) }
+      }
+      6 | 7 => {
+        let opr = OPS[rng.below(OPS.len())];
+        let fst = self.term(rng, depth - 1);
+        let snd = self.term(rng, depth - 1);
+        Term::Oper { opr, fst: Box::new(fst), snd: Box::new(snd) }
+      }
+      8 => {
+        let fan = if rng.chance(50) { FanKind::Tup } else { FanKind::Dup };
+        // Named tags are not supported by the parser, so only the default ones are generated.
+        let tag = if fan == FanKind::Dup { Tag::Auto } else { Tag::Static };
+        let mut els: Vec<_> = (0..2 + rng.below(2)).map(|_| self.term(rng, depth - 1)).collect();
+        // Same for `(-1, x)`.
+        if fan == FanKind::Tup && is_signed_num(&els[0]) {
+          els[0] = Term::Var { nam: Name::new("free0") };
+        }
+        Term::Fan { fan, tag, els }
+      }
+      9 => {
+        let els = (0..rng.below(3)).map(|_| self.term(rng, depth - 1)).collect();
+        Term::List { els }
+      }
+      _ => {
+        // An unscoped lambda, whose variable is used somewhere later in the term.
+        let nam = self.fresh("c");
+        let bod = self.term(rng, depth - 1);
+        self.unused_chns.push(nam.clone());
+        let lam = Term::lam(Pattern::Chn(nam), bod);
+        Term::app(lam, self.term(rng, depth - 1))
+      }
+    }
+  }
+
+  fn leaf(&mut self, rng: &mut Rng) -> Term {
+    if let Some(nam) = self.unused_chns.pop() {
+      return Term::Link { nam };
+    }
+    // Natural number literals are not generated, since `#` also starts a comment.
+    match rng.below(7) {
+      0 | 1 if !self.scope.is_empty() => Term::Var { nam: self.scope[rng.below(self.scope.len())].clone() },
+      0 | 1 => Term::Var { nam: Name::new(format!("free{}", rng.below(3))) },
+      2 => Term::Num { val: Num::U24(rng.below(0x1000) as u32) },
+      3 => Term::Num { val: Num::I24(rng.below(0x1000) as i32 - 0x800) },
+      // Multiples of 1/4 are printed exactly with 3 decimal places.
+      4 => Term::Num { val: Num::F24((rng.below(400) as f32 - 200.0) / 4.0) },
+      5 => Term::Era,
+      _ => {
+        let chars = ['a', 'b', 'c', ' ', '\n', '"', '\\', 'λ'];
+        let str = (0..rng.below(4)).map(|_| chars[rng.below(chars.len())]).collect::<String>();
+        Term::Str { val: STRINGS.get(str) }
+      }
+    }
+  }
+
+  /// A pattern for a lambda or a let, and the names it binds.
+  fn pattern(&mut self, rng: &mut Rng, depth: usize) -> (Pattern, Vec<Name>) {
+    match rng.below(6) {
+      0 if depth > 0 => {
+        let (fan, tag) = if rng.chance(50) { (FanKind::Tup, Tag::Static) } else { (FanKind::Dup, Tag::Auto) };
+        let mut binds = vec![];
+        let mut els = vec![];
+        for _ in 0..2 {
+          let (el, el_binds) = self.pattern(rng, depth - 1);
+          els.push(el);
+          binds.extend(el_binds);
+        }
+        (Pattern::Fan(fan, tag, els), binds)
+      }
+      1 => (Pattern::Var(None), vec![]),
+      _ => {
+        let nam = self.fresh("x");
+        (Pattern::Var(Some(nam.clone())), vec![nam])
+      }
+    }
+  }
+
+  fn with_binds<T>(&mut self, binds: Vec<Name>, f: impl FnOnce(&mut Self) -> T) -> T {
+    let len = self.scope.len();
+    self.scope.extend(binds);
+    let res = f(self);
+    self.scope.truncate(len);
+    res
+  }
+
+  /// A random closed term, where every unscoped lambda has its variable used exactly once.
+  fn gen(rng: &mut Rng, depth: usize) -> Term {
+    let mut gen = TermGen::default();
+    let mut term = gen.term(rng, depth);
+    while let Some(nam) = gen.unused_chns.pop() {
+      term = Term::app(term, Term::Link { nam });
+    }
+    term
+  }
+}
+
+fn is_signed_num(term: &Term) -> bool {
+  match term {
+    Term::Num { val: Num::I24(_) } => true,
+    Term::Num { val: Num::F24(val) } => *val < 0.0,
+    _ => false,
+  }
+}
+
+#[test]
+fn print_parse_round_trip() {
+  for seed in 0..2000 {
+    let mut rng = Rng::new(seed);
+    let term = TermGen::gen(&mut rng, 6);
+    let printed = term.to_string();
+    let parsed = match TermParser::new(&printed).parse_term() {
+      Ok(parsed) => parsed,
+      Err(err) => panic!("Seed {seed}: couldn't parse back '{printed}':\n{err}"),
+    };
+    assert!(term.alpha_eq(&parsed), "Seed {seed}: '{printed}' was parsed back as '{parsed}'");
+  }
+}
@@ -1,44 +1,75 @@
 use crate::{
   diagnostics::Diagnostics,
-  fun::{num_to_name, Book, FanKind, Name, Op, Pattern, Term},
+  fun::{num_to_name, Book, Definition, FanKind, Name, Op, Pattern, Rule, Term},
   hvm::{net_trees, tree_children},
   maybe_grow,
-  net::CtrKind::{self, *},
+  net::{
+    CtrKind::{self, *},
+    SlotId,
+  },
 };
 use hvm::ast::{Net, Tree};
 use loaned::LoanedMut;
 use std::{
-  collections::{hash_map::Entry, HashMap},
+  collections::{hash_map::Entry, HashMap, HashSet},
+  hash::{DefaultHasher, Hash, Hasher},
   ops::{Index, IndexMut},
 };
 
 #[derive(Debug, Clone)]
 pub struct ViciousCycleErr;
 
-pub fn book_to_hvm(book: &Book, diags: &mut Diagnostics) -> Result<(hvm::ast::Book, Labels), Diagnostics> {
+/// The [`NameHints`] of the nets of a book, by the name of their definition in the HVM book.
+pub type BookNameHints = HashMap<String, NameHints>;
+
+/// Compiles the definitions of the book to HVM nets.
+///
+/// Also returns the original names of the variables of each net, which can be used to read back the nets
+/// with [`crate::net::hvm_to_net::hvm_to_net_with_hints`] as long as they're not changed.
+pub fn book_to_hvm(
+  book: &Book,
+  diags: &mut Diagnostics,
+) -> Result<(hvm::ast::Book, Labels, BookNameHints), Diagnostics> {
   diags.start_pass();
 
+  let mut name_hints = BookNameHints::new();
+  let (hvm_book, labels) = compile_defs(book, diags, |def, name, labels| {
+    let rule = def.check_no_pattern_matching_rules()?;
+    let (net, hints) = term_to_hvm_with_hints(&rule.body, labels)?;
+    name_hints.insert(name.to_string(), hints);
+    Ok(net)
+  });
+
+  diags.fatal((hvm_book, labels, name_hints))
+}
+
+/// Builds the HVM book of a desugared book, getting the net of each definition from `compile_def`
+/// and copying the native hvm definitions.
+///
+/// `compile_def` receives each definition with its name in the HVM book, which is different for the entrypoint.
+/// Its errors are added to the diagnostics.
+fn compile_defs(
+  book: &Book,
+  diags: &mut Diagnostics,
+  mut compile_def: impl FnMut(&Definition, &str, &mut Labels) -> Result<Net, String>,
+) -> (hvm::ast::Book, Labels) {
   let mut hvm_book = hvm::ast::Book { defs: Default::default() };
   let mut labels = Labels::default();
 
   let main = book.entrypoint.as_ref();
 
   for def in book.defs.values() {
-    for rule in def.rules.iter() {
-      let net = term_to_hvm(&rule.body, &mut labels);
-
-      let name = if main.is_some_and(|m| &def.name == m) {
-        book.hvm_entrypoint().to_string()
-      } else {
-        def.name.0.to_string()
-      };
+    let name = if main.is_some_and(|m| &def.name == m) {
+      book.hvm_entrypoint().to_string()
+    } else {
+      def.name.0.to_string()
+    };
 
-      match net {
-        Ok(net) => {
-          hvm_book.defs.insert(name, net);
-        }
-        Err(err) => diags.add_inet_error(err, name),
+    match compile_def(def, &name, &mut labels) {
+      Ok(net) => {
+        hvm_book.defs.insert(name, net);
       }
+      Err(err) => diags.add_inet_error(err, name),
     }
   }
 
@@ -50,11 +81,118 @@ pub fn book_to_hvm(book: &Book, diags: &mut Diagnostics) -> Result<(hvm::ast::Bo
   labels.con.finish();
   labels.dup.finish();
 
+  (hvm_book, labels)
+}
+
+impl Book {
+  /// Compiles a single definition to an HVM net, without compiling the definitions it references.
+  ///
+  /// Like [`book_to_hvm`], expects the book to be already desugared,
+  /// so definitions with pattern matching rules give an error.
+  pub fn compile_def(&self, name: &Name) -> Result<Net, String> {
+    let Some(def) = self.defs.get(name) else {
+      return Err(format!("Definition '{name}' not found."));
+    };
+    let rule = def.check_no_pattern_matching_rules()?;
+    term_to_hvm(&rule.body, &mut Labels::default())
+  }
+}
+
+/// The nets of a previous compilation, reused by [`book_to_hvm_incremental`].
+#[derive(Debug, Default, Clone)]
+pub struct NetCache {
+  /// The net of each definition, with the hash of the rules it was compiled from
+  /// and the labels generated while compiling it.
+  nets: HashMap<Name, (u64, Net, Labels)>,
+  /// The definitions that were compiled in the last compilation.
+  recompiled: Vec<Name>,
+}
+
+impl NetCache {
+  pub fn recompiled(&self) -> &[Name] {
+    &self.recompiled
+  }
+}
+
+/// Like [`book_to_hvm`], but only compiles the definitions that changed since
+/// the nets in the cache were compiled, reusing the cached nets of the others.
+///
+/// A definition is recompiled if its rules are different from the ones in the cache,
+/// if it's in `changed`, or if it references, directly or not, another recompiled definition.
+pub fn book_to_hvm_incremental(
+  book: &Book,
+  cache: &mut NetCache,
+  changed: &HashSet<Name>,
+  diags: &mut Diagnostics,
+) -> Result<(hvm::ast::Book, Labels), Diagnostics> {
+  diags.start_pass();
+
+  let hashes = book.defs.iter().map(|(name, def)| (name, hash_rules(&def.rules))).collect::<HashMap<_, _>>();
+  cache.nets.retain(|name, _| book.defs.contains_key(name));
+
+  // Invalidate the changed definitions and everything that depends on them.
+  let mut callers = HashMap::<Name, Vec<Name>>::new();
+  for (caller, refs) in book.call_graph() {
+    for nam in refs {
+      callers.entry(nam).or_default().push(caller.clone());
+    }
+  }
+  let mut to_visit = changed.iter().cloned().collect::<Vec<_>>();
+  for (name, hash) in &hashes {
+    if cache.nets.get(*name).map_or(true, |(cached, ..)| cached != hash) {
+      to_visit.push((*name).clone());
+    }
+  }
+  let mut invalid = HashSet::new();
+  while let Some(name) = to_visit.pop() {
+    if invalid.insert(name.clone()) {
+      to_visit.extend(callers.get(&name).into_iter().flatten().cloned());
+    }
+  }
+
+  cache.recompiled.clear();
+  let (hvm_book, labels) = compile_defs(book, diags, |def, _, labels| {
+    if !invalid.contains(&def.name) {
+      let (_, net, def_labels) = &cache.nets[&def.name];
+      labels.merge(def_labels);
+      return Ok(net.clone());
+    }
+
+    cache.recompiled.push(def.name.clone());
+    cache.nets.remove(&def.name);
+    let rule = def.check_no_pattern_matching_rules()?;
+    // Each definition gets its own labels, so that they can be merged again when its net is reused.
+    let mut def_labels = Labels::default();
+    let net = term_to_hvm(&rule.body, &mut def_labels)?;
+    labels.merge(&def_labels);
+    cache.nets.insert(def.name.clone(), (hashes[&def.name], net.clone(), def_labels));
+    Ok(net)
+  });
+
   diags.fatal((hvm_book, labels))
 }
 
+fn hash_rules(rules: &[Rule]) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  rules.hash(&mut hasher);
+  hasher.finish()
+}
+
 /// Converts an LC term into an IC net.
 pub fn term_to_hvm(term: &Term, labels: &mut Labels) -> Result<Net, String> {
+  term_to_hvm_with_hints(term, labels).map(|(net, _)| net)
+}
+
+/// The original names of the variables of a net, by the place in the net where they're connected.
+pub type NameHints = HashMap<TreePath, Name>;
+
+/// A place in a net, given by the position of a tree in [`net_trees`]
+/// and the aux slots (1 or 2) to follow from the root of that tree.
+pub type TreePath = (usize, Vec<SlotId>);
+
+/// Like [`term_to_hvm`], but also returns the names that the variables of the term had,
+/// so that reading back the net without running it can use them instead of generated ones.
+pub fn term_to_hvm_with_hints(term: &Term, labels: &mut Labels) -> Result<(Net, NameHints), String> {
   let mut net = Net { root: Tree::Era, rbag: Default::default() };
 
   let mut state = EncodeTermState {
@@ -65,19 +203,45 @@ pub fn term_to_hvm(term: &Term, labels: &mut Labels) -> Result<Net, String> {
     name_idx: 0,
     created_nodes: 0,
     labels,
+    linking: None,
+    node_parents: Default::default(),
+    hole_hints: Default::default(),
+    wire_hints: Default::default(),
   };
 
-  state.encode_term(term, Place::Hole(&mut net.root));
+  state.encode_term(term, Place::Hole(&mut net.root, HolePos::Root));
   LoanedMut::from(std::mem::take(&mut state.redexes)).place(&mut net.rbag);
 
-  let EncodeTermState { created_nodes, .. } = { state };
+  let EncodeTermState { created_nodes, node_parents, hole_hints, .. } = { state };
 
   let found_nodes = net_trees(&net).map(count_nodes).sum::<usize>();
   if created_nodes != found_nodes {
     return Err("Found term that compiles into an inet with a vicious cycle".into());
   }
 
-  Ok(net)
+  let mut hints = NameHints::new();
+  for (pos, nam) in hole_hints {
+    if let Some(path) = hole_path(&node_parents, &pos) {
+      hints.insert(path, nam);
+    }
+  }
+
+  Ok((net, hints))
+}
+
+/// The path in the finished net of a hole, following the parents of the node it belongs to.
+fn hole_path(node_parents: &HashMap<usize, NodeParent>, pos: &HolePos) -> Option<TreePath> {
+  maybe_grow(|| match pos {
+    HolePos::Root => Some((0, vec![])),
+    HolePos::Node(node, slots) => {
+      let mut path = match node_parents.get(node)? {
+        NodeParent::Redex(tree_idx) => (*tree_idx, vec![]),
+        NodeParent::Hole(pos) => hole_path(node_parents, pos)?,
+      };
+      path.1.extend(slots);
+      Some(path)
+    }
+  })
 }
 
 #[derive(Debug)]
@@ -89,6 +253,15 @@ struct EncodeTermState<'t, 'l> {
   name_idx: u64,
   created_nodes: usize,
   labels: &'l mut Labels,
+  /// The variable whose occurrences are being linked, if any.
+  linking: Option<Name>,
+  /// Where each created node was placed, by its id.
+  node_parents: HashMap<usize, NodeParent>,
+  /// The variables that were linked to each hole.
+  /// The paths of the holes in the finished net are found with the parents of their nodes.
+  hole_hints: Vec<(HolePos, Name)>,
+  /// The variables linked to the wires that only have one end connected.
+  wire_hints: HashMap<usize, Name>,
 }
 
 fn count_nodes(tree: &Tree) -> usize {
@@ -99,11 +272,35 @@ fn count_nodes(tree: &Tree) -> usize {
 
 #[derive(Debug)]
 enum Place<'t> {
-  Tree(LoanedMut<'t, Tree>),
-  Hole(&'t mut Tree),
+  /// A tree, with the id of its root node if it's a node created by the encoding.
+  Tree(LoanedMut<'t, Tree>, Option<usize>),
+  Hole(&'t mut Tree, HolePos),
   Wire(usize),
 }
 
+/// Where a hole is in the net.
+#[derive(Debug, Clone)]
+enum HolePos {
+  /// The root of the net.
+  Root,
+  /// Inside the node with this id, following these aux slots from it.
+  Node(usize, Vec<SlotId>),
+}
+
+/// Where a node was placed in the net.
+#[derive(Debug)]
+enum NodeParent {
+  /// At the root of a redex tree, given by its position in [`net_trees`].
+  Redex(usize),
+  Hole(HolePos),
+}
+
+impl<'t> Place<'t> {
+  fn leaf(tree: Tree) -> Self {
+    Place::Tree(LoanedMut::new(tree), None)
+  }
+}
+
 impl<'t, 'l> EncodeTermState<'t, 'l> {
   /// Adds a subterm connected to `up` to the `inet`.
   /// `scope` has the current variable scope.
@@ -113,13 +310,13 @@ impl<'t, 'l> EncodeTermState<'t, 'l> {
   fn encode_term(&mut self, term: &'t Term, up: Place<'t>) {
     maybe_grow(|| {
       match term {
-        Term::Era => self.link(up, Place::Tree(LoanedMut::new(Tree::Era))),
+        Term::Era => self.link(up, Place::leaf(Tree::Era)),
         Term::Var { nam } => self.link_var(false, nam, up),
         Term::Link { nam } => self.link_var(true, nam, up),
-        Term::Ref { nam } => self.link(up, Place::Tree(LoanedMut::new(Tree::Ref { nam: nam.to_string() }))),
+        Term::Ref { nam } => self.link(up, Place::leaf(Tree::Ref { nam: nam.to_string() })),
         Term::Num { val } => {
           let val = hvm::ast::Numb(val.to_bits());
-          self.link(up, Place::Tree(LoanedMut::new(Tree::Num { val })))
+          self.link(up, Place::leaf(Tree::Num { val }))
         }
         // A lambda becomes to a con node. Ports:
         // - 0: points to where the lambda occurs.
@@ -154,6 +351,7 @@ impl<'t, 'l> EncodeTermState<'t, 'l> {
           assert!(pred.is_none());
           assert!(arms.len() == 2);
 
+          let id = self.created_nodes;
           self.created_nodes += 2;
           let loaned = Tree::Swi { fst: Box::new(Tree::Con{fst: Box::new(Tree::Era), snd: Box::new(Tree::Era)}), snd: Box::new(Tree::Era)};
           let ((zero, succ, out), node) =
@@ -163,10 +361,10 @@ impl<'t, 'l> EncodeTermState<'t, 'l> {
               (l.loan_mut(zero), l.loan_mut(succ), l.loan_mut(out))
             });
 
-          self.encode_term(arg, Place::Tree(node));
-          self.encode_term(&arms[0], Place::Hole(zero));
-          self.encode_term(&arms[1], Place::Hole(succ));
-          self.link(up, Place::Hole(out));
+          self.encode_term(arg, Place::Tree(node, Some(id)));
+          self.encode_term(&arms[0], Place::Hole(zero, HolePos::Node(id, vec![1, 1])));
+          self.encode_term(&arms[1], Place::Hole(succ, HolePos::Node(id, vec![1, 2])));
+          self.link(up, Place::Hole(out, HolePos::Node(id, vec![2])));
         }
         Term::Let { pat, val, nxt } => {
           // Dups/tup eliminators are not actually scoped like other terms.
@@ -185,7 +383,7 @@ impl<'t, 'l> EncodeTermState<'t, 'l> {
             (Term::Num { val }, snd) => {
               let val = val.to_bits();
               let val = hvm::ast::Numb((val & !0x1F) | opr.to_native_tag() as u32);
-              let fst = Place::Tree(LoanedMut::new(Tree::Num { val }));
+              let fst = Place::leaf(Tree::Num { val });
               let node = self.new_opr();
               self.link(fst, node.0);
               self.encode_term(snd, node.1);
@@ -196,7 +394,7 @@ impl<'t, 'l> EncodeTermState<'t, 'l> {
               if [Op::POW, Op::ATN, Op::LOG].contains(opr) {
                 // POW, ATN and LOG share tags with AND, OR and XOR, so don't flip or results will be wrong
                 let opr_val = hvm::ast::Numb(hvm::hvm::Numb::new_sym(opr.to_native_tag()).0);
-                let oper = Place::Tree(LoanedMut::new(Tree::Num { val: opr_val }));
+                let oper = Place::leaf(Tree::Num { val: opr_val });
                 let node1 = self.new_opr();
                 self.encode_term(fst, node1.0);
                 self.link(oper, node1.1);
@@ -208,7 +406,7 @@ impl<'t, 'l> EncodeTermState<'t, 'l> {
                 // flip
                 let val = val.to_bits();
                 let val = hvm::ast::Numb((val & !0x1F) | flip_sym(opr.to_native_tag()) as u32);
-                let snd = Place::Tree(LoanedMut::new(Tree::Num { val }));
+                let snd = Place::leaf(Tree::Num { val });
                 let node = self.new_opr();
                 self.encode_term(fst, node.0);
                 self.link(snd, node.1);
@@ -218,7 +416,7 @@ impl<'t, 'l> EncodeTermState<'t, 'l> {
             // Don't partially apply
             (fst, snd) => {
               let opr_val = hvm::ast::Numb(hvm::hvm::Numb::new_sym(opr.to_native_tag()).0);
-              let oper = Place::Tree(LoanedMut::new(Tree::Num { val: opr_val }));
+              let oper = Place::leaf(Tree::Num { val: opr_val });
               let node1 = self.new_opr();
               self.encode_term(fst, node1.0);
               self.link(oper, node1.1);
@@ -254,8 +452,7 @@ impl<'t, 'l> EncodeTermState<'t, 'l> {
     match opr {
       Op::LE | Op::GE => {
         let node_eq = self.new_opr();
-        let eq_val =
-          Place::Tree(LoanedMut::new(Tree::Num { val: hvm::ast::Numb(Op::EQ.to_native_tag() as u32) }));
+        let eq_val = Place::leaf(Tree::Num { val: hvm::ast::Numb(Op::EQ.to_native_tag() as u32) });
         self.link(eq_val, node_eq.0);
         self.link(node_eq.1, node);
         self.link(up, node_eq.2);
@@ -266,7 +463,7 @@ impl<'t, 'l> EncodeTermState<'t, 'l> {
 
   fn encode_pat(&mut self, pat: &Pattern, up: Place<'t>) {
     maybe_grow(|| match pat {
-      Pattern::Var(None) => self.link(up, Place::Tree(LoanedMut::new(Tree::Era))),
+      Pattern::Var(None) => self.link(up, Place::leaf(Tree::Era)),
       Pattern::Var(Some(name)) => self.link_var(false, name, up),
       Pattern::Chn(name) => self.link_var(true, name, up),
       Pattern::Fan(fan, tag, els) => {
@@ -279,32 +476,62 @@ impl<'t, 'l> EncodeTermState<'t, 'l> {
 
   fn link(&mut self, a: Place<'t>, b: Place<'t>) {
     match (a, b) {
-      (Place::Tree(a), Place::Tree(b)) => {
+      (Place::Tree(a, a_id), Place::Tree(b, b_id)) => {
+        // The trees of the redex come after the root in `net_trees`.
+        let idx = 1 + 2 * self.redexes.len();
+        if let Some(b_id) = b_id {
+          self.node_parents.insert(b_id, NodeParent::Redex(idx));
+        }
+        if let Some(a_id) = a_id {
+          self.node_parents.insert(a_id, NodeParent::Redex(idx + 1));
+        }
         self.redexes.push(LoanedMut::merge((false, Tree::Era, Tree::Era), |r, m| {
           m.place(b, &mut r.1);
           m.place(a, &mut r.2);
         }))
       }
-      (Place::Tree(t), Place::Hole(h)) | (Place::Hole(h), Place::Tree(t)) => {
+      (Place::Tree(t, t_id), Place::Hole(h, pos)) | (Place::Hole(h, pos), Place::Tree(t, t_id)) => {
+        if let Some(nam) = &self.linking {
+          self.hole_hints.push((pos.clone(), nam.clone()));
+        }
+        if let Some(t_id) = t_id {
+          self.node_parents.insert(t_id, NodeParent::Hole(pos));
+        }
         t.place(h);
       }
-      (Place::Hole(a), Place::Hole(b)) => {
+      (Place::Hole(a, a_pos), Place::Hole(b, b_pos)) => {
+        if let Some(nam) = &self.linking {
+          self.hole_hints.push((a_pos, nam.clone()));
+          self.hole_hints.push((b_pos, nam.clone()));
+        }
         let var = Tree::Var { nam: num_to_name(self.name_idx) };
         self.name_idx += 1;
         *a = var.clone();
         *b = var;
       }
       (Place::Wire(v), p) | (p, Place::Wire(v)) => {
-        let v = &mut self.wires[v];
-        match v.take() {
-          Some(q) => self.link(p, q),
-          None => *v = Some(p),
+        let wire = &mut self.wires[v];
+        match wire.take() {
+          Some(q) => {
+            // If a variable was linked to the other end of the wire, it is also linked to this one.
+            let linking = self.wire_hints.remove(&v).or_else(|| self.linking.clone());
+            let prev = std::mem::replace(&mut self.linking, linking);
+            self.link(p, q);
+            self.linking = prev;
+          }
+          None => {
+            *wire = Some(p);
+            if let Some(nam) = &self.linking {
+              self.wire_hints.insert(v, nam.clone());
+            }
+          }
         }
       }
     }
   }
 
   fn new_ctr(&mut self, kind: CtrKind) -> (Place<'t>, Place<'t>, Place<'t>) {
+    let id = self.created_nodes;
     self.created_nodes += 1;
     let node = match kind {
       CtrKind::Con(None) => Tree::Con { fst: Box::new(Tree::Era), snd: Box::new(Tree::Era) },
@@ -317,17 +544,26 @@ impl<'t, 'l> EncodeTermState<'t, 'l> {
       Tree::Dup { fst, snd } => (l.loan_mut(fst), l.loan_mut(snd)),
       _ => unreachable!(),
     });
-    (Place::Tree(node), Place::Hole(a), Place::Hole(b))
+    (
+      Place::Tree(node, Some(id)),
+      Place::Hole(a, HolePos::Node(id, vec![1])),
+      Place::Hole(b, HolePos::Node(id, vec![2])),
+    )
   }
 
   fn new_opr(&mut self) -> (Place<'t>, Place<'t>, Place<'t>) {
+    let id = self.created_nodes;
     self.created_nodes += 1;
     let ((fst, snd), node) =
       LoanedMut::loan_with(Tree::Opr { fst: Box::new(Tree::Era), snd: Box::new(Tree::Era) }, |t, l| {
         let Tree::Opr { fst, snd } = t else { unreachable!() };
         (l.loan_mut(fst), l.loan_mut(snd))
       });
-    (Place::Tree(node), Place::Hole(fst), Place::Hole(snd))
+    (
+      Place::Tree(node, Some(id)),
+      Place::Hole(fst, HolePos::Node(id, vec![1])),
+      Place::Hole(snd, HolePos::Node(id, vec![2])),
+    )
   }
 
   /// Adds a list-like tree of nodes of the same kind to the inet.
@@ -366,7 +602,9 @@ impl<'t, 'l> EncodeTermState<'t, 'l> {
     match self.vars.entry((global, name.clone())) {
       Entry::Occupied(e) => {
         let other = e.remove();
+        self.linking = Some(name.clone());
         self.link(place, other);
+        self.linking = None;
       }
       Entry::Vacant(e) => {
         e.insert(place);
@@ -375,14 +613,14 @@ impl<'t, 'l> EncodeTermState<'t, 'l> {
   }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Labels {
   pub con: LabelGenerator,
   pub dup: LabelGenerator,
   pub tup: LabelGenerator,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct LabelGenerator {
   pub next: u16,
   pub name_to_label: HashMap<Name, u16>,
@@ -409,7 +647,22 @@ impl IndexMut<FanKind> for Labels {
   }
 }
 
+impl Labels {
+  /// Adds the labels generated with `other`, as if its terms had been compiled with these labels.
+  fn merge(&mut self, other: &Labels) {
+    self.con.merge(&other.con);
+    self.dup.merge(&other.dup);
+    self.tup.merge(&other.tup);
+  }
+}
+
 impl LabelGenerator {
+  fn merge(&mut self, other: &LabelGenerator) {
+    self.next = self.next.max(other.next);
+    self.name_to_label.extend(other.name_to_label.iter().map(|(nam, lab)| (nam.clone(), *lab)));
+    self.label_to_name.extend(other.label_to_name.iter().map(|(lab, nam)| (*lab, nam.clone())));
+  }
+
   // If some tag and new generate a new label, otherwise return the generated label.
   // If none use the implicit label counter.
   fn generate(&mut self, tag: &crate::fun::Tag) -> Option<u16> {
@@ -456,7 +709,7 @@ impl LabelGenerator {
 }
 
 impl Op {
-  fn to_native_tag(self) -> hvm::hvm::Tag {
+  pub fn to_native_tag(self) -> hvm::hvm::Tag {
     match self {
       Op::ADD => hvm::hvm::OP_ADD,
       Op::SUB => hvm::hvm::OP_SUB,
@@ -500,3 +753,123 @@ fn flip_sym(tag: hvm::hvm::Tag) -> hvm::hvm::Tag {
     _ => tag,
   }
 }
+
+#[test]
+fn incremental_compilation() {
+  use crate::{
+    diagnostics::DiagnosticsConfig,
+    fun::load_book::{parse_test_book, parse_test_term},
+  };
+  let code = "
+leaf = λx x
+mid = λx (leaf x)
+top = (mid 1)
+other = λx λy x
+";
+  let mut book = parse_test_book(code);
+
+  let mut cache = NetCache::default();
+  let mut compile = |book: &Book, changed: &[&str]| {
+    let changed = changed.iter().map(|nam| Name::new(*nam)).collect();
+    let mut diags = Diagnostics::new(DiagnosticsConfig::default());
+    let (hvm_book, _) = book_to_hvm_incremental(book, &mut cache, &changed, &mut diags).unwrap();
+    assert_eq!(hvm_book.defs.len(), 4);
+    cache.recompiled().iter().map(|nam| nam.to_string()).collect::<Vec<_>>()
+  };
+
+  assert_eq!(compile(&book, &[]), ["leaf", "mid", "top", "other"]);
+  assert!(compile(&book, &[]).is_empty());
+
+  // Changing a definition recompiles it and the ones that refer to it.
+  let leaf = &mut book.defs.get_mut(&Name::new("leaf")).unwrap().rules[0].body;
+  *leaf = parse_test_term("λx λy y");
+  assert_eq!(compile(&book, &[]), ["leaf", "mid", "top"]);
+  assert!(compile(&book, &[]).is_empty());
+
+  // Definitions can also be invalidated explicitly.
+  assert_eq!(compile(&book, &["mid"]), ["mid", "top"]);
+  assert_eq!(compile(&book, &["other"]), ["other"]);
+}
+
+#[test]
+fn incremental_compilation_labels() {
+  use crate::{diagnostics::DiagnosticsConfig, fun::load_book::parse_test_book};
+  // The duplication gets a dup label.
+  let book = parse_test_book("copy = λx let {a b} = x; (a b)\nmain = (copy 1)");
+
+  let mut diags = Diagnostics::new(DiagnosticsConfig::default());
+  let (full_book, full_labels, _) = book_to_hvm(&book, &mut diags).unwrap();
+  let mut cache = NetCache::default();
+  // The labeled definition is compiled the first time and taken from the cache the second one.
+  for changed in [HashSet::new(), HashSet::from([Name::new("main")])] {
+    let (hvm_book, labels) = book_to_hvm_incremental(&book, &mut cache, &changed, &mut diags).unwrap();
+    assert_eq!(hvm_book.show(), full_book.show());
+    assert_eq!(labels, full_labels);
+  }
+  assert_eq!(cache.recompiled(), [Name::new("main")]);
+}
+
+#[test]
+fn compile_single_def() {
+  use crate::fun::load_book::parse_test_book;
+  let code = "
+id = λx x
+apply = λf λx (f (id x))
+(is_zero 0) = 1
+(is_zero _) = 0
+";
+  let book = parse_test_book(code);
+
+  assert_eq!(book.compile_def(&Name::new("id")).unwrap().show(), "(a a)");
+  // References are kept as references to the other definitions.
+  assert_eq!(book.compile_def(&Name::new("apply")).unwrap().show(), "((b c) (a c)) & @id ~ (a b)");
+  assert!(book.compile_def(&Name::new("is_zero")).unwrap_err().contains("has pattern matching rules"));
+  assert!(book.compile_def(&Name::new("missing")).is_err());
+}
+
+#[test]
+fn compile_pattern_matching_def_errors() {
+  use crate::{diagnostics::DiagnosticsConfig, fun::load_book::parse_test_book};
+  let code = "
+(is_zero 0) = 1
+(is_zero _) = 0
+main = (is_zero 1)
+";
+  let book = parse_test_book(code);
+
+  // The rules were not desugared, so compiling gives an error instead of panicking.
+  let mut diags = Diagnostics::new(DiagnosticsConfig::default());
+  let Err(err) = book_to_hvm(&book, &mut diags) else { panic!("Should not compile") };
+  let err = err.to_string();
+  assert!(err.contains("Definition 'is_zero' has pattern matching rules"));
+  assert!(!err.contains("Definition 'main'"));
+}
+
+#[test]
+fn book_to_hvm_name_hints() {
+  use crate::{
+    diagnostics::DiagnosticsConfig,
+    fun::{load_book::parse_test_book, net_to_term::net_to_term_with_hints},
+    net::hvm_to_net::hvm_to_net_with_hints,
+  };
+  let code = "
+id = λthing thing
+apply = λfunc λval (func (id val))
+twice = λfunc λval let {fst snd} = func; (fst (snd val))
+main = λarg (apply λinner (inner arg) id)
+";
+  let book = parse_test_book(code);
+
+  let mut diags = Diagnostics::new(DiagnosticsConfig::default());
+  let (hvm_book, labels, name_hints) = book_to_hvm(&book, &mut diags).unwrap();
+  let readback = |name: &str| {
+    let (net, hints) = hvm_to_net_with_hints(&hvm_book.defs[name], &name_hints[name]);
+    let term = net_to_term_with_hints(&net, &book, &labels, true, hints, &mut Diagnostics::default());
+    term.to_string()
+  };
+  assert_eq!(readback("id"), "λthing thing");
+  assert_eq!(readback("apply"), "λfunc λval (func (id val))");
+  assert_eq!(readback("twice"), "λfunc λval let {fst snd} = func; (fst (snd val))");
+  // The variables inside the redexes also keep their names.
+  assert_eq!(readback("main"), "λarg (apply λinner (inner arg) id)");
+}
@@ -0,0 +1,93 @@
+use crate::{
+  fun::{Book, FanKind, Pattern, Term},
+  maybe_grow,
+};
+
+impl Book {
+  /// Simplifies the duplications of a superposition with the same label,
+  /// binding each variable of the duplication to the matching element of the superposition,
+  /// like HVM would do when they annihilate.
+  ///
+  /// Example:
+  /// ```bend
+  /// let {a b} = {x y}; (a b)
+  ///
+  /// // Becomes
+  /// let a = x; let b = y; (a b)
+  /// ```
+  ///
+  /// A duplication and a superposition with different labels commute instead, so they're left unchanged.
+  ///
+  /// Must run after the variable names are made unique, so that the new `let`s don't capture any variables.
+  pub fn annihilate_dups(&mut self) {
+    for def in self.defs.values_mut() {
+      for rule in def.rules.iter_mut() {
+        rule.body.annihilate_dups();
+      }
+    }
+  }
+}
+
+impl Term {
+  pub fn annihilate_dups(&mut self) {
+    maybe_grow(|| {
+      for child in self.children_mut() {
+        child.annihilate_dups();
+      }
+      let Term::Let { pat, val, nxt } = self else { return };
+      let (Pattern::Fan(FanKind::Dup, pat_tag, binds), Term::Fan { fan: FanKind::Dup, tag, els }) =
+        (pat.as_ref(), val.as_mut())
+      else {
+        return;
+      };
+      if pat_tag != tag
+        || binds.len() != els.len()
+        || !binds.iter().all(|bind| matches!(bind, Pattern::Var(_)))
+      {
+        return;
+      }
+      let lets = binds.iter().cloned().zip(std::mem::take(els)).rev();
+      *self = lets.fold(std::mem::take(nxt.as_mut()), |nxt, (bind, el)| Term::Let {
+        pat: Box::new(bind),
+        val: Box::new(el),
+        nxt: Box::new(nxt),
+      });
+    })
+  }
+}
+
+#[test]
+fn annihilate_dups() {
+  use crate::fun::{load_book::parse_test_term, Tag};
+  let annihilated = |mut term: Term| {
+    term.annihilate_dups();
+    term.to_string()
+  };
+
+  assert_eq!(annihilated(parse_test_term("let {a b} = {1 2}; (a b)")), "let a = 1; let b = 2; (a b)");
+  assert_eq!(annihilated(parse_test_term("let {a *} = {3 2}; a")), "let a = 3; let * = 2; a");
+  // Nested pairs are simplified from the inside out.
+  assert_eq!(
+    annihilated(parse_test_term("λx let {a b} = {x let {c d} = {1 2}; d}; b")),
+    "λx let a = x; let b = let c = 1; let d = 2; d; b"
+  );
+  // Unscoped variables are kept.
+  assert_eq!(annihilated(parse_test_term("let {$a b} = {1 2}; (b $a)")), "let {$a b} = {1 2}; (b $a)");
+
+  // The labels must match, otherwise they commute.
+  // Labels can't be written in the syntax, so they're set after parsing.
+  let labelled = |pat_tag: Tag, tag: Tag| {
+    let mut term = parse_test_term("let {a b} = {1 2}; (a b)");
+    let Term::Let { pat, val, .. } = &mut term else { unreachable!() };
+    let (Pattern::Fan(_, dup_tag, _), Term::Fan { tag: sup_tag, .. }) = (pat.as_mut(), val.as_mut()) else {
+      unreachable!()
+    };
+    (*dup_tag, *sup_tag) = (pat_tag, tag);
+    term
+  };
+  assert_eq!(annihilated(labelled(Tag::Numeric(1), Tag::Numeric(1))), "let a = 1; let b = 2; (a b)");
+  let term = labelled(Tag::Numeric(1), Tag::Numeric(2));
+  assert_eq!(annihilated(term.clone()), term.to_string());
+  let term = labelled(Tag::Numeric(1), Tag::Auto);
+  assert_eq!(annihilated(term.clone()), term.to_string());
+}
@@ -46,7 +46,7 @@ impl Ctx<'_> {
         main_body = Term::call(main_body, args);
       }
 
-      main_def.rules = vec![Rule { pats: vec![], body: main_body }];
+      main_def.rules = vec![Rule { pats: vec![], body: main_body, guard: None }];
     }
 
     self.info.fatal(())
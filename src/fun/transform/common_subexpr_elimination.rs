@@ -0,0 +1,145 @@
+use crate::{
+  fun::{Book, Name, Pattern, Term},
+  maybe_grow,
+};
+use indexmap::IndexMap;
+
+impl Book {
+  /// Computes repeated numeric operations only once, binding them with a `let`.
+  ///
+  /// Only operations on variables, numbers and other such operations are shared,
+  /// since they always refer to the same value. Variables used more than once
+  /// are then duplicated by [`Book::linearize_vars`], so the result is computed once
+  /// and only the number is copied.
+  ///
+  /// The `let` is placed right inside the closest binder around the repeated operations,
+  /// like a lambda or a match arm, so the shared value is only computed where it was used.
+  ///
+  /// Example:
+  /// ```bend
+  /// λx λy (+ (* x y) (- (* x y) 1))
+  ///
+  /// // Becomes
+  /// λx λy let %cse0 = (* x y); (+ %cse0 (- %cse0 1))
+  /// ```
+  pub fn eliminate_common_subexprs(&mut self) {
+    for def in self.defs.values_mut() {
+      let mut gen = 0;
+      for rule in def.rules.iter_mut() {
+        rule.body.eliminate_common_subexprs(&mut gen);
+      }
+    }
+  }
+}
+
+impl Term {
+  /// Shares the repeated operations in the part of this term not under any binder.
+  /// Every variable in this part refers to the same binding, so equal terms have the same value.
+  fn eliminate_common_subexprs(&mut self, gen: &mut usize) {
+    maybe_grow(|| {
+      self.eliminate_nested_subexprs(gen);
+
+      let mut shared = vec![];
+      // The smallest repeated operation is shared first, so that the larger ones containing it
+      // refer to the new variable and can also be found repeated.
+      while let Some(expr) = self.smallest_repeated_oper() {
+        let nam = Name::new(format!("%cse{gen}"));
+        *gen += 1;
+        self.replace_oper(&expr, &nam);
+        shared.push((nam, expr));
+      }
+
+      let nxt = std::mem::take(self);
+      *self = shared.into_iter().rfold(nxt, |nxt, (nam, val)| Term::Let {
+        pat: Box::new(Pattern::Var(Some(nam))),
+        val: Box::new(val),
+        nxt: Box::new(nxt),
+      });
+    })
+  }
+
+  fn eliminate_nested_subexprs(&mut self, gen: &mut usize) {
+    maybe_grow(|| {
+      for (child, mut binds) in self.children_mut_with_binds() {
+        if binds.next().is_some() {
+          child.eliminate_common_subexprs(gen);
+        } else {
+          child.eliminate_nested_subexprs(gen);
+        }
+      }
+    })
+  }
+
+  fn smallest_repeated_oper(&self) -> Option<Term> {
+    let mut counts = IndexMap::new();
+    self.count_opers(&mut counts);
+    counts
+      .into_iter()
+      .filter(|(_, count)| *count > 1)
+      .map(|(expr, _)| expr)
+      .min_by_key(|expr| expr.size())
+      .cloned()
+  }
+
+  fn count_opers<'a>(&'a self, counts: &mut IndexMap<&'a Term, usize>) {
+    maybe_grow(|| {
+      if self.is_shareable_oper() {
+        *counts.entry(self).or_default() += 1;
+      }
+      for (child, mut binds) in self.children_with_binds() {
+        if binds.next().is_none() {
+          child.count_opers(counts);
+        }
+      }
+    })
+  }
+
+  fn replace_oper(&mut self, expr: &Term, nam: &Name) {
+    maybe_grow(|| {
+      if self == expr {
+        *self = Term::Var { nam: nam.clone() };
+        return;
+      }
+      for (child, mut binds) in self.children_mut_with_binds() {
+        if binds.next().is_none() {
+          child.replace_oper(expr, nam);
+        }
+      }
+    })
+  }
+
+  fn is_shareable_oper(&self) -> bool {
+    fn is_operand(term: &Term) -> bool {
+      matches!(term, Term::Var { .. } | Term::Num { .. }) || term.is_shareable_oper()
+    }
+    maybe_grow(|| match self {
+      Term::Oper { fst, snd, .. } => is_operand(fst) && is_operand(snd),
+      _ => false,
+    })
+  }
+}
+
+#[test]
+fn eliminate_common_subexprs() {
+  use crate::fun::load_book::parse_test_term;
+  let eliminate = |code: &str| {
+    let mut term = parse_test_term(code);
+    term.eliminate_common_subexprs(&mut 0);
+    term.to_string()
+  };
+
+  assert_eq!(
+    eliminate("λx λy (+ (* x y) (- (* x y) 1))"),
+    "λx λy let %cse0 = (* x y); (+ %cse0 (- %cse0 1))"
+  );
+  // Larger repeated operations are shared after the ones inside them.
+  assert_eq!(
+    eliminate("λx (+ (* (+ x 1) 2) (* (+ x 1) 2))"),
+    "λx let %cse0 = (+ x 1); let %cse1 = (* %cse0 2); (+ %cse1 %cse1)"
+  );
+  // Each binder has its own scope, where the same variable names can mean something else.
+  assert_eq!(eliminate("λx (x (+ x 1) λx (+ x 1))"), "λx (x (+ x 1) λx (+ x 1))");
+  assert_eq!(eliminate("λx (x (+ x 1) λy (+ x 1))"), "λx (x (+ x 1) λy (+ x 1))");
+  // Only numeric operations are shared.
+  assert_eq!(eliminate("λx (+ (f x) (f x))"), "λx (+ (f x) (f x))");
+}
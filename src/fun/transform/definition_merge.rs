@@ -16,12 +16,12 @@ impl Book {
   /// Should not be preceded by passes that cares about the origins.
   pub fn merge_definitions(&mut self) {
     let defs: Vec<_> = self.defs.keys().cloned().collect();
-    self.merge(defs.into_iter());
+    self.merge_identical(defs.into_iter());
   }
 
   /// Checks and merges identical definitions given by `defs`.
   /// We never merge the entrypoint function with something else.
-  fn merge(&mut self, defs: impl Iterator<Item = Name>) {
+  fn merge_identical(&mut self, defs: impl Iterator<Item = Name>) {
     let name = self.entrypoint.clone();
     // Sets of definitions that are identical, indexed by the body term.
     let equal_terms =
@@ -40,7 +40,11 @@ impl Book {
       if equal_defs.len() > 1 {
         // Merging some defs
         // Add the merged def
-        let new_def = Definition::new_gen(new_name.clone(), vec![Rule { pats: vec![], body: term }], builtin);
+        let new_def = Definition::new_gen(
+          new_name.clone(),
+          vec![Rule { pats: vec![], body: term, guard: None }],
+          builtin,
+        );
         self.defs.insert(new_name.clone(), new_def);
         // Remove the old ones and write the map of old names to new ones.
         for name in equal_defs {
@@ -79,7 +83,7 @@ impl Book {
     }
 
     if !updated_defs.is_empty() {
-      self.merge(updated_defs.into_iter());
+      self.merge_identical(updated_defs.into_iter());
     }
   }
 }
@@ -1,10 +1,10 @@
 use crate::{
   diagnostics::WarningType,
-  fun::{Book, Ctx, Name, Source, Term},
+  fun::{Book, Ctx, Name, Pattern, Source, Term},
   maybe_grow,
 };
 use hvm::ast::{Net, Tree};
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum Used {
@@ -18,12 +18,24 @@ enum Used {
 
 type Definitions = HashMap<Name, Used>;
 
+/// The constructors that each definition matches on, by the name of the definition.
+pub type MatchedCtrs = HashMap<Name, HashSet<Name>>;
+
 impl Ctx<'_> {
   /// If `prune_all`, removes all unused definitions and adts starting from Main.
   /// Otherwise, prunes only the builtins not accessible from any non-built-in definition.
   ///
   /// Emits unused definition warnings.
   pub fn prune(&mut self, prune_all: bool) {
+    self.prune_with_matches(prune_all, &MatchedCtrs::new())
+  }
+
+  /// Like [`Ctx::prune`], but also keeps the constructors in `matched_ctrs` that are matched on
+  /// by used definitions.
+  ///
+  /// Once the matches are encoded they don't name their constructors anymore, so these must be
+  /// collected before with [`Book::matched_ctrs`].
+  pub fn prune_with_matches(&mut self, prune_all: bool, matched_ctrs: &MatchedCtrs) {
     let mut used = Definitions::new();
 
     // Get the functions that are accessible from the main entry point.
@@ -32,6 +44,7 @@ impl Ctx<'_> {
       used.insert(main.clone(), Used::Main);
       self.book.find_used_definitions_from_term(&def.rule().body, Used::Main, &mut used);
     }
+    self.book.insert_matched_ctrs(matched_ctrs, &mut used);
 
     // Get the functions that are accessible from non-builtins.
     for def in self.book.defs.values() {
@@ -50,6 +63,7 @@ impl Ctx<'_> {
         self.book.find_used_definitions_from_hvm_net(&def.body, Used::NonBuiltin, &mut used);
       }
     }
+    self.book.insert_matched_ctrs(matched_ctrs, &mut used);
 
     fn rm_def(book: &mut Book, def_name: &Name) {
       if book.defs.contains_key(def_name) {
@@ -100,6 +114,59 @@ impl Ctx<'_> {
 }
 
 impl Book {
+  /// Collects the constructors that each definition matches on, in match terms or rule patterns,
+  /// for [`Ctx::prune_with_matches`].
+  pub fn matched_ctrs(&self) -> MatchedCtrs {
+    fn go_pat(pat: &Pattern, ctrs: &mut HashSet<Name>) {
+      maybe_grow(|| {
+        if let Pattern::Ctr(ctr, _) = pat {
+          ctrs.insert(ctr.clone());
+        }
+        for child in pat.children() {
+          go_pat(child, ctrs);
+        }
+      })
+    }
+    let mut matched = MatchedCtrs::new();
+    for (def_name, def) in &self.defs {
+      let mut ctrs = HashSet::new();
+      for rule in &def.rules {
+        for pat in &rule.pats {
+          go_pat(pat, &mut ctrs);
+        }
+        rule.body.visit(|term| {
+          if let Term::Mat { arms, .. } | Term::Fold { arms, .. } = term {
+            ctrs.extend(arms.iter().flat_map(|(ctr, _, _)| ctr.clone()));
+          }
+        });
+      }
+      ctrs.retain(|ctr| self.ctrs.contains_key(ctr));
+      if !ctrs.is_empty() {
+        matched.insert(def_name.clone(), ctrs);
+      }
+    }
+    matched
+  }
+
+  /// Marks as used the constructors matched on by the used definitions,
+  /// until the ones that they use don't match on any other constructor.
+  fn insert_matched_ctrs(&self, matched_ctrs: &MatchedCtrs, uses: &mut Definitions) {
+    loop {
+      let new_ctrs = matched_ctrs
+        .iter()
+        .filter_map(|(def_name, ctrs)| uses.get(def_name).map(|used| (*used, ctrs)))
+        .flat_map(|(used, ctrs)| ctrs.iter().map(move |ctr| (ctr, used)))
+        .filter(|(ctr, _)| !uses.contains_key(*ctr) && self.defs.contains_key(*ctr))
+        .collect::<Vec<_>>();
+      if new_ctrs.is_empty() {
+        break;
+      }
+      for (ctr, used) in new_ctrs {
+        self.insert_used(ctr, used, uses);
+      }
+    }
+  }
+
   /// Finds all used definitions on the book, starting from the given term.
   fn find_used_definitions_from_term(&self, term: &Term, used: Used, uses: &mut Definitions) {
     maybe_grow(|| {
@@ -116,6 +183,14 @@ impl Book {
             self.insert_used(&Name::new(crate::fun::builtins::SCONS), used, uses);
             self.insert_used(&Name::new(crate::fun::builtins::SNIL), used, uses);
           }
+          // Constructors matched on are used, even if they're never built.
+          Term::Mat { arms, .. } | Term::Fold { arms, .. } => {
+            for (ctr, _, _) in arms.iter() {
+              if let Some(ctr) = ctr.as_ref().filter(|ctr| self.defs.contains_key(*ctr)) {
+                self.insert_used(ctr, used, uses);
+              }
+            }
+          }
           _ => {}
         }
 
@@ -126,6 +201,19 @@ impl Book {
     })
   }
 
+  fn find_used_definitions_from_pattern(&self, pat: &Pattern, used: Used, uses: &mut Definitions) {
+    maybe_grow(|| {
+      if let Pattern::Ctr(ctr, _) = pat {
+        if self.defs.contains_key(ctr) {
+          self.insert_used(ctr, used, uses);
+        }
+      }
+      for child in pat.children() {
+        self.find_used_definitions_from_pattern(child, used, uses);
+      }
+    })
+  }
+
   fn find_used_definitions_from_hvm_net(&self, net: &Net, used: Used, uses: &mut Definitions) {
     maybe_grow(|| {
       let mut to_find = [&net.root]
@@ -158,6 +246,9 @@ impl Book {
       // E.g.: the `flatten_rules` golden test
       if let Some(def) = self.defs.get(def_name) {
         for rule in &def.rules {
+          for pat in &rule.pats {
+            self.find_used_definitions_from_pattern(pat, used, uses);
+          }
           self.find_used_definitions_from_term(&rule.body, used, uses);
         }
       } else if let Some(def) = self.hvm_defs.get(def_name) {
@@ -87,7 +87,8 @@ impl Term {
         let body = Term::rfold_lams(body, free_vars.iter().cloned().map(Some));
 
         // Make a definition from the new function
-        let def = Definition::new(new_nam.clone(), vec![Rule { pats: vec![], body }], source.clone());
+        let def =
+          Definition::new(new_nam.clone(), vec![Rule { pats: vec![], body, guard: None }], source.clone());
         new_defs.insert(new_nam.clone(), def);
 
         // Call the new function in the original term.
@@ -121,7 +121,8 @@ impl Term {
         let body = Term::rfold_lams(body, free_vars.iter().map(|nam| Some(nam.clone())));
         let body = Term::lam(Pattern::Var(Some(x_nam)), body);
 
-        let def = Definition::new(new_nam.clone(), vec![Rule { pats: vec![], body }], source.clone());
+        let def =
+          Definition::new(new_nam.clone(), vec![Rule { pats: vec![], body, guard: None }], source.clone());
         new_defs.push(def);
 
         // Call the new function
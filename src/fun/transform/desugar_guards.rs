@@ -0,0 +1,116 @@
+use crate::{
+  diagnostics::Diagnostics,
+  fun::{Ctx, Definition, Name, Pattern, Term},
+  maybe_grow,
+};
+use indexmap::IndexMap;
+
+const NEW_FN_SEP: &str = "__guard";
+
+impl Ctx<'_> {
+  /// Desugars the guards of pattern matching rules into a switch on the guard condition.
+  ///
+  /// When the guard is false, evaluation falls through to the rules after the guarded one.
+  /// This is done by calling a new definition with only those rules, passing back the matched arguments.
+  ///
+  /// Example:
+  /// ```bend
+  /// (Foo (Bar x)) | (> x 0) = A
+  /// (Foo (Bar x)) = B
+  /// (Foo *) = C
+  ///
+  /// // Becomes
+  /// (Foo (Bar x)) = switch %cond = (> x 0) { 0: (Foo__guard0 (Bar x)); _: A }
+  /// (Foo (Bar x)) = B
+  /// (Foo *) = C
+  ///
+  /// (Foo__guard0 (Bar x)) = B
+  /// (Foo__guard0 *) = C
+  /// ```
+  pub fn desugar_guards(&mut self) -> Result<(), Diagnostics> {
+    self.info.start_pass();
+
+    let mut new_defs = IndexMap::new();
+    for def in self.book.defs.values_mut() {
+      if let Err(err) = def.desugar_guards(&mut new_defs) {
+        self.info.add_rule_error(err, def.name.clone());
+      }
+      for rule in &def.rules {
+        if rule.body.has_local_guards() {
+          self.info.add_rule_error("Guards are not supported in local definitions.", def.name.clone());
+          break;
+        }
+      }
+    }
+    self.book.defs.extend(new_defs);
+
+    self.info.fatal(())
+  }
+}
+
+impl Definition {
+  fn desugar_guards(&mut self, new_defs: &mut IndexMap<Name, Definition>) -> Result<(), String> {
+    if self.rules.iter().all(|rule| rule.guard.is_none()) {
+      return Ok(());
+    }
+    if self.rules.last().unwrap().guard.is_some() {
+      return Err(
+        "The last rule has a guard, so some arguments might not match any rule. Add a rule without a guard after it."
+          .to_string(),
+      );
+    }
+
+    // Going from the last rule, so that the rules copied to each new definition are already desugared.
+    for i in (0..self.rules.len()).rev() {
+      let Some(guard) = self.rules[i].guard.take() else { continue };
+      let new_nam = Name::new(format!("{}{}{}", self.name, NEW_FN_SEP, i));
+
+      // Erased arguments need to be bound, so they can be passed to the next rules.
+      let rule = &mut self.rules[i];
+      let mut fresh = 0;
+      for pat in rule.pats.iter_mut() {
+        pat.name_erased(&mut fresh);
+      }
+
+      let call = Term::call(Term::Ref { nam: new_nam.clone() }, rule.pats.iter().map(Pattern::to_term));
+      rule.body = Term::Swt {
+        arg: Box::new(guard),
+        bnd: Some(Name::new("%cond")),
+        with_bnd: vec![],
+        with_arg: vec![],
+        pred: Some(Name::new("%cond-1")),
+        arms: vec![call, std::mem::take(&mut rule.body)],
+      };
+
+      let rules = self.rules[i + 1..].to_vec();
+      new_defs.insert(new_nam.clone(), Definition::new_gen(new_nam, rules, self.is_builtin()));
+    }
+    Ok(())
+  }
+}
+
+impl Pattern {
+  fn name_erased(&mut self, fresh: &mut usize) {
+    maybe_grow(|| {
+      if let Pattern::Var(nam @ None) = self {
+        *nam = Some(Name::new(format!("%arg{fresh}")));
+        *fresh += 1;
+      }
+      for child in self.children_mut() {
+        child.name_erased(fresh);
+      }
+    })
+  }
+}
+
+impl Term {
+  fn has_local_guards(&self) -> bool {
+    maybe_grow(|| match self {
+      Term::Def { def, nxt } => {
+        def.rules.iter().any(|rule| rule.guard.is_some() || rule.body.has_local_guards())
+          || nxt.has_local_guards()
+      }
+      _ => self.children().any(Term::has_local_guards),
+    })
+  }
+}
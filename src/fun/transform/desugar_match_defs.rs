@@ -1,12 +1,13 @@
 use crate::{
-  diagnostics::{Diagnostics, WarningType},
+  diagnostics::{CompileError, Diagnostics, WarningType},
   fun::{builtins, Adts, Constructors, Ctx, Definition, FanKind, Name, Num, Pattern, Rule, Tag, Term},
   maybe_grow,
 };
+use itertools::Itertools;
 use std::collections::{BTreeSet, HashSet};
 
 pub enum DesugarMatchDefErr {
-  AdtNotExhaustive { adt: Name, ctr: Name },
+  AdtNotExhaustive { adt: Name, ctrs: Vec<Name> },
   NumMissingDefault,
   TypeMismatch { expected: Type, found: Type, pat: Pattern },
   RepeatedBind { bind: Name },
@@ -21,10 +22,26 @@ impl Ctx<'_> {
       let errs = def.desugar_match_def(&self.book.ctrs, &self.book.adts);
       for err in errs {
         match err {
-          DesugarMatchDefErr::AdtNotExhaustive { .. }
-          | DesugarMatchDefErr::NumMissingDefault
-          | DesugarMatchDefErr::TypeMismatch { .. } => self.info.add_rule_error(err, def_name.clone()),
-          DesugarMatchDefErr::RepeatedBind { .. } => {
+          DesugarMatchDefErr::AdtNotExhaustive { adt, ctrs } => {
+            let kind = CompileError::NonExhaustiveMatch {
+              def_name: def_name.clone(),
+              adt: Some(adt),
+              missing: ctrs,
+              span: def.source.span(),
+            };
+            self.info.add_compile_error(kind)
+          }
+          DesugarMatchDefErr::NumMissingDefault => {
+            let kind = CompileError::NonExhaustiveMatch {
+              def_name: def_name.clone(),
+              adt: None,
+              missing: vec![],
+              span: def.source.span(),
+            };
+            self.info.add_compile_error(kind)
+          }
+          err @ DesugarMatchDefErr::TypeMismatch { .. } => self.info.add_rule_error(err, def_name.clone()),
+          err @ DesugarMatchDefErr::RepeatedBind { .. } => {
             self.info.add_rule_warning(err, WarningType::RepeatedBind, def_name.clone())
           }
         }
@@ -49,7 +66,7 @@ impl Definition {
     match simplify_rule_match(args.clone(), rules, vec![], ctrs, adts) {
       Ok(body) => {
         let body = Term::rfold_lams(body, args.into_iter().map(Some));
-        self.rules = vec![Rule { pats: vec![], body }];
+        self.rules = vec![Rule { pats: vec![], body, guard: None }];
       }
       Err(e) => errs.push(e),
     }
@@ -147,7 +164,7 @@ fn simplify_rule_match(
 /// This is useful to avoid unnecessary pattern matching.
 fn irrefutable_fst_row_rule(args: Vec<Name>, rule: Rule) -> Term {
   let mut term = rule.body;
-  for (arg, pat) in args.into_iter().zip(rule.pats.into_iter()) {
+  for (arg, pat) in args.into_iter().zip(rule.pats) {
     match pat {
       Pattern::Var(None) => {}
       Pattern::Var(Some(var)) => {
@@ -193,7 +210,7 @@ fn var_rule(
       };
     }
 
-    let new_rule = Rule { pats: new_pats, body: rule.body };
+    let new_rule = Rule { pats: new_pats, body: rule.body, guard: None };
     new_rules.push(new_rule);
   }
 
@@ -254,7 +271,7 @@ fn fan_rule(
     };
     new_pats.extend(old_pats);
 
-    let new_rule = Rule { pats: new_pats, body: rule.body };
+    let new_rule = Rule { pats: new_pats, body: rule.body, guard: None };
     new_rules.push(new_rule);
   }
 
@@ -304,7 +321,7 @@ fn num_rule(
       match &rule.pats[0] {
         Pattern::Num(n) if n == num => {
           let body = rule.body.clone();
-          let rule = Rule { pats: rule.pats[1..].to_vec(), body };
+          let rule = Rule { pats: rule.pats[1..].to_vec(), body, guard: None };
           new_rules.push(rule);
         }
         Pattern::Var(var) => {
@@ -316,7 +333,7 @@ fn num_rule(
               nxt: Box::new(std::mem::take(&mut body)),
             };
           }
-          let rule = Rule { pats: rule.pats[1..].to_vec(), body };
+          let rule = Rule { pats: rule.pats[1..].to_vec(), body, guard: None };
           new_rules.push(rule);
         }
         _ => (),
@@ -338,7 +355,7 @@ fn num_rule(
         body = Term::Use { nam: Some(var.clone()), val: Box::new(var_recovered), nxt: Box::new(body) };
         fast_pred_access(&mut body, cur_num, var, &pred_var);
       }
-      let rule = Rule { pats: rule.pats[1..].to_vec(), body };
+      let rule = Rule { pats: rule.pats[1..].to_vec(), body, guard: None };
       new_rules.push(rule);
     }
   }
@@ -454,6 +471,23 @@ fn switch_rule(
   let arg = args[0].clone();
   let old_args = args.split_off(1);
 
+  // Report all the uncovered constructors at once instead of stopping at the first one.
+  let missing_ctrs = adts[&adt_name]
+    .ctrs
+    .keys()
+    .filter(|ctr| {
+      !rules.iter().any(|rule| match &rule.pats[0] {
+        Pattern::Ctr(found_ctr, _) => found_ctr == *ctr,
+        Pattern::Var(_) => true,
+        _ => false,
+      })
+    })
+    .cloned()
+    .collect::<Vec<_>>();
+  if !missing_ctrs.is_empty() {
+    return Err(DesugarMatchDefErr::AdtNotExhaustive { adt: adt_name, ctrs: missing_ctrs });
+  }
+
   let mut new_arms = vec![];
   for (ctr, fields) in &adts[&adt_name].ctrs {
     let new_args = fields.iter().map(|f| Name::new(format!("{}.{}", arg, f.nam)));
@@ -470,7 +504,7 @@ fn switch_rule(
         Pattern::Ctr(found_ctr, new_pats) if ctr == found_ctr => {
           let pats = new_pats.iter().cloned().chain(old_pats).collect();
           let body = rule.body.clone();
-          let rule = Rule { pats, body };
+          let rule = Rule { pats, body, guard: None };
           new_rules.push(rule);
         }
         // Var, match and rebuild the constructor.
@@ -488,17 +522,13 @@ fn switch_rule(
             body =
               Term::Use { nam: Some(var.clone()), val: Box::new(reconstructed_var), nxt: Box::new(body) };
           }
-          let rule = Rule { pats, body };
+          let rule = Rule { pats, body, guard: None };
           new_rules.push(rule);
         }
         _ => (),
       }
     }
 
-    if new_rules.is_empty() {
-      return Err(DesugarMatchDefErr::AdtNotExhaustive { adt: adt_name, ctr: ctr.clone() });
-    }
-
     let body = simplify_rule_match(args, new_rules, with.clone(), ctrs, adts)?;
     new_arms.push((Some(ctr.clone()), new_args.map(Some).collect(), body));
   }
@@ -587,9 +617,15 @@ impl std::fmt::Display for Type {
 impl std::fmt::Display for DesugarMatchDefErr {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
-      DesugarMatchDefErr::AdtNotExhaustive { adt, ctr } => {
-        write!(f, "Non-exhaustive pattern matching rule. Constructor '{ctr}' of type '{adt}' not covered")
-      }
+      DesugarMatchDefErr::AdtNotExhaustive { adt, ctrs } => match ctrs.as_slice() {
+        [ctr] => {
+          write!(f, "Non-exhaustive pattern matching rule. Constructor '{ctr}' of type '{adt}' not covered")
+        }
+        ctrs => {
+          let ctrs = ctrs.iter().map(|ctr| format!("'{ctr}'")).join(", ");
+          write!(f, "Non-exhaustive pattern matching rule. Constructors {ctrs} of type '{adt}' not covered")
+        }
+      },
       DesugarMatchDefErr::TypeMismatch { expected, found, pat } => {
         write!(
           f,
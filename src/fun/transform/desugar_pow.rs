@@ -0,0 +1,131 @@
+use crate::{
+  fun::{Book, Name, Num, Op, Pattern, Term},
+  maybe_grow,
+};
+
+impl Book {
+  /// Desugars integer exponentiations into multiplications.
+  ///
+  /// HVM only has exponentiation for floats, using the same opcode as `^` for integers.
+  /// Since there's no type system, this can only be done when the exponent is an integer literal,
+  /// which means the base must also be an integer, since the number types can't be mixed.
+  ///
+  /// The power is computed by repeated squaring, so the number of multiplications
+  /// is logarithmic on the exponent. If the base is also a literal, the result is computed directly.
+  ///
+  /// Example:
+  /// ```bend
+  /// (** x 5)
+  ///
+  /// // Becomes
+  /// let %pow0 = x; (* %pow0 let %pow1 = (* %pow0 %pow0); let %pow2 = (* %pow1 %pow1); %pow2)
+  /// ```
+  ///
+  /// Negative exponents are left unchanged.
+  pub fn desugar_pow(&mut self) {
+    for def in self.defs.values_mut() {
+      for rule in def.rules.iter_mut() {
+        rule.body.desugar_pow();
+      }
+    }
+  }
+}
+
+impl Term {
+  pub fn desugar_pow(&mut self) {
+    maybe_grow(|| {
+      for child in self.children_mut() {
+        child.desugar_pow();
+      }
+      if let Term::Oper { opr: Op::POW, fst, snd } = self {
+        let (exp, one) = match snd.as_ref() {
+          Term::Num { val: Num::U24(exp) } => (*exp, Num::U24(1)),
+          Term::Num { val: Num::I24(exp @ 0..) } => (*exp as u32, Num::I24(1)),
+          _ => return,
+        };
+        *self = match std::mem::take(fst.as_mut()) {
+          Term::Num { val } => Term::Num { val: num_pow(val, exp, one) },
+          base if exp == 0 => Term::Let {
+            pat: Box::new(Pattern::Var(None)),
+            val: Box::new(base),
+            nxt: Box::new(Term::Num { val: one }),
+          },
+          base => {
+            let nam = pow_var(0);
+            Term::Let {
+              pat: Box::new(Pattern::Var(Some(nam.clone()))),
+              val: Box::new(base),
+              nxt: Box::new(pow_by_squaring(nam, exp, 0)),
+            }
+          }
+        };
+      }
+    })
+  }
+}
+
+/// Builds `x^exp` for the variable `x` bound to `%pow{id}`, with `exp > 0`.
+fn pow_by_squaring(x: Name, exp: u32, id: usize) -> Term {
+  if exp == 1 {
+    Term::Var { nam: x }
+  } else if exp % 2 == 1 {
+    let rest = pow_by_squaring(x.clone(), exp - 1, id);
+    Term::Oper { opr: Op::MUL, fst: Box::new(Term::Var { nam: x }), snd: Box::new(rest) }
+  } else {
+    let sqr = pow_var(id + 1);
+    let val = Term::Oper {
+      opr: Op::MUL,
+      fst: Box::new(Term::Var { nam: x.clone() }),
+      snd: Box::new(Term::Var { nam: x }),
+    };
+    Term::Let {
+      pat: Box::new(Pattern::Var(Some(sqr.clone()))),
+      val: Box::new(val),
+      nxt: Box::new(pow_by_squaring(sqr, exp / 2, id + 1)),
+    }
+  }
+}
+
+fn pow_var(id: usize) -> Name {
+  Name::new(format!("%pow{id}"))
+}
+
+/// Computes the power with the same wraparound as the multiplications it would be compiled to.
+fn num_pow(base: Num, mut exp: u32, one: Num) -> Num {
+  let mul = |a, b| Op::MUL.compute(a, b).unwrap();
+  let mut base = base;
+  let mut res = one;
+  while exp > 0 {
+    if exp % 2 == 1 {
+      res = mul(res, base);
+    }
+    base = mul(base, base);
+    exp /= 2;
+  }
+  res
+}
+
+#[test]
+fn desugar_integer_pow() {
+  use crate::fun::load_book::parse_test_term;
+  let desugar = |code: &str| {
+    let mut term = parse_test_term(code);
+    term.desugar_pow();
+    term.to_string()
+  };
+
+  assert_eq!(
+    desugar("(** x 5)"),
+    "let %pow0 = x; (* %pow0 let %pow1 = (* %pow0 %pow0); let %pow2 = (* %pow1 %pow1); %pow2)"
+  );
+  assert_eq!(desugar("(** (f x) 1)"), "let %pow0 = (f x); %pow0");
+  assert_eq!(desugar("(** x 0)"), "let * = x; 1");
+  assert_eq!(desugar("(** x +0)"), "let * = x; +1");
+  // Literals are computed directly, wrapping around like the multiplications would.
+  assert_eq!(desugar("(** 3 4)"), "81");
+  assert_eq!(desugar("(** -2 +3)"), "-8");
+  assert_eq!(desugar("(** 2 24)"), "0");
+  // Float and negative exponents are left to HVM.
+  assert_eq!(desugar("(** x 0.5)"), "(** x 0.500)");
+  assert_eq!(desugar("(** x -1)"), "(** x -1)");
+}
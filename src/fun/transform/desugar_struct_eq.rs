@@ -0,0 +1,430 @@
+use crate::{
+  diagnostics::Diagnostics,
+  fun::{Adts, Constructors, CtrField, Ctx, Definition, FanKind, Name, Num, Op, Pattern, Rule, Term},
+  maybe_grow,
+};
+use indexmap::IndexSet;
+use std::collections::HashMap;
+
+pub enum StructEqErr {
+  PartialCtr { opr: Op, operand: Term },
+}
+
+impl Ctx<'_> {
+  /// Desugars `==` and `!=` between tuples or constructors into structural comparisons.
+  ///
+  /// Since there's no type system, this is only done when one of the operands is a tuple
+  /// or a fully applied constructor, so that the shape of the values is known, or a variable
+  /// known to hold a value of some type. That's the case for the variables bound to the
+  /// recursive fields of constructor patterns, to a matched value inside the arms of a `match`,
+  /// or to a constructor by a `let`.
+  /// Tuples and constructors are compared field by field, recursing into the fields whose
+  /// shape is known from either operand. When the other operand has an unknown shape, it's
+  /// matched against the known constructor.
+  /// Values of a known type whose shape is unknown, like recursive fields, are compared with a
+  /// generated `Type__equals` function, and the other values of unknown shape with `==`,
+  /// so they must hold numbers.
+  ///
+  /// Values that are known to have different shapes, like tuples of different sizes
+  /// or constructors of different types, are never equal.
+  /// Comparing a constructor that is missing some of its fields is an error.
+  ///
+  /// Example:
+  /// ```bend
+  /// (== (a, b) x)
+  ///
+  /// // Becomes
+  /// let (%eq0, %eq1) = x; (& (== a %eq0) (== b %eq1))
+  ///
+  /// type List = (Cons head ~tail) | Nil
+  /// (== (List/Cons 1 xs) x)
+  ///
+  /// // Becomes
+  /// match %eq0 = x { List/Cons: (& (== 1 %eq0.head) (List__equals xs %eq0.tail)); List/Nil: 0 }
+  ///
+  /// // With
+  /// (List__equals (List/Cons %a0 %a1) (List/Cons %b0 %b1)) = (& (== %a0 %b0) (List__equals %a1 %b1))
+  /// (List__equals List/Nil List/Nil) = 1
+  /// (List__equals * *) = 0
+  /// ```
+  ///
+  /// Must run after the references are resolved and before the pattern matching definitions are desugared.
+  pub fn desugar_struct_eq(&mut self) -> Result<(), Diagnostics> {
+    self.info.start_pass();
+
+    let mut ctx = StructEqCtx {
+      ctrs: &self.book.ctrs,
+      adts: &self.book.adts,
+      eq_adts: IndexSet::new(),
+      var_count: 0,
+      errs: vec![],
+    };
+    for (def_name, def) in self.book.defs.iter_mut() {
+      ctx.var_count = 0;
+      for rule in def.rules.iter_mut() {
+        let mut env = Env::new();
+        for pat in rule.pats.iter() {
+          ctx.bind_pattern(pat, &mut env);
+        }
+        rule.body.desugar_struct_eq(&mut ctx, &env);
+      }
+      for err in std::mem::take(&mut ctx.errs) {
+        self.info.add_rule_error(err, def_name.clone());
+      }
+    }
+
+    // The generated functions can require the functions of other types, so they're generated until there are no new ones.
+    let mut adt_idx = 0;
+    while let Some(adt_name) = ctx.eq_adts.get_index(adt_idx).cloned() {
+      adt_idx += 1;
+      let def_name = eq_def_name(&adt_name);
+      if self.book.defs.contains_key(&def_name) {
+        continue;
+      }
+      ctx.var_count = 0;
+      let adt = &ctx.adts[&adt_name];
+      let mut rules = vec![];
+      for (ctr_name, fields) in adt.ctrs.iter() {
+        let var = |side: &str, idx: usize| Name::new(format!("%{side}{idx}"));
+        let pat = |side: &str| {
+          let fields = (0..fields.len()).map(|idx| Pattern::Var(Some(var(side, idx)))).collect();
+          Pattern::Ctr(ctr_name.clone(), fields)
+        };
+        let field_eqs = fields
+          .iter()
+          .enumerate()
+          .map(|(idx, field)| {
+            let (a, b) = (Term::Var { nam: var("a", idx) }, Term::Var { nam: var("b", idx) });
+            ctx.field_eq(a, b, field, &adt_name, &Env::new())
+          })
+          .collect::<Vec<_>>();
+        rules.push(Rule {
+          pats: vec![pat("a"), pat("b")],
+          body: and_all(field_eqs.into_iter()),
+          guard: None,
+        });
+      }
+      if adt.ctrs.len() > 1 {
+        rules.push(Rule {
+          pats: vec![Pattern::Var(None), Pattern::Var(None)],
+          body: bool_num(false),
+          guard: None,
+        });
+      }
+      let def = Definition::new_gen(def_name.clone(), rules, adt.source.is_builtin());
+      self.book.defs.insert(def_name, def);
+    }
+
+    self.info.fatal(())
+  }
+}
+
+struct StructEqCtx<'a> {
+  ctrs: &'a Constructors,
+  adts: &'a Adts,
+  /// The types that need a generated equality function.
+  eq_adts: IndexSet<Name>,
+  var_count: usize,
+  errs: Vec<StructEqErr>,
+}
+
+/// The variables in scope that are known to hold a value of some type, with the name of the type.
+type Env = HashMap<Name, Name>;
+
+/// What is known about the value of an operand.
+enum Shape {
+  /// A tuple with this many elements.
+  Tup(usize),
+  /// A fully applied constructor, with its type and name.
+  Ctr(Name, Name),
+  /// A value of this type, of an unknown constructor.
+  Adt(Name),
+  /// A constructor applied to fewer arguments than it has fields.
+  PartialCtr,
+  Num,
+  Unknown,
+}
+
+impl Term {
+  fn desugar_struct_eq(&mut self, ctx: &mut StructEqCtx, env: &Env) {
+    maybe_grow(|| {
+      match self {
+        Term::Def { def, nxt } => {
+          for rule in def.rules.iter_mut() {
+            let mut env = env.clone();
+            for pat in rule.pats.iter() {
+              unbind_all(&mut env, pat.binds());
+              ctx.bind_pattern(pat, &mut env);
+            }
+            rule.body.desugar_struct_eq(ctx, &env);
+          }
+          nxt.desugar_struct_eq(ctx, env);
+          return;
+        }
+        Term::Mat { arg, bnd, with_bnd, with_arg, arms } => {
+          arg.desugar_struct_eq(ctx, env);
+          for arg in with_arg.iter_mut() {
+            arg.desugar_struct_eq(ctx, env);
+          }
+          for (ctr, _, body) in arms.iter_mut() {
+            let mut env = env.clone();
+            unbind_all(&mut env, [&*bnd].into_iter().chain(with_bnd.iter()));
+            if let (Some(bnd), Some(ctr)) = (bnd.as_ref(), ctr.as_ref()) {
+              ctx.bind_matched(bnd, ctr, &mut env);
+            }
+            body.desugar_struct_eq(ctx, &env);
+          }
+        }
+        Term::Let { pat, val, nxt } => {
+          let adt = match (pat.as_ref(), ctx.shape(val, env)) {
+            (Pattern::Var(Some(nam)), Shape::Ctr(adt, _) | Shape::Adt(adt)) => Some((nam.clone(), adt)),
+            _ => None,
+          };
+          val.desugar_struct_eq(ctx, env);
+          let mut env = env.clone();
+          unbind_all(&mut env, pat.binds());
+          env.extend(adt);
+          nxt.desugar_struct_eq(ctx, &env);
+        }
+        _ => {
+          for (child, binds) in self.children_mut_with_binds() {
+            let mut env = env.clone();
+            unbind_all(&mut env, binds);
+            child.desugar_struct_eq(ctx, &env);
+          }
+        }
+      }
+      if let Term::Oper { opr: opr @ (Op::EQ | Op::NEQ), fst, snd } = self {
+        let is_neq = *opr == Op::NEQ;
+        let shapes = [ctx.shape(fst, env), ctx.shape(snd, env)];
+        for (operand, shape) in [fst.as_ref(), snd.as_ref()].into_iter().zip(&shapes) {
+          if let Shape::PartialCtr = shape {
+            ctx.errs.push(StructEqErr::PartialCtr { opr: *opr, operand: operand.clone() });
+          }
+        }
+        if !shapes.iter().any(|shape| matches!(shape, Shape::Tup(_) | Shape::Ctr(..) | Shape::Adt(_))) {
+          return;
+        }
+        let eq = ctx.struct_eq(std::mem::take(fst), std::mem::take(snd), env);
+        *self = if is_neq {
+          Term::Oper { opr: Op::EQ, fst: Box::new(eq), snd: Box::new(bool_num(false)) }
+        } else {
+          eq
+        };
+      }
+    })
+  }
+}
+
+impl StructEqCtx<'_> {
+  fn shape(&self, term: &Term, env: &Env) -> Shape {
+    let mut head = term;
+    let mut args = 0;
+    while let Term::App { fun, .. } = head {
+      head = fun;
+      args += 1;
+    }
+    match head {
+      Term::Fan { fan: FanKind::Tup, els, .. } if args == 0 => Shape::Tup(els.len()),
+      Term::Num { .. } if args == 0 => Shape::Num,
+      Term::Var { nam } if args == 0 => match env.get(nam) {
+        Some(adt) => Shape::Adt(adt.clone()),
+        None => Shape::Unknown,
+      },
+      Term::Ref { nam } => match self.ctrs.get(nam) {
+        Some(adt) if self.adts[adt].ctrs[nam].len() == args => Shape::Ctr(adt.clone(), nam.clone()),
+        Some(adt) if self.adts[adt].ctrs[nam].len() > args => Shape::PartialCtr,
+        _ => Shape::Unknown,
+      },
+      _ => Shape::Unknown,
+    }
+  }
+
+  /// Adds the variables of a constructor pattern that bind recursive fields, which hold values of the same type.
+  fn bind_pattern(&self, pat: &Pattern, env: &mut Env) {
+    if let Pattern::Ctr(ctr, fields) = pat {
+      if let Some(adt) = self.ctrs.get(ctr) {
+        for (field, pat) in self.adts[adt].ctrs[ctr].iter().zip(fields) {
+          if let (true, Pattern::Var(Some(nam))) = (field.rec, pat) {
+            env.insert(nam.clone(), adt.clone());
+          }
+        }
+      }
+    }
+    for child in pat.children() {
+      self.bind_pattern(child, env);
+    }
+  }
+
+  /// Adds the matched variable of a `match` arm and its recursive fields, which hold values of the matched type.
+  fn bind_matched(&self, bnd: &Name, ctr: &Name, env: &mut Env) {
+    let Some(adt) = self.ctrs.get(ctr) else { return };
+    env.insert(bnd.clone(), adt.clone());
+    for field in self.adts[adt].ctrs[ctr].iter().filter(|field| field.rec) {
+      env.insert(Name::new(format!("{bnd}.{}", field.nam)), adt.clone());
+    }
+  }
+
+  /// Builds a term that is 1 if `a` and `b` are equal and 0 otherwise.
+  fn struct_eq(&mut self, a: Term, b: Term, env: &Env) -> Term {
+    maybe_grow(|| match (self.shape(&a, env), self.shape(&b, env)) {
+      (Shape::Tup(a_len), Shape::Tup(b_len)) => {
+        if a_len != b_len {
+          return bool_num(false);
+        }
+        let (mut a, mut b) = (a, b);
+        let (Term::Fan { els: a_els, .. }, Term::Fan { els: b_els, .. }) = (&mut a, &mut b) else {
+          unreachable!()
+        };
+        let eqs = std::mem::take(a_els)
+          .into_iter()
+          .zip(std::mem::take(b_els))
+          .map(|(a, b)| self.struct_eq(a, b, env))
+          .collect::<Vec<_>>();
+        and_all(eqs.into_iter())
+      }
+      (Shape::Tup(_), Shape::Unknown) => self.tup_eq(a, b, env),
+      (Shape::Unknown, Shape::Tup(_)) => self.tup_eq(b, a, env),
+      (Shape::Ctr(a_adt, a_ctr), Shape::Ctr(b_adt, b_ctr)) => {
+        if a_adt != b_adt || a_ctr != b_ctr {
+          return bool_num(false);
+        }
+        let (mut a, mut b) = (a, b);
+        let fields = &self.adts[&a_adt].ctrs[&a_ctr];
+        let eqs = take_args(&mut a)
+          .into_iter()
+          .zip(take_args(&mut b))
+          .zip(fields)
+          .map(|((a, b), field)| self.field_eq(a, b, field, &a_adt, env))
+          .collect::<Vec<_>>();
+        and_all(eqs.into_iter())
+      }
+      (Shape::Ctr(adt, _), Shape::Adt(other)) | (Shape::Adt(other), Shape::Ctr(adt, _)) if adt != other => {
+        bool_num(false)
+      }
+      (Shape::Ctr(..), Shape::Unknown | Shape::Adt(_)) => self.ctr_eq(a, b, env),
+      (Shape::Unknown | Shape::Adt(_), Shape::Ctr(..)) => self.ctr_eq(b, a, env),
+      (Shape::Adt(a_adt), Shape::Adt(b_adt)) if a_adt != b_adt => bool_num(false),
+      (Shape::Adt(adt), Shape::Adt(_) | Shape::Unknown) | (Shape::Unknown, Shape::Adt(adt)) => {
+        self.eq_adts.insert(adt.clone());
+        Term::call(Term::Ref { nam: eq_def_name(&adt) }, [a, b])
+      }
+      (Shape::Num | Shape::Unknown, Shape::Num | Shape::Unknown) => {
+        Term::Oper { opr: Op::EQ, fst: Box::new(a), snd: Box::new(b) }
+      }
+      // A number, a tuple and a constructor are never equal to each other.
+      _ => bool_num(false),
+    })
+  }
+
+  /// Compares two fields of a constructor of type `adt`.
+  ///
+  /// If the shape of neither is known but the field is recursive, they're compared with the equality function of `adt`.
+  fn field_eq(&mut self, a: Term, b: Term, field: &CtrField, adt: &Name, env: &Env) -> Term {
+    if field.rec && matches!((self.shape(&a, env), self.shape(&b, env)), (Shape::Unknown, Shape::Unknown)) {
+      self.eq_adts.insert(adt.clone());
+      Term::call(Term::Ref { nam: eq_def_name(adt) }, [a, b])
+    } else {
+      self.struct_eq(a, b, env)
+    }
+  }
+
+  /// Compares a constructor with a value of unknown shape, by matching the value against the constructor's type.
+  fn ctr_eq(&mut self, mut ctr: Term, other: Term, env: &Env) -> Term {
+    let Shape::Ctr(adt_name, ctr_name) = self.shape(&ctr, env) else { unreachable!() };
+    let bnd = Name::new(format!("%eq{}", self.var_count));
+    self.var_count += 1;
+    let mut args = take_args(&mut ctr).into_iter();
+    let adts = self.adts;
+    let arms = adts[&adt_name]
+      .ctrs
+      .iter()
+      .map(|(arm_ctr, fields)| {
+        let body = if *arm_ctr == ctr_name {
+          let eqs = fields
+            .iter()
+            .zip(args.by_ref())
+            .map(|(field, arg)| {
+              let var = Term::Var { nam: Name::new(format!("{bnd}.{}", field.nam)) };
+              self.field_eq(arg, var, field, &adt_name, env)
+            })
+            .collect::<Vec<_>>();
+          and_all(eqs.into_iter())
+        } else {
+          bool_num(false)
+        };
+        (Some(arm_ctr.clone()), vec![], body)
+      })
+      .collect();
+    Term::Mat { bnd: Some(bnd), arg: Box::new(other), with_bnd: vec![], with_arg: vec![], arms }
+  }
+
+  /// Compares a tuple with a value of unknown shape, by destructuring the value as a tuple of the same size.
+  fn tup_eq(&mut self, mut tup: Term, other: Term, env: &Env) -> Term {
+    let Term::Fan { fan, tag, els } = &mut tup else { unreachable!() };
+    let (fan, tag, els) = (*fan, std::mem::take(tag), std::mem::take(els));
+    let vars = (0..els.len())
+      .map(|_| {
+        let nam = Name::new(format!("%eq{}", self.var_count));
+        self.var_count += 1;
+        nam
+      })
+      .collect::<Vec<_>>();
+    let eqs = els
+      .into_iter()
+      .zip(&vars)
+      .map(|(el, var)| self.struct_eq(el, Term::Var { nam: var.clone() }, env))
+      .collect::<Vec<_>>();
+    Term::Let {
+      pat: Box::new(Pattern::Fan(fan, tag, vars.into_iter().map(|var| Pattern::Var(Some(var))).collect())),
+      val: Box::new(other),
+      nxt: Box::new(and_all(eqs.into_iter())),
+    }
+  }
+}
+
+/// Removes the variables that are bound again, along with the fields of a matched variable that is bound again.
+fn unbind_all<'a>(env: &mut Env, binds: impl IntoIterator<Item = &'a Option<Name>>) {
+  for nam in binds.into_iter().flatten() {
+    let fields = format!("{nam}.");
+    env.retain(|var, _| var != nam && !var.starts_with(&fields));
+  }
+}
+
+/// Takes the arguments of an application, leaving the function applied to nothing.
+fn take_args(term: &mut Term) -> Vec<Term> {
+  let mut args = vec![];
+  let mut head = term;
+  while let Term::App { fun, arg, .. } = head {
+    args.push(std::mem::take(arg.as_mut()));
+    head = fun.as_mut();
+  }
+  args.reverse();
+  args
+}
+
+fn eq_def_name(adt: &Name) -> Name {
+  Name::new(format!("{adt}__equals"))
+}
+
+fn bool_num(val: bool) -> Term {
+  Term::Num { val: Num::U24(val as u32) }
+}
+
+/// `(& a (& b ...))`, or 1 if there's nothing to compare.
+fn and_all(eqs: impl DoubleEndedIterator<Item = Term>) -> Term {
+  let mut eqs = eqs.rev();
+  let Some(last) = eqs.next() else { return bool_num(true) };
+  eqs.fold(last, |acc, eq| Term::Oper { opr: Op::AND, fst: Box::new(eq), snd: Box::new(acc) })
+}
+
+impl std::fmt::Display for StructEqErr {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      StructEqErr::PartialCtr { opr, operand } => write!(
+        f,
+        "Operand '{operand}' of operation '{opr}' is a constructor missing some of its fields, so it can't be compared."
+      ),
+    }
+  }
+}
@@ -5,6 +5,9 @@ use crate::{
 
 impl Book {
   /// Defines a function for each constructor in each ADT in the book.
+  ///
+  /// Each function takes one argument per field of the constructor, so constructors
+  /// can be used like any other function, including being partially applied.
   pub fn encode_adts(&mut self, adt_encoding: AdtEncoding) {
     let mut defs = vec![];
     let mut tags = vec![];
@@ -24,7 +27,7 @@ impl Book {
           }
         };
 
-        let rules = vec![Rule { pats: vec![], body }];
+        let rules = vec![Rule { pats: vec![], body, guard: None }];
         let def = Definition::new(ctr_name.clone(), rules, adt.source.clone());
         defs.push((ctr_name.clone(), def));
       }
@@ -66,6 +69,21 @@ fn encode_ctr_num_scott<'a>(ctr_args: impl DoubleEndedIterator<Item = &'a Name>
 }
 
 fn make_tag_def(ctr_idx: usize, tag: &Name, builtin: bool) -> Definition {
-  let tag_rule = vec![Rule { pats: vec![], body: Term::Num { val: Num::U24(ctr_idx as u32) } }];
+  let tag_rule = vec![Rule { pats: vec![], body: Term::Num { val: Num::U24(ctr_idx as u32) }, guard: None }];
   Definition::new_gen(tag.clone(), tag_rule, builtin)
 }
+
+#[test]
+fn ctr_functions() {
+  use crate::fun::load_book::do_parse_book_default;
+  let code = "type Shape = (Circle radius) | (Rect width height)\nmain = *";
+  let mut book = do_parse_book_default(code, std::path::Path::new("ctrs")).unwrap();
+  book.encode_adts(AdtEncoding::NumScott);
+
+  let circle = &book.defs[&Name::new("Shape/Circle")];
+  let rect = &book.defs[&Name::new("Shape/Rect")];
+  assert_eq!(circle.rule().body.to_string(), "λradius λ%x (%x Shape/Circle/tag radius)");
+  assert_eq!(rect.rule().body.to_string(), "λwidth λheight λ%x (%x Shape/Rect/tag width height)");
+  assert_eq!(book.defs[&Name::new("Shape/Circle/tag")].rule().body.to_string(), "0");
+  assert_eq!(book.defs[&Name::new("Shape/Rect/tag")].rule().body.to_string(), "1");
+}
@@ -0,0 +1,59 @@
+use crate::{
+  fun::{Book, Pattern, Term},
+  maybe_grow,
+};
+
+impl Book {
+  /// Eta-reduces all definitions, turning `λx (f x)` into `f` when `x` doesn't occur in `f`.
+  pub fn eta_reduction(&mut self) {
+    let recursive_defs = self.recursive_defs();
+    for (def_name, def) in self.defs.iter_mut() {
+      for rule in def.rules.iter_mut() {
+        for child in rule.body.children_mut() {
+          child.eta_reduction();
+        }
+        // Keep the outermost lambda of recursive definitions, since removing it could leave
+        // definitions that reference each other directly, like in `Foo = λx (Bar x)` and
+        // `Bar = λx (Foo x)`, which would expand forever.
+        if recursive_defs.contains(def_name) {
+          continue;
+        }
+        while rule.body.eta_reduce_once() {}
+      }
+    }
+  }
+}
+
+impl Term {
+  pub fn eta_reduction(&mut self) {
+    maybe_grow(|| {
+      for child in self.children_mut() {
+        child.eta_reduction();
+      }
+      // Reducing can expose another redex, as in `λa λb (f a b)`.
+      while self.eta_reduce_once() {}
+    })
+  }
+
+  /// If this term is an eta-redex `λx (f x)`, returns `f`.
+  fn eta_redex_fun(&self) -> Option<&Term> {
+    let Term::Lam { tag: lam_tag, pat, bod } = self else { return None };
+    let Pattern::Var(Some(var)) = pat.as_ref() else { return None };
+    let Term::App { tag: app_tag, fun, arg } = bod.as_ref() else { return None };
+    let is_redex = lam_tag == app_tag
+      && matches!(arg.as_ref(), Term::Var { nam } if nam == var)
+      && !fun.free_vars().contains_key(var);
+    is_redex.then_some(fun)
+  }
+
+  /// Tries to eta-reduce this term, returning whether it was reduced.
+  fn eta_reduce_once(&mut self) -> bool {
+    if self.eta_redex_fun().is_none() {
+      return false;
+    }
+    let Term::Lam { bod, .. } = self else { unreachable!() };
+    let Term::App { fun, .. } = bod.as_mut() else { unreachable!() };
+    *self = std::mem::take(fun.as_mut());
+    true
+  }
+}
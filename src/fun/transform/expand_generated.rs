@@ -1,9 +1,8 @@
-use std::collections::{BTreeSet, HashMap, HashSet};
-
 use crate::{
   fun::{Book, Name, Term},
   maybe_grow,
 };
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 /// Dereferences any non recursive generated definitions in the term.
 /// Used after readback.
@@ -23,84 +22,118 @@ impl Term {
   }
 }
 
-type DepGraph = HashMap<Name, HashSet<Name>>;
-type Cycles = Vec<Vec<Name>>;
 type RecursiveDefs = BTreeSet<Name>;
 
 impl Book {
   pub fn recursive_defs(&self) -> RecursiveDefs {
-    let mut cycle_map = BTreeSet::new();
-    let deps = book_def_deps(self);
-    let cycles = cycles(&deps);
+    self.find_recursion().into_iter().flatten().collect()
+  }
 
-    for cycle in cycles {
-      for name in cycle {
-        cycle_map.insert(name);
+  /// Finds the groups of definitions that are recursive, directly or through each other.
+  ///
+  /// These are the strongly connected components of the [call graph](Book::call_graph)
+  /// that have more than one definition, or a single one that references itself.
+  /// Both the groups and the definitions in each group are in the same order as in the book.
+  pub fn find_recursion(&self) -> Vec<Vec<Name>> {
+    // Native hvm definitions can't reference back the definitions of the book.
+    let mut deps = self.call_graph();
+    for refs in deps.values_mut() {
+      refs.retain(|nam| self.defs.contains_key(nam));
+    }
+    let mut tarjan = Tarjan {
+      deps: &deps,
+      index: HashMap::new(),
+      low_link: HashMap::new(),
+      stack: vec![],
+      on_stack: HashSet::new(),
+      sccs: vec![],
+    };
+    for nam in self.defs.keys() {
+      if !tarjan.index.contains_key(nam) {
+        tarjan.visit(nam);
       }
     }
 
-    cycle_map
+    let order = |nam: &Name| self.defs.get_index_of(nam);
+    let mut sccs = tarjan
+      .sccs
+      .into_iter()
+      .filter(|scc| scc.len() > 1 || deps[scc[0]].contains(scc[0]))
+      .map(|scc| {
+        let mut scc = scc.into_iter().cloned().collect::<Vec<_>>();
+        scc.sort_by_key(|nam| order(nam));
+        scc
+      })
+      .collect::<Vec<_>>();
+    sccs.sort_by_key(|scc| order(&scc[0]));
+    sccs
   }
 }
 
-/// Find all cycles in the dependency graph.
-fn cycles(deps: &DepGraph) -> Cycles {
-  let mut cycles = vec![];
-  let mut visited = HashSet::new();
-  // let mut stack = vec![];
-  for nam in deps.keys() {
-    if !visited.contains(nam) {
-      find_cycles(deps, nam, &mut visited, &mut cycles);
-    }
-  }
-  cycles
+/// Tarjan's strongly connected components algorithm.
+struct Tarjan<'a> {
+  deps: &'a HashMap<Name, HashSet<Name>>,
+  /// The order in which each definition was first visited.
+  index: HashMap<&'a Name, usize>,
+  /// The smallest index reachable from each definition still in the stack.
+  low_link: HashMap<&'a Name, usize>,
+  stack: Vec<&'a Name>,
+  on_stack: HashSet<&'a Name>,
+  sccs: Vec<Vec<&'a Name>>,
 }
 
-fn find_cycles(deps: &DepGraph, nam: &Name, visited: &mut HashSet<Name>, cycles: &mut Cycles) {
-  let mut stack = vec![(nam.clone(), vec![])];
-  while let Some((current, path)) = stack.pop() {
-    if visited.contains(&current) {
-      // Check if the current ref is already in the stack, which indicates a cycle.
-      if let Some(cycle_start) = path.iter().position(|n| n == &current) {
-        // If found, add the cycle to the cycles vector.
-        cycles.push(path[cycle_start..].to_vec());
+impl<'a> Tarjan<'a> {
+  fn visit(&mut self, nam: &'a Name) {
+    maybe_grow(|| {
+      let idx = self.index.len();
+      self.index.insert(nam, idx);
+      self.low_link.insert(nam, idx);
+      self.stack.push(nam);
+      self.on_stack.insert(nam);
+
+      for dep in &self.deps[nam] {
+        if !self.index.contains_key(dep) {
+          self.visit(dep);
+          let low = self.low_link[nam].min(self.low_link[dep]);
+          self.low_link.insert(nam, low);
+        } else if self.on_stack.contains(dep) {
+          let low = self.low_link[nam].min(self.index[dep]);
+          self.low_link.insert(nam, low);
+        }
       }
-      continue;
-    }
-
-    // If the ref has not been visited yet, mark it as visited.
-    visited.insert(current.clone());
-    // Add the current ref to the stack to keep track of the path.
-    let mut new_path = path.clone();
-    new_path.push(current.clone());
 
-    // Search for cycles from each dependency.
-    if let Some(deps) = deps.get(&current) {
-      for dep in deps {
-        stack.push((dep.clone(), new_path.clone()));
+      // The definition is the root of a component, which is everything above it in the stack.
+      if self.low_link[nam] == idx {
+        let mut scc = vec![];
+        loop {
+          let member = self.stack.pop().unwrap();
+          self.on_stack.remove(member);
+          scc.push(member);
+          if member == nam {
+            break;
+          }
+        }
+        self.sccs.push(scc);
       }
-    }
+    })
   }
 }
 
-fn book_def_deps(book: &Book) -> DepGraph {
-  book.defs.iter().map(|(nam, def)| (nam.clone(), def_deps(def))).collect()
-}
-
-fn def_deps(def: &crate::fun::Definition) -> HashSet<Name> {
-  fn collect_refs(term: &Term, set: &mut HashSet<Name>) {
-    if let Term::Ref { nam } = term {
-      set.insert(nam.clone());
-    }
-    for children in term.children() {
-      collect_refs(children, set);
-    }
-  }
-
-  let mut set = HashSet::new();
-  let term = &def.rule().body;
-
-  collect_refs(term, &mut set);
-
-  set
+#[test]
+fn find_recursion() {
+  use crate::fun::load_book::parse_test_book;
+  let code = "
+chain_a = chain_b
+chain_b = (chain_c 1)
+chain_c = λx x
+is_even n = switch n { 0: 1; _: (is_odd n-1) }
+is_odd n = switch n { 0: 0; _: (is_even n-1) }
+loop x = (loop x)
+main = (chain_a (is_even 4))
+";
+  let book = parse_test_book(code);
+  assert_eq!(
+    book.find_recursion(),
+    [vec![Name::new("is_even"), Name::new("is_odd")], vec![Name::new("loop")]]
+  );
 }
@@ -0,0 +1,104 @@
+use crate::fun::{Book, Definition, Name, Pattern, Rule, Term};
+use std::collections::HashSet;
+
+impl Book {
+  /// Defines a function `Type.field` for each field of the ADTs with a single constructor,
+  /// that takes a value of that type and returns the field.
+  ///
+  /// Example:
+  /// ```bend
+  /// object Point { x, y }
+  ///
+  /// // Generates
+  /// (Point.x (Point x *)) = x
+  /// (Point.y (Point * y)) = y
+  /// ```
+  ///
+  /// Only the accessors that are used somewhere in the book are generated,
+  /// and only for user defined types, so builtin types don't get any.
+  ///
+  /// Types with more than one constructor don't get accessors, since the field
+  /// could be missing from the value. The ones that would have the same name as an existing
+  /// definition are also not generated, so that user definitions take precedence.
+  ///
+  /// Must run before the pattern matching definitions are desugared.
+  pub fn generate_field_accessors(&mut self) {
+    let used = self.used_names();
+    let mut defs = vec![];
+    for (adt_name, adt) in self.adts.iter() {
+      if adt.source.is_builtin() {
+        continue;
+      }
+      let [(ctr_name, fields)] = adt.ctrs.iter().collect::<Vec<_>>()[..] else { continue };
+      for field in fields {
+        let def_name = Name::new(format!("{adt_name}.{}", field.nam));
+        if !used.contains(&def_name)
+          || self.defs.contains_key(&def_name)
+          || self.hvm_defs.contains_key(&def_name)
+        {
+          continue;
+        }
+        let pats = fields
+          .iter()
+          .map(|other| Pattern::Var((other.nam == field.nam).then(|| other.nam.clone())))
+          .collect();
+        let pat = Pattern::Ctr(ctr_name.clone(), pats);
+        let body = Term::Var { nam: field.nam.clone() };
+        let rules = vec![Rule { pats: vec![pat], body, guard: None }];
+        defs.push((def_name.clone(), Definition::new_gen(def_name, rules, false)));
+      }
+    }
+    self.defs.extend(defs);
+  }
+
+  /// The names of the variables and references used in the rules of the definitions,
+  /// including the ones of local definitions.
+  fn used_names(&self) -> HashSet<Name> {
+    fn go(term: &Term, used: &mut HashSet<Name>) {
+      term.visit(|term| match term {
+        Term::Var { nam } | Term::Ref { nam } => {
+          used.insert(nam.clone());
+        }
+        Term::Def { def, .. } => {
+          for rule in &def.rules {
+            go(&rule.body, used);
+          }
+        }
+        _ => (),
+      });
+    }
+    let mut used = HashSet::new();
+    for def in self.defs.values() {
+      for rule in &def.rules {
+        go(&rule.body, &mut used);
+      }
+    }
+    used
+  }
+}
+
+#[test]
+fn field_accessors() {
+  use crate::fun::load_book::do_parse_book_default;
+  let code = "
+object Point { x, y }
+type Shape = (Circle radius) | (Rect width height)
+type Box = (Box ~val)
+type Unused = (Unused val)
+Point.y = *
+main = (Point.x (Point.y (Shape.radius (Box.val *))))
+";
+  let mut book = do_parse_book_default(code, std::path::Path::new("accessors")).unwrap();
+  book.generate_field_accessors();
+
+  let accessor =
+    |nam: &str| book.defs.get(&Name::new(nam)).map(|def| def.rules[0].display(&def.name).to_string());
+  assert_eq!(accessor("Point.x").unwrap(), "(Point.x (Point x *)) = x");
+  assert_eq!(accessor("Box.val").unwrap(), "(Box.val (Box/Box val)) = val");
+  // User definitions are not replaced
+  assert_eq!(accessor("Point.y").unwrap(), "(Point.y) = *");
+  // Types with many constructors don't get accessors
+  assert!(accessor("Shape.radius").is_none());
+  // Accessors that are never used are not generated
+  assert!(accessor("Unused.val").is_none());
+}
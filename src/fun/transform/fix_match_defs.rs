@@ -1,8 +1,13 @@
 use crate::{
-  diagnostics::Diagnostics,
-  fun::{Adts, Constructors, Ctx, Pattern, Rule, Term},
+  diagnostics::{CompileError, Diagnostics},
+  fun::{Adts, Constructors, Ctx, Name, Pattern, Rule, Source, Term},
 };
 
+enum FixMatchErr {
+  Arity { def_name: Name, source: Source, rule: usize, expected: usize, found: usize },
+  Other(String),
+}
+
 impl Ctx<'_> {
   /// Makes every pattern matching definition have correct a left-hand side.
   ///
@@ -14,12 +19,25 @@ impl Ctx<'_> {
       let mut errs = vec![];
 
       let def_arity = def.arity();
-      for rule in &mut def.rules {
-        rule.fix_match_defs(def_arity, &self.book.ctrs, &self.book.adts, &mut errs);
+      for (idx, rule) in def.rules.iter_mut().enumerate() {
+        rule.fix_match_defs(
+          (&def.name, &def.source),
+          idx,
+          def_arity,
+          &self.book.ctrs,
+          &self.book.adts,
+          &mut errs,
+        );
       }
 
       for err in errs {
-        self.info.add_rule_error(err, def.name.clone());
+        match err {
+          FixMatchErr::Arity { def_name, source, rule, expected, found } => {
+            let kind = CompileError::ArityMismatch { def_name, rule, expected, found, span: source.span() };
+            self.info.add_compile_error(kind);
+          }
+          FixMatchErr::Other(err) => self.info.add_rule_error(err, def.name.clone()),
+        }
       }
     }
 
@@ -28,13 +46,25 @@ impl Ctx<'_> {
 }
 
 impl Rule {
-  fn fix_match_defs(&mut self, def_arity: usize, ctrs: &Constructors, adts: &Adts, errs: &mut Vec<String>) {
+  /// `idx` is the position of this rule in its definition `def`, used in the error messages.
+  fn fix_match_defs(
+    &mut self,
+    def: (&Name, &Source),
+    idx: usize,
+    def_arity: usize,
+    ctrs: &Constructors,
+    adts: &Adts,
+    errs: &mut Vec<FixMatchErr>,
+  ) {
+    // The arity of the definition is the arity of its first rule.
     if self.arity() != def_arity {
-      errs.push(format!(
-        "Incorrect pattern matching rule arity. Expected {} args, found {}.",
-        def_arity,
-        self.arity()
-      ));
+      errs.push(FixMatchErr::Arity {
+        def_name: def.0.clone(),
+        source: def.1.clone(),
+        rule: idx,
+        expected: def_arity,
+        found: self.arity(),
+      });
     }
 
     for pat in &mut self.pats {
@@ -42,17 +72,20 @@ impl Rule {
       pat.check_good_ctr(ctrs, adts, errs);
     }
 
+    if let Some(guard) = &mut self.guard {
+      guard.fix_match_defs(ctrs, adts, errs);
+    }
     self.body.fix_match_defs(ctrs, adts, errs);
   }
 }
 
 impl Term {
-  fn fix_match_defs(&mut self, ctrs: &Constructors, adts: &Adts, errs: &mut Vec<String>) {
+  fn fix_match_defs(&mut self, ctrs: &Constructors, adts: &Adts, errs: &mut Vec<FixMatchErr>) {
     match self {
       Term::Def { def, nxt } => {
         let def_arity = def.arity();
-        for rule in &mut def.rules {
-          rule.fix_match_defs(def_arity, ctrs, adts, errs);
+        for (idx, rule) in def.rules.iter_mut().enumerate() {
+          rule.fix_match_defs((&def.name, &def.source), idx, def_arity, ctrs, adts, errs);
         }
         nxt.fix_match_defs(ctrs, adts, errs);
       }
@@ -79,19 +112,19 @@ impl Pattern {
   }
 
   /// Check that ADT constructor pats are correct, meaning defined in a `data` and with correct arity.
-  fn check_good_ctr(&self, ctrs: &Constructors, adts: &Adts, errs: &mut Vec<String>) {
+  fn check_good_ctr(&self, ctrs: &Constructors, adts: &Adts, errs: &mut Vec<FixMatchErr>) {
     if let Pattern::Ctr(nam, args) = self {
       if let Some(adt) = ctrs.get(nam) {
         let expected_arity = adts[adt].ctrs[nam].len();
         let found_arity = args.len();
         if expected_arity != found_arity {
-          errs.push(format!(
+          errs.push(FixMatchErr::Other(format!(
             "Incorrect arity for constructor '{}' of type '{}' in pattern matching rule. Expected {} fields, found {}",
             nam, adt, expected_arity, found_arity
-          ));
+          )));
         }
       } else {
-        errs.push(format!("Unbound constructor '{nam}' in pattern matching rule."));
+        errs.push(FixMatchErr::Other(format!("Unbound constructor '{nam}' in pattern matching rule.")));
       }
     }
     for child in self.children() {
@@ -47,7 +47,7 @@ impl Book {
       let builtin = def.is_builtin();
       let body = &mut def.rule_mut().body;
       ctx.reset();
-      ctx.def_size = body.size();
+      ctx.def_size = body.net_size();
       body.float_combinators(&mut ctx, def_name, builtin);
     }
 
@@ -91,13 +91,13 @@ impl Term {
         child.float_combinators(ctx, def_name, builtin);
       }
 
-      let mut size = self.size();
+      let mut size = self.net_size();
       let is_combinator = self.is_combinator();
 
       // Float unsafe children and children that make the term too big.
       for child in self.float_children_mut() {
         let child_is_safe = child.is_safe(ctx);
-        let child_size = child.size();
+        let child_size = child.net_size();
 
         let extract_for_size = if is_combinator { size > ctx.max_size } else { ctx.def_size > ctx.max_size };
 
@@ -118,7 +118,7 @@ impl Term {
     let comb_ref = Term::Ref { nam: comb_name.clone() };
     let extracted_term = std::mem::replace(self, comb_ref);
 
-    let rules = vec![Rule { body: extracted_term, pats: Vec::new() }];
+    let rules = vec![Rule { body: extracted_term, pats: Vec::new(), guard: None }];
     let rule = Definition::new_gen(comb_name.clone(), rules, builtin);
     ctx.combinators.insert(comb_name, (is_safe, rule));
   }
@@ -227,9 +227,9 @@ impl Term {
     }
   }
 
-  fn size(&self) -> usize {
+  fn net_size(&self) -> usize {
     maybe_grow(|| {
-      let children_size: usize = self.children().map(|c| c.size()).sum();
+      let children_size: usize = self.children().map(|c| c.net_size()).sum();
       self.base_size() + children_size
     })
   }
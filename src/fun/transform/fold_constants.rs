@@ -0,0 +1,84 @@
+use crate::{
+  fun::{Book, Num, Op, Term},
+  maybe_grow,
+};
+use hvm::hvm::{Numb, OP_EQ, TY_F24};
+
+const PORT_VAL_MASK: u32 = 0x1FFF_FFFF;
+
+impl Book {
+  /// Evaluates the numeric operations whose operands are both number literals.
+  ///
+  /// The results are computed exactly like HVM would at runtime, including wraparound.
+  /// Operations that would be undefined at runtime, like an integer division by zero,
+  /// are left unchanged.
+  pub fn fold_constants(&mut self) {
+    for def in self.defs.values_mut() {
+      for rule in def.rules.iter_mut() {
+        rule.body.fold_constants();
+      }
+    }
+  }
+}
+
+impl Term {
+  pub fn fold_constants(&mut self) {
+    maybe_grow(|| {
+      for child in self.children_mut() {
+        child.fold_constants();
+      }
+      if let Term::Oper { opr, fst, snd } = self {
+        if let (Term::Num { val: fst }, Term::Num { val: snd }) = (fst.as_ref(), snd.as_ref()) {
+          if let Some(val) = opr.compute(*fst, *snd) {
+            *self = Term::Num { val };
+          }
+        }
+      }
+    })
+  }
+}
+
+impl Op {
+  /// Computes the result of the operation with the same encoding the compiled net uses.
+  /// Returns `None` if the result is undefined.
+  pub fn compute(self, fst: Num, snd: Num) -> Option<Num> {
+    let is_int = Numb(snd.to_bits()).get_typ() != TY_F24;
+    match self {
+      Op::DIV | Op::REM if is_int && snd.is_zero() => return None,
+      Op::SHL | Op::SHR if !matches!(snd, Num::U24(_)) => return None,
+      _ => {}
+    }
+
+    // Numbers lose their top bits when stored in a net port, which truncates the payload to 24 bits.
+    let port_numb = |bits: u32| Numb(bits & PORT_VAL_MASK);
+    let fst = port_numb((fst.to_bits() & !0x1F) | self.to_native_tag() as u32);
+    let res = Numb::operate(fst, port_numb(snd.to_bits()));
+    let res = match self {
+      Op::LE | Op::GE => Numb::operate(Numb(OP_EQ as u32), res),
+      _ => res,
+    };
+    Some(Num::from_bits(port_numb(res.0).0))
+  }
+}
+
+#[test]
+fn fold_constants_net_size() {
+  use crate::{
+    fun::load_book::parse_test_term, fun::term_to_net::term_to_hvm, hvm::check_net_size::count_nodes,
+  };
+  let nodes = |term: &Term| count_nodes(&term_to_hvm(term, &mut Default::default()).unwrap());
+
+  let term = parse_test_term("λx (+ (+ 1 2) x)");
+  let mut folded = term.clone();
+  folded.fold_constants();
+  assert_eq!(folded.to_string(), "λx (+ 3 x)");
+
+  // Each operation compiles to two OPX nodes, and the folded one also saves a redex at runtime.
+  assert_eq!(nodes(&term), 4);
+  assert_eq!(nodes(&folded), 2);
+
+  // Only operations between literals are folded, the symbolic ones are kept.
+  let mut term = parse_test_term("λx (+ (+ x 2) 1)");
+  term.fold_constants();
+  assert_eq!(term.to_string(), "λx (+ (+ x 2) 1)");
+}
@@ -0,0 +1,168 @@
+use crate::{
+  fun::{Book, FanKind, Name, Pattern, Term},
+  maybe_grow,
+};
+use std::collections::{HashMap, HashSet};
+
+/// The maximum number of nodes a definition can have to be inlined.
+pub const INLINE_MAX_SIZE: usize = 32;
+
+impl Book {
+  /// Replaces references to small definitions with their bodies, when applied to enough
+  /// arguments to remove all of their leading lambdas.
+  ///
+  /// A definition can be inlined if it has a single rule without patterns, its body has at most
+  /// `max_size` nodes and it's not recursive, directly or through other definitions.
+  ///
+  /// Bodies with duplications, superpositions or unscoped variables are never inlined,
+  /// since copying them could change the behaviour of the program.
+  /// Neither are definitions that erase or duplicate one of their arguments.
+  pub fn inline_defs(&mut self, max_size: usize) {
+    let inlinable = self.inlinable_defs(max_size);
+    if inlinable.is_empty() {
+      return;
+    }
+    for def in self.defs.values_mut() {
+      for rule in def.rules.iter_mut() {
+        rule.body.inline_defs(&inlinable);
+      }
+    }
+  }
+
+  fn inlinable_defs(&self, max_size: usize) -> HashMap<Name, Term> {
+    let refs = self
+      .defs
+      .iter()
+      .map(|(name, def)| {
+        let mut refs = HashSet::new();
+        for rule in &def.rules {
+          rule.body.collect_refs(&mut refs);
+        }
+        (name, refs)
+      })
+      .collect::<HashMap<_, _>>();
+
+    let is_recursive = |def_name: &Name| {
+      let mut seen = HashSet::new();
+      let mut to_visit = refs[def_name].iter().collect::<Vec<_>>();
+      while let Some(name) = to_visit.pop() {
+        if *name == def_name {
+          return true;
+        }
+        if seen.insert(name) {
+          to_visit.extend(refs.get(name).into_iter().flatten());
+        }
+      }
+      false
+    };
+
+    self
+      .defs
+      .iter()
+      .filter(|(_, def)| def.rules.len() == 1 && def.rules[0].pats.is_empty())
+      .filter(|(_, def)| def.rule().body.size() <= max_size && def.rule().body.is_inlinable())
+      .filter(|(name, _)| !is_recursive(name))
+      .map(|(name, def)| (name.clone(), def.rule().body.clone()))
+      .collect()
+  }
+}
+
+impl Term {
+  pub fn inline_defs(&mut self, inlinable: &HashMap<Name, Term>) {
+    maybe_grow(|| {
+      for child in self.children_mut() {
+        child.inline_defs(inlinable);
+      }
+      if let Some(inlined) = self.inline_call(inlinable) {
+        *self = inlined;
+        // The inlined body may reference other inlinable definitions
+        // and substituting the arguments may have created new calls.
+        self.inline_defs(inlinable);
+      }
+    })
+  }
+
+  /// If this term is a call to an inlinable definition, returns the result of
+  /// substituting the arguments in its body.
+  fn inline_call(&self, inlinable: &HashMap<Name, Term>) -> Option<Term> {
+    let mut args = vec![];
+    let mut head = self;
+    while let Term::App { tag, fun, arg } = head {
+      args.push((tag, arg.as_ref()));
+      head = fun;
+    }
+    let Term::Ref { nam } = head else { return None };
+    let body = inlinable.get(nam)?;
+    let lam_count = body.lam_count();
+    // Partial applications would just become a new combinator.
+    if lam_count > args.len() {
+      return None;
+    }
+
+    let mut body = body.clone();
+    let mut args = args.into_iter().rev();
+    for (app_tag, arg) in args.by_ref().take(lam_count) {
+      let Term::Lam { tag: lam_tag, pat, bod } = &mut body else { unreachable!() };
+      if lam_tag != app_tag {
+        return None;
+      }
+      body = match pat.as_ref() {
+        Pattern::Var(Some(var)) if bod.free_vars().get(var) == Some(&1) => {
+          let var = var.clone();
+          let mut bod = std::mem::take(bod.as_mut());
+          bod.subst(&var, arg);
+          bod
+        }
+        // Discarding an argument could leave a closed term that would then be floated
+        // into a new definition, making it lazier than the original call.
+        _ => return None,
+      };
+    }
+
+    Some(args.fold(body, |fun, (tag, arg)| Term::App {
+      tag: tag.clone(),
+      fun: Box::new(fun),
+      arg: Box::new(arg.clone()),
+    }))
+  }
+
+  fn is_inlinable(&self) -> bool {
+    maybe_grow(|| {
+      let has_dup_or_unscoped = match self {
+        Term::Fan { fan: FanKind::Dup, .. } | Term::Link { .. } => true,
+        _ => self.pattern().is_some_and(Pattern::has_dup_or_unscoped),
+      };
+      !has_dup_or_unscoped && self.children().all(Term::is_inlinable)
+    })
+  }
+
+  fn lam_count(&self) -> usize {
+    let mut count = 0;
+    let mut body = self;
+    while let Term::Lam { bod, .. } = body {
+      count += 1;
+      body = bod;
+    }
+    count
+  }
+
+  fn collect_refs<'a>(&'a self, refs: &mut HashSet<&'a Name>) {
+    maybe_grow(|| {
+      if let Term::Ref { nam } = self {
+        refs.insert(nam);
+      }
+      for child in self.children() {
+        child.collect_refs(refs);
+      }
+    })
+  }
+}
+
+impl Pattern {
+  fn has_dup_or_unscoped(&self) -> bool {
+    maybe_grow(|| {
+      matches!(self, Pattern::Fan(FanKind::Dup, _, _) | Pattern::Chn(_))
+        || self.children().any(Pattern::has_dup_or_unscoped)
+    })
+  }
+}
@@ -1,19 +1,28 @@
+pub mod annihilate_dups;
 pub mod apply_args;
+pub mod common_subexpr_elimination;
 pub mod definition_merge;
 pub mod definition_pruning;
 pub mod desugar_bend;
 pub mod desugar_fold;
+pub mod desugar_guards;
 pub mod desugar_match_defs;
 pub mod desugar_open;
+pub mod desugar_pow;
+pub mod desugar_struct_eq;
 pub mod desugar_use;
 pub mod desugar_with_blocks;
 pub mod encode_adts;
 pub mod encode_match_terms;
+pub mod eta_reduction;
 pub mod expand_generated;
 pub mod expand_main;
+pub mod field_accessors;
 pub mod fix_match_defs;
 pub mod fix_match_terms;
 pub mod float_combinators;
+pub mod fold_constants;
+pub mod inline_defs;
 pub mod lift_local_defs;
 pub mod linearize_matches;
 pub mod linearize_vars;
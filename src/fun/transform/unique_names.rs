@@ -4,7 +4,7 @@ use crate::{
   fun::{Book, Name, Term},
   maybe_grow,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 impl Book {
   /// Makes all variables in each definition have a new unique name.
@@ -29,9 +29,19 @@ type VarId = u64;
 pub struct UniqueNameGenerator {
   name_map: HashMap<Name, Vec<VarId>>,
   name_count: VarId,
+  /// If set, only the binds with these names are renamed.
+  only: Option<HashSet<Name>>,
+  /// Names that can't be given to renamed variables.
+  used: HashSet<Name>,
 }
 
 impl UniqueNameGenerator {
+  /// A generator that only renames the variables bound with one of the names in `names`,
+  /// giving them fresh names that don't appear in `used`.
+  pub fn renaming(names: HashSet<Name>, used: HashSet<Name>) -> Self {
+    UniqueNameGenerator { only: Some(names), used, ..Default::default() }
+  }
+
   // Recursively assign an id to each variable in the term, then convert each id into a unique name.
   pub fn unique_names_in_term(&mut self, term: &mut Term) {
     // Note: we can't use the children iterators here because we mutate the binds,
@@ -167,7 +177,10 @@ impl UniqueNameGenerator {
   }
 
   fn push(&mut self, nam: Option<&Name>) {
-    if let Some(name) = nam {
+    if let Some(name) = nam.filter(|nam| self.should_rename(nam)) {
+      while self.used.contains(&Name::from(self.name_count)) {
+        self.name_count += 1;
+      }
       if let Some(ids) = self.name_map.get_mut(name) {
         ids.push(self.name_count);
       } else {
@@ -178,17 +191,21 @@ impl UniqueNameGenerator {
   }
 
   fn pop(&mut self, nam: Option<&Name>) -> Option<Name> {
-    if let Some(name) = nam {
+    if let Some(name) = nam.filter(|nam| self.should_rename(nam)) {
       let var_id = self.name_map.get_mut(name).unwrap().pop().unwrap();
       if self.name_map[name].is_empty() {
         self.name_map.remove(name);
       }
       Some(Name::from(var_id))
     } else {
-      None
+      nam.cloned()
     }
   }
 
+  fn should_rename(&self, nam: &Name) -> bool {
+    self.only.as_ref().map_or(true, |only| only.contains(nam))
+  }
+
   fn use_var(&self, nam: &Name) -> Name {
     if let Some(vars) = self.name_map.get(nam) {
       let var_id = *vars.last().unwrap();
@@ -200,3 +217,21 @@ impl UniqueNameGenerator {
     }
   }
 }
+
+#[test]
+fn unique_names_with_shadowing() {
+  use crate::fun::load_book::parse_test_term;
+  let unique = |code: &str| {
+    let mut term = parse_test_term(code);
+    term.make_var_names_unique();
+    term.to_string()
+  };
+
+  // Each use refers to the innermost binder with its name.
+  assert_eq!(unique("λx λx (x λx x)"), "λa λb (b λc c)");
+  assert_eq!(unique("λx let x = (x 1); let (x, y) = x; (y x)"), "λa let b = (a 1); let (c, d) = b; (d c)");
+  assert_eq!(unique("λx let {x y} = x; (x y)"), "λa let {b c} = a; (b c)");
+  assert_eq!(unique("λn switch n { 0: n; _: λn (n n-1) }"), "λa switch b = a { 0: b; _ c: λd (d c); }");
+  // Unbound and scopeless variables are left as is, so `$x` is still paired with `λ$x`.
+  assert_eq!(unique("λx (x y λ$x $x)"), "λa (a y λ$x $x)");
+}
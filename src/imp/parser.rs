@@ -61,6 +61,13 @@ impl<'a> Parser<'a> for PyParser<'a> {
         continue;
       }
       if c == '#' {
+        if self.starts_with_multi_line_comment() {
+          // If the comment is not closed, stop at it so that the next error reports it.
+          if self.skip_multi_line_comment().is_none() {
+            break;
+          }
+          continue;
+        }
         while let Some(c) = self.peek_one() {
           if c != '\n' {
             self.advance_one();
@@ -122,7 +129,7 @@ impl<'a> PyParser<'a> {
       Ok(Expr::Str { val: STRINGS.get(self.parse_quoted_string()?) })
     } else if self.starts_with("'") {
       // Char
-      Ok(Expr::Num { val: Num::U24(self.parse_quoted_char()? as u32 & 0x00ff_ffff) })
+      Ok(Expr::Num { val: Num::U24(self.parse_char_literal()? as u32 & 0x00ff_ffff) })
     } else if self.starts_with("$") {
       // Unscoped var
       self.advance_one();
@@ -131,6 +138,37 @@ impl<'a> PyParser<'a> {
       // Era
       self.advance_one();
       Ok(Expr::Era)
+    } else if self.starts_with_unary_neg() {
+      // Unary negation
+      if let Some(val) = self.parse_neg_number()? {
+        return Ok(Expr::Num { val });
+      }
+      // Like `Term::negate`, so that negating a float doesn't mix number types.
+      match self.call_or_postfix(inline)? {
+        Expr::Num { val: Num::I24(val) } if val != -0x00800000 => Ok(Expr::Num { val: Num::I24(-val) }),
+        Expr::Num { val: Num::F24(val) } => Ok(Expr::Num { val: Num::F24(-val) }),
+        expr => {
+          let zero = if expr.is_float() { Num::F24(0.0) } else { Num::U24(0) };
+          Ok(Expr::Opr { op: Op::SUB, lhs: Box::new(Expr::Num { val: zero }), rhs: Box::new(expr) })
+        }
+      }
+    } else if self.starts_with("~") {
+      // Bitwise not
+      let ini_idx = *self.index();
+      self.advance_one();
+      let expr = self.call_or_postfix(inline)?;
+      let end_idx = *self.index();
+      match expr {
+        Expr::Num { val: Num::U24(val) } => Ok(Expr::Num { val: Num::U24(!val & 0xFFFFFF) }),
+        Expr::Num { val: Num::I24(val) } => Ok(Expr::Num { val: Num::I24(!val) }),
+        Expr::Num { val: Num::F24(_) } => {
+          self.with_ctx(Err("Bitwise not is only defined for integers."), ini_idx..end_idx)
+        }
+        expr => {
+          let all_bits = Box::new(Expr::Num { val: Num::U24(0xFFFFFF) });
+          Ok(Expr::Opr { op: Op::XOR, lhs: all_bits, rhs: Box::new(expr) })
+        }
+      }
     } else if let Some(c) = self.peek_one() {
       if is_num_char(c) {
         // Number
@@ -1143,6 +1181,17 @@ impl<'a> PyParser<'a> {
   }
 }
 
+impl Expr {
+  /// Like `Term::is_float`.
+  fn is_float(&self) -> bool {
+    match self {
+      Expr::Num { val } => matches!(val, Num::F24(_)),
+      Expr::Opr { op, lhs, rhs } => !op.is_comparison() && (lhs.is_float() || rhs.is_float()),
+      _ => false,
+    }
+  }
+}
+
 impl Op {
   fn precedence(&self) -> usize {
     match self {
@@ -34,8 +34,8 @@ impl Definition {
       }
     };
 
-    let rule =
-      fun::Rule { pats: self.params.into_iter().map(|param| fun::Pattern::Var(Some(param))).collect(), body };
+    let pats = self.params.into_iter().map(|param| fun::Pattern::Var(Some(param))).collect();
+    let rule = fun::Rule { pats, body, guard: None };
 
     let def = fun::Definition::new(self.name, vec![rule], self.source);
     Ok(def)
@@ -1,5 +1,11 @@
 use crate::{
-  fun::{book_to_hvm, net_to_term::net_to_term, term_to_net::Labels, Book, Ctx, Term},
+  fun::{
+    book_to_hvm,
+    net_to_term::{net_to_term, net_to_term_with_names, NameGen, VarNaming},
+    term_to_net::Labels,
+    transform::{definition_pruning::MatchedCtrs, inline_defs::INLINE_MAX_SIZE},
+    Book, Ctx, Term,
+  },
   hvm::{
     add_recursive_priority::add_recursive_priority,
     check_net_size::{check_net_sizes, MAX_NET_SIZE_CUDA},
@@ -12,6 +18,7 @@ use crate::{
 };
 use diagnostics::{Diagnostics, DiagnosticsConfig, ERR_INDENT_SIZE};
 use net::hvm_to_net::hvm_to_net;
+use std::{cell::RefCell, rc::Rc};
 
 pub mod diagnostics;
 // `Name` triggers this warning, but it's safe because we're not using its internal mutability.
@@ -23,7 +30,7 @@ pub mod imports;
 pub mod net;
 mod utils;
 
-pub use fun::load_book::{load_file_to_book, load_to_book};
+pub use fun::load_book::{load_file_to_book, load_to_book, load_to_book_with_templates};
 
 pub const ENTRY_POINT: &str = "main";
 pub const HVM1_ENTRY_POINT: &str = "Main";
@@ -47,7 +54,7 @@ pub fn compile_book(
 ) -> Result<CompileResult, Diagnostics> {
   let mut diagnostics = desugar_book(book, opts.clone(), diagnostics_cfg, args)?;
 
-  let (mut hvm_book, labels) = book_to_hvm(book, &mut diagnostics)?;
+  let (mut hvm_book, labels, _) = book_to_hvm(book, &mut diagnostics)?;
 
   if opts.eta {
     hvm_book.defs.values_mut().for_each(eta_reduce_hvm_net);
@@ -81,87 +88,279 @@ pub fn compile_book(
   Ok(CompileResult { hvm_book, labels, diagnostics })
 }
 
+impl Book {
+  /// Compiles a copy of the book and shows the resulting nets in the textual HVM format,
+  /// which can be saved to a `.hvm` file and run with the standalone `hvm` binary.
+  ///
+  /// The warnings are discarded, use [`compile_book`] and [`CompileResult::to_hvm_text`] to get them.
+  pub fn to_hvm_text(
+    &self,
+    opts: CompileOpts,
+    diagnostics_cfg: DiagnosticsConfig,
+  ) -> Result<String, Diagnostics> {
+    let res = compile_book(&mut self.clone(), opts, diagnostics_cfg, None)?;
+    Ok(res.to_hvm_text())
+  }
+
+  /// Reads a book in the textual HVM format, reading back each net as a definition with a single rule.
+  ///
+  /// This is best-effort, since a net can be read back as many different terms:
+  /// - The definitions are left in their compiled form, so pattern matching, constructors and
+  ///   the other syntax sugar show up as the lambda-encoded terms they were compiled to.
+  /// - Variables get new names, and since the labels are not known, labelled
+  ///   duplications and superpositions get numeric labels.
+  /// - Duplications are placed right before the lambda body where their variables are used,
+  ///   which may not be where they were originally.
+  /// - Nets that are not valid terms, like the ones with vicious circles, are reported as errors.
+  pub fn from_hvm_text(text: &str) -> Result<Book, Diagnostics> {
+    let hvm_book = ::hvm::ast::CoreParser::new(text).parse_book()?;
+    let mut book = Book::default();
+    let mut diags = Diagnostics::default();
+    diags.start_pass();
+    for (name, net) in hvm_book.defs.iter() {
+      let name = fun::Name::new(name.as_str());
+      let body = net_to_term(&hvm_to_net(net), &book, &Labels::default(), true, &mut diags);
+      let rule = fun::Rule { pats: vec![], body, guard: None };
+      book.defs.insert(name.clone(), fun::Definition::new(name, vec![rule], fun::Source::Imported));
+    }
+    diags.fatal(())?;
+    if book.defs.contains_key(&fun::Name::new(ENTRY_POINT)) {
+      book.entrypoint = Some(fun::Name::new(ENTRY_POINT));
+    }
+    Ok(book)
+  }
+}
+
 pub fn desugar_book(
   book: &mut Book,
   opts: CompileOpts,
   diagnostics_cfg: DiagnosticsConfig,
   args: Option<Vec<Term>>,
 ) -> Result<Diagnostics, Diagnostics> {
-  let mut ctx = Ctx::new(book, diagnostics_cfg);
+  desugar_book_with_trace(book, opts, diagnostics_cfg, args, None)
+}
 
-  ctx.check_shared_names();
+/// The state of the book after each stage of [`desugar_book`], for debugging the compiler.
+#[derive(Debug, Default, Clone)]
+pub struct CompileTrace {
+  /// The name of each stage and the book right after it, in the order they ran.
+  pub stages: Vec<(String, Book)>,
+}
 
-  ctx.set_entrypoint();
+/// Like [`desugar_book`], but if `trace` is given, records a copy of the book after each stage.
+///
+/// Stages that are disabled by the compile options are not recorded.
+pub fn desugar_book_with_trace(
+  book: &mut Book,
+  opts: CompileOpts,
+  diagnostics_cfg: DiagnosticsConfig,
+  args: Option<Vec<Term>>,
+  trace: Option<&mut CompileTrace>,
+) -> Result<Diagnostics, Diagnostics> {
+  Pipeline::new(&opts, args).run(book, diagnostics_cfg, trace)
+}
 
-  ctx.book.encode_adts(opts.adt_encoding);
+/// A named step of a [`Pipeline`].
+pub struct Pass {
+  pub name: String,
+  /// Whether the pass only checks the book without changing it.
+  /// Checks are not recorded in a [`CompileTrace`].
+  pub is_check: bool,
+  pub run: PassFn,
+}
 
-  ctx.fix_match_defs()?;
+pub type PassFn = Box<dyn FnMut(&mut Ctx) -> Result<(), Diagnostics>>;
 
-  ctx.apply_args(args)?;
+/// What the function of a [`Pass`] can return, so that passes that can't fail don't need to return `Ok(())`.
+pub trait PassResult {
+  fn into_result(self) -> Result<(), Diagnostics>;
+}
 
-  ctx.desugar_open()?;
+impl PassResult for () {
+  fn into_result(self) -> Result<(), Diagnostics> {
+    Ok(())
+  }
+}
 
-  ctx.book.encode_builtins();
+impl PassResult for Result<(), Diagnostics> {
+  fn into_result(self) -> Result<(), Diagnostics> {
+    self
+  }
+}
 
-  ctx.resolve_refs()?;
+impl Pass {
+  pub fn new<R: PassResult>(name: impl Into<String>, mut run: impl FnMut(&mut Ctx) -> R + 'static) -> Self {
+    Self { name: name.into(), is_check: false, run: Box::new(move |ctx| run(ctx).into_result()) }
+  }
 
-  ctx.desugar_match_defs()?;
+  pub fn check<R: PassResult>(name: impl Into<String>, mut run: impl FnMut(&mut Ctx) -> R + 'static) -> Self {
+    Self { name: name.into(), is_check: true, run: Box::new(move |ctx| run(ctx).into_result()) }
+  }
+}
 
-  ctx.fix_match_terms()?;
+/// The passes that turn a parsed book into one ready to be compiled to nets, in the order they run.
+///
+/// The default pipeline is the one used by [`desugar_book`]. Passes can be removed, reordered
+/// or added before running it, but most of them expect the ones before to have already run.
+pub struct Pipeline {
+  pub passes: Vec<Pass>,
+}
 
-  ctx.book.lift_local_defs();
+impl Pipeline {
+  /// The pipeline used by [`desugar_book`] with the given options and arguments for the entrypoint.
+  ///
+  /// Passes disabled by the options are not included.
+  pub fn new(opts: &CompileOpts, args: Option<Vec<Term>>) -> Self {
+    let mut passes = vec![];
+    let adt_encoding = opts.adt_encoding;
+    let prune = opts.prune;
+    let mut args = Some(args);
+    // Collected before the matches are encoded, so that pruning can keep the constructors they match on.
+    let matched_ctrs = Rc::new(RefCell::new(MatchedCtrs::new()));
+
+    passes.push(Pass::check("check_shared_names", |ctx| ctx.check_shared_names()));
+    passes.push(Pass::check("set_entrypoint", |ctx| ctx.set_entrypoint()));
+    passes.push(Pass::new("encode_adts", move |ctx| ctx.book.encode_adts(adt_encoding)));
+    passes.push(Pass::new("generate_field_accessors", |ctx| ctx.book.generate_field_accessors()));
+    passes.push(Pass::new("fix_match_defs", |ctx| ctx.fix_match_defs()));
+    passes.push(Pass::check("check_unreachable_rules", |ctx| ctx.check_unreachable_rules()));
+    passes.push(Pass::new("desugar_guards", |ctx| ctx.desugar_guards()));
+    passes.push(Pass::new("apply_args", move |ctx| ctx.apply_args(args.take().flatten())));
+    passes.push(Pass::new("desugar_open", |ctx| ctx.desugar_open()));
+    passes.push(Pass::new("encode_builtins", |ctx| ctx.book.encode_builtins()));
+    passes.push(Pass::new("resolve_refs", |ctx| ctx.resolve_refs()));
+    passes.push(Pass::check("check_unused_vars", |ctx| ctx.check_unused_vars()));
+    passes.push(Pass::check("check_dup_vars", |ctx| ctx.check_dup_vars()));
+    passes.push(Pass::check("check_shadowed_vars", |ctx| ctx.check_shadowed_vars()));
+    passes.push(Pass::new("desugar_struct_eq", |ctx| ctx.desugar_struct_eq()));
+    passes.push(Pass::new("desugar_match_defs", |ctx| ctx.desugar_match_defs()));
+    passes.push(Pass::new("fix_match_terms", |ctx| ctx.fix_match_terms()));
+    passes.push(Pass::check("check_num_operands", |ctx| ctx.check_num_operands()));
+    passes.push(Pass::new("lift_local_defs", |ctx| ctx.book.lift_local_defs()));
+    passes.push(Pass::new("desugar_pow", |ctx| ctx.book.desugar_pow()));
+    passes.push(Pass::new("desugar_bend", |ctx| ctx.desugar_bend()));
+    passes.push(Pass::new("desugar_fold", |ctx| ctx.desugar_fold()));
+    passes.push(Pass::new("desugar_with_blocks", |ctx| ctx.desugar_with_blocks()));
+    passes.push(Pass::check("check_unbound_vars", |ctx| ctx.check_unbound_vars()));
+
+    // Auto match linearization
+    passes.push(Pass::new("make_var_names_unique", |ctx| ctx.book.make_var_names_unique()));
+    passes.push(Pass::new("desugar_use", |ctx| ctx.book.desugar_use()));
+    passes.push(Pass::new("annihilate_dups", |ctx| ctx.book.annihilate_dups()));
+    match opts.linearize_matches {
+      OptLevel::Disabled => (),
+      OptLevel::Alt => {
+        passes.push(Pass::new("linearize_match_binds", |ctx| ctx.book.linearize_match_binds()))
+      }
+      OptLevel::Enabled => passes.push(Pass::new("linearize_matches", |ctx| ctx.book.linearize_matches())),
+    }
+    // Manual match linearization
+    passes.push(Pass::new("linearize_match_with", |ctx| ctx.book.linearize_match_with()));
 
-  ctx.desugar_bend()?;
-  ctx.desugar_fold()?;
-  ctx.desugar_with_blocks()?;
+    let matched = matched_ctrs.clone();
+    passes.push(Pass::check("find_matched_ctrs", move |ctx| *matched.borrow_mut() = ctx.book.matched_ctrs()));
+    passes.push(Pass::new("encode_matches", move |ctx| ctx.book.encode_matches(adt_encoding)));
 
-  ctx.check_unbound_vars()?;
+    // sanity check
+    passes.push(Pass::check("check_unbound_vars", |ctx| ctx.check_unbound_vars()));
 
-  // Auto match linearization
-  ctx.book.make_var_names_unique();
-  ctx.book.desugar_use();
-  match opts.linearize_matches {
-    OptLevel::Disabled => (),
-    OptLevel::Alt => ctx.book.linearize_match_binds(),
-    OptLevel::Enabled => ctx.book.linearize_matches(),
-  }
-  // Manual match linearization
-  ctx.book.linearize_match_with();
+    passes.push(Pass::new("make_var_names_unique", |ctx| ctx.book.make_var_names_unique()));
+    passes.push(Pass::new("desugar_use", |ctx| ctx.book.desugar_use()));
+
+    // Before linearizing, so that the shared values get duplicated.
+    if opts.cse {
+      passes.push(Pass::new("eliminate_common_subexprs", |ctx| ctx.book.eliminate_common_subexprs()));
+    }
+
+    passes.push(Pass::new("make_var_names_unique", |ctx| ctx.book.make_var_names_unique()));
+    passes.push(Pass::new("linearize_vars", |ctx| ctx.book.linearize_vars()));
+
+    // sanity check
+    passes.push(Pass::check("check_unbound_vars", |ctx| ctx.check_unbound_vars()));
+
+    if opts.term_eta {
+      passes.push(Pass::new("eta_reduction", |ctx| ctx.book.eta_reduction()));
+    }
+
+    // sanity check, then pruning
+    let prune_passes = move || {
+      [
+        Pass::check("check_unbound_refs", |ctx| ctx.check_unbound_refs()),
+        Pass::new("prune", move |ctx| ctx.prune_with_matches(prune, &matched_ctrs.borrow())),
+      ]
+    };
 
-  ctx.book.encode_matches(opts.adt_encoding);
+    if opts.inline_defs {
+      // Pruned before inlining, so definitions only used where they get inlined aren't reported as unused.
+      passes.extend(prune_passes.clone()());
+      passes.push(Pass::new("inline_defs", |ctx| ctx.book.inline_defs(INLINE_MAX_SIZE)));
+    }
+
+    if opts.fold_constants {
+      passes.push(Pass::new("fold_constants", |ctx| ctx.book.fold_constants()));
+    }
+
+    if opts.float_combinators {
+      passes.push(Pass::new("float_combinators", |ctx| ctx.book.float_combinators(MAX_NET_SIZE_CUDA)));
+    }
 
-  // sanity check
-  ctx.check_unbound_vars()?;
+    if !opts.inline_defs {
+      passes.extend(prune_passes());
+    }
 
-  ctx.book.make_var_names_unique();
-  ctx.book.desugar_use();
+    // Optimizing passes
+    if opts.merge {
+      passes.push(Pass::new("merge_definitions", |ctx| ctx.book.merge_definitions()));
+    }
 
-  ctx.book.make_var_names_unique();
-  ctx.book.linearize_vars();
+    passes.push(Pass::new("expand_main", |ctx| ctx.book.expand_main()));
 
-  // sanity check
-  ctx.check_unbound_vars()?;
+    passes.push(Pass::new("make_var_names_unique", |ctx| ctx.book.make_var_names_unique()));
 
-  if opts.float_combinators {
-    ctx.book.float_combinators(MAX_NET_SIZE_CUDA);
+    Self { passes }
   }
-  // sanity check
-  ctx.check_unbound_refs()?;
 
-  // Optimizing passes
-  ctx.prune(opts.prune);
-  if opts.merge {
-    ctx.book.merge_definitions();
+  /// Removes all the passes with the given name.
+  pub fn remove(&mut self, name: &str) {
+    self.passes.retain(|pass| pass.name != name);
   }
 
-  ctx.book.expand_main();
+  /// The position of the first pass with the given name.
+  pub fn position(&self, name: &str) -> Option<usize> {
+    self.passes.iter().position(|pass| pass.name == name)
+  }
+
+  /// Runs the passes in order, stopping at the first one that returns an error.
+  ///
+  /// If `trace` is given, records a copy of the book after each pass that isn't a check.
+  pub fn run(
+    self,
+    book: &mut Book,
+    diagnostics_cfg: DiagnosticsConfig,
+    mut trace: Option<&mut CompileTrace>,
+  ) -> Result<Diagnostics, Diagnostics> {
+    let mut ctx = Ctx::new(book, diagnostics_cfg);
+
+    for mut pass in self.passes {
+      (pass.run)(&mut ctx)?;
+      if let Some(trace) = trace.as_deref_mut() {
+        if !pass.is_check {
+          trace.stages.push((pass.name, ctx.book.clone()));
+        }
+      }
+    }
 
-  ctx.book.make_var_names_unique();
+    if !ctx.info.has_errors() {
+      Ok(ctx.info)
+    } else {
+      Err(ctx.info)
+    }
+  }
+}
 
-  if !ctx.info.has_errors() {
-    Ok(ctx.info)
-  } else {
-    Err(ctx.info)
+impl Default for Pipeline {
+  fn default() -> Self {
+    Self::new(&CompileOpts::default(), None)
   }
 }
 
@@ -183,27 +382,32 @@ pub fn run_book(
 
   let out = run_hvm(&core_book, cmd, &run_opts)?;
   let (net, stats) = parse_hvm_output(&out)?;
-  let (term, diags) =
-    readback_hvm_net(&net, &book, &labels, run_opts.linear_readback, compile_opts.adt_encoding);
+  let (term, diags) = readback_hvm_net(&net, &book, &labels, &run_opts, compile_opts.adt_encoding);
 
   Ok(Some((term, stats, diags)))
 }
 
+/// Reads back the result of running a book, using the run options that change how the term is shown.
+///
+/// Unless `raw_readback` is set, lists and strings are converted back to list and string literals.
 pub fn readback_hvm_net(
   net: &::hvm::ast::Net,
   book: &Book,
   labels: &Labels,
-  linear: bool,
+  run_opts: &RunOpts,
   adt_encoding: AdtEncoding,
 ) -> (Term, Diagnostics) {
   let mut diags = Diagnostics::default();
   let net = hvm_to_net(net);
-  let mut term = net_to_term(&net, book, labels, linear, &mut diags);
+  let namegen = NameGen::with_naming(run_opts.var_naming.clone());
+  let mut term = net_to_term_with_names(&net, book, labels, run_opts.linear_readback, namegen, &mut diags);
   #[allow(clippy::mutable_key_type)] // Safe to allow, we know how `Name` works.
   let recursive_defs = book.recursive_defs();
   term.expand_generated(book, &recursive_defs);
-  term.resugar_strings(adt_encoding);
-  term.resugar_lists(adt_encoding);
+  if !run_opts.raw_readback {
+    term.resugar_strings(adt_encoding);
+    term.resugar_lists(adt_encoding);
+  }
   (term, diags)
 }
 
@@ -301,13 +505,22 @@ fn filter_hvm_output(
 #[derive(Clone, Debug)]
 pub struct RunOpts {
   pub linear_readback: bool,
+  pub raw_readback: bool,
   pub pretty: bool,
   pub hvm_path: String,
+  /// How the variables of the result are named.
+  pub var_naming: VarNaming,
 }
 
 impl Default for RunOpts {
   fn default() -> Self {
-    RunOpts { linear_readback: false, pretty: false, hvm_path: "hvm".to_string() }
+    RunOpts {
+      linear_readback: false,
+      raw_readback: false,
+      pretty: false,
+      hvm_path: "hvm".to_string(),
+      var_naming: VarNaming::default(),
+    }
   }
 }
 
@@ -344,6 +557,9 @@ pub struct CompileOpts {
   /// Enables [hvm::eta_reduce].
   pub eta: bool,
 
+  /// Enables [fun::transform::eta_reduction].
+  pub term_eta: bool,
+
   /// Enables [fun::transform::definition_pruning] and [hvm::prune].
   pub prune: bool,
 
@@ -353,6 +569,15 @@ pub struct CompileOpts {
   /// Enables [fun::transform::float_combinators].
   pub float_combinators: bool,
 
+  /// Enables [fun::transform::fold_constants].
+  pub fold_constants: bool,
+
+  /// Enables [fun::transform::inline_defs].
+  pub inline_defs: bool,
+
+  /// Enables [fun::transform::common_subexpr_elimination].
+  pub cse: bool,
+
   /// Enables [fun::transform::definition_merge]
   pub merge: bool,
 
@@ -373,8 +598,12 @@ impl CompileOpts {
     Self {
       target_architecture: self.target_architecture,
       eta: true,
+      term_eta: true,
       prune: true,
       float_combinators: true,
+      fold_constants: true,
+      inline_defs: true,
+      cse: true,
       merge: true,
       inline: true,
       linearize_matches: OptLevel::Enabled,
@@ -389,9 +618,13 @@ impl CompileOpts {
     Self {
       target_architecture: self.target_architecture,
       eta: false,
+      term_eta: false,
       prune: false,
       linearize_matches: OptLevel::Disabled,
       float_combinators: false,
+      fold_constants: false,
+      inline_defs: false,
+      cse: false,
       merge: false,
       inline: false,
       check_net_size: self.check_net_size,
@@ -420,9 +653,13 @@ impl Default for CompileOpts {
     Self {
       target_architecture: CompilerTarget::Unknown,
       eta: true,
+      term_eta: false,
       prune: false,
       linearize_matches: OptLevel::Enabled,
       float_combinators: true,
+      fold_constants: false,
+      inline_defs: false,
+      cse: false,
       merge: false,
       inline: false,
       check_net_size: true,
@@ -452,9 +689,141 @@ pub struct CompileResult {
   pub labels: Labels,
 }
 
+impl CompileResult {
+  /// Shows the compiled nets in the textual HVM format, one `@name = net` definition each.
+  pub fn to_hvm_text(&self) -> String {
+    hvm_book_show_pretty(&self.hvm_book)
+  }
+}
+
 fn maybe_grow<R, F>(f: F) -> R
 where
   F: FnOnce() -> R,
 {
   stacker::maybe_grow(1024 * 32, 1024 * 1024, f)
 }
+
+#[test]
+fn desugar_trace_stages() {
+  use fun::load_book::do_parse_book_default;
+  let code = "main = (+ (* 2 3) 1)";
+  let mut book = do_parse_book_default(code, std::path::Path::new("trace")).unwrap();
+  let opts = CompileOpts { fold_constants: true, ..CompileOpts::default() };
+  let mut trace = CompileTrace::default();
+  desugar_book_with_trace(&mut book, opts, DiagnosticsConfig::default(), None, Some(&mut trace)).unwrap();
+
+  let stages = trace.stages.iter().map(|(stage, _)| stage.as_str()).collect::<Vec<_>>();
+  let pos = |stage: &str| stages.iter().position(|s| *s == stage).unwrap();
+  assert_eq!(stages.first(), Some(&"encode_adts"));
+  assert!(pos("resolve_refs") < pos("desugar_match_defs"));
+  assert!(pos("desugar_match_defs") < pos("encode_matches"));
+  assert!(pos("encode_matches") < pos("linearize_vars"));
+  assert!(pos("linearize_vars") < pos("fold_constants"));
+  // Without inlining, pruning keeps its place after the other optimizations.
+  assert!(pos("fold_constants") < pos("prune"));
+  // Disabled passes are not recorded.
+  assert!(!stages.contains(&"inline_defs"));
+
+  // Each stage has the book as it was right after it.
+  let main = |book: &Book| book.defs[&fun::Name::new("main")].rule().body.to_string();
+  assert_eq!(main(&trace.stages[pos("fold_constants") - 1].1), "(+ (* 2 3) 1)");
+  assert_eq!(main(&trace.stages[pos("fold_constants")].1), "7");
+  assert_eq!(main(trace.stages.last().map(|(_, book)| book).unwrap()), main(&book));
+}
+
+#[test]
+fn custom_pipeline() {
+  use fun::load_book::do_parse_book_default;
+  let code = "main = (+ (* 2 3) 1)";
+  let opts = CompileOpts { fold_constants: true, ..CompileOpts::default() };
+  let main = |book: &Book| book.defs[&fun::Name::new("main")].rule().body.to_string();
+
+  let mut book = do_parse_book_default(code, std::path::Path::new("pipeline")).unwrap();
+  Pipeline::new(&opts, None).run(&mut book, DiagnosticsConfig::default(), None).unwrap();
+  assert_eq!(main(&book), "7");
+
+  // Without constant folding, the operations are kept.
+  let mut book = do_parse_book_default(code, std::path::Path::new("pipeline")).unwrap();
+  let mut pipeline = Pipeline::new(&opts, None);
+  pipeline.remove("fold_constants");
+  assert_eq!(pipeline.position("fold_constants"), None);
+  // And custom passes can be added.
+  let at = pipeline.position("expand_main").unwrap();
+  pipeline.passes.insert(
+    at,
+    Pass::new("double_main", |ctx| {
+      let main = &mut ctx.book.defs.get_mut(&fun::Name::new("main")).unwrap().rules[0].body;
+      *main = Term::Oper {
+        opr: fun::Op::MUL,
+        fst: Box::new(std::mem::take(main)),
+        snd: Box::new(Term::Num { val: fun::Num::U24(2) }),
+      };
+    }),
+  );
+  let mut trace = CompileTrace::default();
+  pipeline.run(&mut book, DiagnosticsConfig::default(), Some(&mut trace)).unwrap();
+  assert_eq!(main(&book), "(* (+ (* 2 3) 1) 2)");
+  assert!(trace.stages.iter().any(|(stage, _)| stage == "double_main"));
+}
+
+#[test]
+fn compile_with_warnings() {
+  use diagnostics::Severity;
+  use fun::load_book::do_parse_book_default;
+  let code = "unused = 1\nmain = (/ 1 0)";
+  let mut cfg = DiagnosticsConfig { unused_definition: Severity::Info, ..DiagnosticsConfig::default() };
+
+  // Warnings are returned with the compiled book instead of stopping the compilation.
+  let mut book = do_parse_book_default(code, std::path::Path::new("warnings")).unwrap();
+  let res = compile_book(&mut book, CompileOpts::default(), cfg, None).unwrap();
+  assert!(res.hvm_book.defs.contains_key("main"));
+  assert!(!res.diagnostics.has_errors());
+  let mut severities =
+    res.diagnostics.diagnostics.values().flatten().map(|diag| diag.severity()).collect::<Vec<_>>();
+  severities.sort();
+  assert_eq!(severities, [Severity::Info, Severity::Warning]);
+  let shown = res.diagnostics.to_string();
+  assert!(shown.find("Info:").unwrap() < shown.find("Warnings:").unwrap());
+
+  // Unless they're denied.
+  cfg.division_by_zero = Severity::Error;
+  let mut book = do_parse_book_default(code, std::path::Path::new("warnings")).unwrap();
+  let Err(diags) = compile_book(&mut book, CompileOpts::default(), cfg, None) else { panic!() };
+  assert!(diags.has_errors());
+}
+
+#[test]
+fn hvm_text_parses_back() {
+  use fun::load_book::do_parse_book_default;
+  let code =
+    "type N = (S pred) | Z\nadd (N/S a) b = (N/S (add a b))\nadd N/Z b = b\nmain = (add (N/S N/Z) N/Z)";
+  let book = do_parse_book_default(code, std::path::Path::new("text")).unwrap();
+  let text = book.to_hvm_text(CompileOpts::default(), DiagnosticsConfig::default()).unwrap();
+  assert!(text.starts_with("@") && text.contains("@main = "));
+
+  let parsed = ::hvm::ast::CoreParser::new(&text).parse_book().unwrap();
+  let res =
+    compile_book(&mut book.clone(), CompileOpts::default(), DiagnosticsConfig::default(), None).unwrap();
+  assert_eq!(parsed.defs.keys().collect::<Vec<_>>(), res.hvm_book.defs.keys().collect::<Vec<_>>());
+  assert_eq!(hvm_book_show_pretty(&parsed), text);
+}
+
+#[test]
+fn hvm_text_round_trip() {
+  use fun::load_book::do_parse_book_default;
+  let code = "id = @x x\ntwice = @f @x (f (f x))\nmain = (twice id (1, 2))";
+  let mut book = do_parse_book_default(code, std::path::Path::new("round_trip")).unwrap();
+  let text = book.to_hvm_text(CompileOpts::default(), DiagnosticsConfig::default()).unwrap();
+  let read = Book::from_hvm_text(&text).unwrap();
+
+  desugar_book(&mut book, CompileOpts::default(), DiagnosticsConfig::default(), None).unwrap();
+  assert_eq!(read.entrypoint, Some(fun::Name::new(ENTRY_POINT)));
+  for name in ["id", "main"].map(fun::Name::new) {
+    let (def, body) = (&book.defs[&name].rule().body, &read.defs[&name].rule().body);
+    assert!(def.alpha_eq(body), "{name}: {def} != {body}");
+  }
+  // The duplication in `twice` is moved, but it's still the same net.
+  assert_eq!(read.defs[&fun::Name::new("twice")].rule().body.to_string(), "λa λb let {c d} = a; (c (d b))");
+  assert_eq!(read.to_hvm_text(CompileOpts::default(), DiagnosticsConfig::default()).unwrap(), text);
+  assert!(Book::from_hvm_text("@main = (").is_err());
+}
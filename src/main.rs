@@ -2,7 +2,6 @@ use bend::{
   check_book, compile_book, desugar_book,
   diagnostics::{Diagnostics, DiagnosticsConfig, Severity},
   fun::{Book, Name},
-  hvm::hvm_book_show_pretty,
   imports::DefaultLoader,
   load_file_to_book, run_book, AdtEncoding, CompileOpts, CompilerTarget, OptLevel, RunOpts,
 };
@@ -132,6 +131,9 @@ struct CliRunOpts {
   #[arg(short = 'l', help = "Linear readback (show explicit dups)")]
   linear: bool,
 
+  #[arg(short = 'r', long = "raw", help = "Raw readback (don't show lists and strings as literals)")]
+  raw: bool,
+
   #[arg(short = 's', long = "stats", help = "Shows runtime stats and rewrite counts")]
   print_stats: bool,
 }
@@ -165,6 +167,15 @@ struct CliWarnOpts {
     help = "Allow the specified compilation warning",
   )]
   pub allows: Vec<WarningArgs>,
+
+  #[arg(
+    short = 'I',
+    long = "info",
+    value_delimiter = ' ',
+    action = clap::ArgAction::Append,
+    help = "Show the specified compilation warning as information",
+  )]
+  pub infos: Vec<WarningArgs>,
 }
 
 #[derive(clap::ValueEnum, Clone, Debug)]
@@ -173,6 +184,8 @@ pub enum OptArgs {
   NoAll,
   Eta,
   NoEta,
+  TermEta,
+  NoTermEta,
   Prune,
   NoPrune,
   LinearizeMatches,
@@ -180,6 +193,12 @@ pub enum OptArgs {
   NoLinearizeMatches,
   FloatCombinators,
   NoFloatCombinators,
+  FoldConstants,
+  NoFoldConstants,
+  InlineDefs,
+  NoInlineDefs,
+  Cse,
+  NoCse,
   Merge,
   NoMerge,
   Inline,
@@ -200,10 +219,18 @@ fn compile_opts_from_cli(args: &Vec<OptArgs>, compiler_target: CompilerTarget) -
       NoAll => opts = opts.set_no_all(),
       Eta => opts.eta = true,
       NoEta => opts.eta = false,
+      TermEta => opts.term_eta = true,
+      NoTermEta => opts.term_eta = false,
       Prune => opts.prune = true,
       NoPrune => opts.prune = false,
       FloatCombinators => opts.float_combinators = true,
       NoFloatCombinators => opts.float_combinators = false,
+      FoldConstants => opts.fold_constants = true,
+      NoFoldConstants => opts.fold_constants = false,
+      InlineDefs => opts.inline_defs = true,
+      NoInlineDefs => opts.inline_defs = false,
+      Cse => opts.cse = true,
+      NoCse => opts.cse = false,
       Merge => opts.merge = true,
       NoMerge => opts.merge = false,
       Inline => opts.inline = true,
@@ -230,6 +257,10 @@ pub enum WarningArgs {
   RedundantMatch,
   UnreachableMatch,
   UnusedDefinition,
+  UnusedVariable,
+  ShadowedVariable,
+  DupUsage,
+  DivisionByZero,
   RepeatedBind,
   RecursionCycle,
   ImportShadow,
@@ -314,13 +345,13 @@ fn execute_cli_mode(mut cli: Cli) -> Result<(), Diagnostics> {
       let compile_res = compile_book(&mut book, opts, diagnostics_cfg, None)?;
 
       eprint!("{}", compile_res.diagnostics);
-      println!("{}", hvm_book_show_pretty(&compile_res.hvm_book));
+      println!("{}", compile_res.to_hvm_text());
     }
 
     Mode::RunC(RunArgs { pretty, run_opts, comp_opts, warn_opts, path, arguments })
     | Mode::RunCu(RunArgs { pretty, run_opts, comp_opts, warn_opts, path, arguments })
     | Mode::RunRs(RunArgs { pretty, run_opts, comp_opts, warn_opts, path, arguments }) => {
-      let CliRunOpts { linear, print_stats } = run_opts;
+      let CliRunOpts { linear, raw, print_stats } = run_opts;
 
       let diagnostics_cfg =
         set_warning_cfg_from_cli(DiagnosticsConfig::new(Severity::Allow, arg_verbose), warn_opts);
@@ -329,7 +360,13 @@ fn execute_cli_mode(mut cli: Cli) -> Result<(), Diagnostics> {
 
       compile_opts.check_for_strict();
 
-      let run_opts = RunOpts { linear_readback: linear, pretty, hvm_path: hvm_bin };
+      let run_opts = RunOpts {
+        linear_readback: linear,
+        raw_readback: raw,
+        pretty,
+        hvm_path: hvm_bin,
+        var_naming: Default::default(),
+      };
 
       let book = load_book(&path, diagnostics_cfg)?;
       if let Some((term, stats, diags)) =
@@ -356,7 +393,7 @@ fn execute_cli_mode(mut cli: Cli) -> Result<(), Diagnostics> {
       let compile_res = compile_book(&mut book, opts, diagnostics_cfg, None)?;
 
       let out_path = ".out.hvm";
-      std::fs::write(out_path, hvm_book_show_pretty(&compile_res.hvm_book)).map_err(|x| x.to_string())?;
+      std::fs::write(out_path, compile_res.to_hvm_text()).map_err(|x| x.to_string())?;
 
       let gen_fn = |out_path: &str| {
         let mut process = std::process::Command::new(hvm_bin);
@@ -405,6 +442,10 @@ fn set_warning_cfg_from_cli(mut cfg: DiagnosticsConfig, warn_opts: CliWarnOpts)
         cfg.redundant_match = severity;
         cfg.unreachable_match = severity;
         cfg.unused_definition = severity;
+        cfg.unused_variable = severity;
+        cfg.shadowed_variable = severity;
+        cfg.dup_usage = severity;
+        cfg.division_by_zero = severity;
         cfg.repeated_bind = severity;
         cfg.recursion_cycle = severity;
         cfg.import_shadow = severity;
@@ -413,6 +454,10 @@ fn set_warning_cfg_from_cli(mut cfg: DiagnosticsConfig, warn_opts: CliWarnOpts)
       WarningArgs::RedundantMatch => cfg.redundant_match = severity,
       WarningArgs::UnreachableMatch => cfg.unreachable_match = severity,
       WarningArgs::UnusedDefinition => cfg.unused_definition = severity,
+      WarningArgs::UnusedVariable => cfg.unused_variable = severity,
+      WarningArgs::ShadowedVariable => cfg.shadowed_variable = severity,
+      WarningArgs::DupUsage => cfg.dup_usage = severity,
+      WarningArgs::DivisionByZero => cfg.division_by_zero = severity,
       WarningArgs::RepeatedBind => cfg.repeated_bind = severity,
       WarningArgs::RecursionCycle => cfg.recursion_cycle = severity,
       WarningArgs::ImportShadow => cfg.import_shadow = severity,
@@ -429,11 +474,13 @@ fn set_warning_cfg_from_cli(mut cfg: DiagnosticsConfig, warn_opts: CliWarnOpts)
     let mut allows = warn_opts.allows.into_iter();
     let mut warns = warn_opts.warns.into_iter();
     let mut denies = warn_opts.denies.into_iter();
+    let mut infos = warn_opts.infos.into_iter();
     for id in warn_opts_ids {
       match id.as_ref() {
         "allows" => set(&mut cfg, Severity::Allow, allows.next().unwrap()),
         "denies" => set(&mut cfg, Severity::Error, denies.next().unwrap()),
         "warns" => set(&mut cfg, Severity::Warning, warns.next().unwrap()),
+        "infos" => set(&mut cfg, Severity::Info, infos.next().unwrap()),
         _ => unreachable!(),
       }
     }
@@ -1,24 +1,49 @@
 use super::{INet, INode, INodes, NodeId, NodeKind::*, Port, SlotId, ROOT};
 use crate::{
-  fun::Name,
+  fun::{
+    term_to_net::{NameHints, TreePath},
+    Name,
+  },
   net::{CtrKind, NodeKind},
 };
 use hvm::ast::{Net, Tree};
+use std::collections::HashMap;
 
 pub fn hvm_to_net(net: &Net) -> INet {
-  let inodes = hvm_to_inodes(net);
-  inodes_to_inet(&inodes)
+  let (inodes, _) = hvm_to_inodes(net);
+  inodes_to_inet(&inodes).0
 }
 
-fn hvm_to_inodes(net: &Net) -> INodes {
+/// Like [`hvm_to_net`], but also returns the original names of the variables connected
+/// to the aux ports of the nodes, given the places of the net where they're connected.
+pub fn hvm_to_net_with_hints(net: &Net, hints: &NameHints) -> (INet, HashMap<Port, Name>) {
+  let (inodes, paths) = hvm_to_inodes(net);
+  let (inet, node_ids) = inodes_to_inet(&inodes);
+  let mut port_hints = HashMap::new();
+  for (mut path, node) in paths.into_iter().zip(node_ids) {
+    for slot in 1..=2 {
+      path.1.push(slot);
+      if let Some(hint) = hints.get(&path) {
+        port_hints.insert(Port(node, slot), hint.clone());
+      }
+      path.1.pop();
+    }
+  }
+  (inet, port_hints)
+}
+
+/// Returns the inodes of the net and the place in the net of each of them.
+fn hvm_to_inodes(net: &Net) -> (INodes, Vec<TreePath>) {
   let mut inodes = vec![];
+  let mut paths = vec![];
   let mut n_vars = 0;
   let net_root = if let Tree::Var { nam } = &net.root { nam } else { "" };
 
   // If we have a tree attached to the net root, convert that first
   if !matches!(&net.root, Tree::Var { .. }) {
     let mut root = tree_to_inodes(&net.root, "_".to_string(), net_root, &mut n_vars);
-    inodes.append(&mut root);
+    paths.extend(root.1.into_iter().map(|path| (0, path)));
+    inodes.append(&mut root.0);
   }
 
   // Convert all the trees forming active pairs.
@@ -26,11 +51,13 @@ fn hvm_to_inodes(net: &Net) -> INodes {
     // This name cannot appear anywhere in the original net
     let tree_root = format!("%a{i}");
     let mut tree1 = tree_to_inodes(tree1, tree_root.clone(), net_root, &mut n_vars);
-    inodes.append(&mut tree1);
+    paths.extend(tree1.1.into_iter().map(|path| (1 + 2 * i, path)));
+    inodes.append(&mut tree1.0);
     let mut tree2 = tree_to_inodes(tree2, tree_root, net_root, &mut n_vars);
-    inodes.append(&mut tree2);
+    paths.extend(tree2.1.into_iter().map(|path| (2 + 2 * i, path)));
+    inodes.append(&mut tree2.0);
   }
-  inodes
+  (inodes, paths)
 }
 
 fn new_var(n_vars: &mut NodeId) -> String {
@@ -40,11 +67,18 @@ fn new_var(n_vars: &mut NodeId) -> String {
   new_var
 }
 
-fn tree_to_inodes(tree: &Tree, tree_root: String, net_root: &str, n_vars: &mut NodeId) -> INodes {
+/// Returns the inodes of the tree and the aux slots to follow from the root of the tree to reach each of them.
+fn tree_to_inodes(
+  tree: &Tree,
+  tree_root: String,
+  net_root: &str,
+  n_vars: &mut NodeId,
+) -> (INodes, Vec<Vec<SlotId>>) {
   fn process_node_subtree<'a>(
     subtree: &'a Tree,
+    path: Vec<SlotId>,
     net_root: &str,
-    subtrees: &mut Vec<(String, &'a Tree)>,
+    subtrees: &mut Vec<(String, &'a Tree, Vec<SlotId>)>,
     n_vars: &mut NodeId,
   ) -> String {
     if let Tree::Var { nam } = subtree {
@@ -55,14 +89,16 @@ fn tree_to_inodes(tree: &Tree, tree_root: String, net_root: &str, n_vars: &mut N
       }
     } else {
       let var = new_var(n_vars);
-      subtrees.push((var.clone(), subtree));
+      subtrees.push((var.clone(), subtree, path));
       var
     }
   }
 
   let mut inodes = vec![];
-  let mut subtrees = vec![(tree_root, tree)];
-  while let Some((subtree_root, subtree)) = subtrees.pop() {
+  let mut paths = vec![];
+  let mut subtrees = vec![(tree_root, tree, vec![])];
+  while let Some((subtree_root, subtree, path)) = subtrees.pop() {
+    let child_path = |slot| path.iter().copied().chain([slot]).collect();
     match subtree {
       Tree::Era => {
         let var = new_var(n_vars);
@@ -70,14 +106,14 @@ fn tree_to_inodes(tree: &Tree, tree_root: String, net_root: &str, n_vars: &mut N
       }
       Tree::Con { fst, snd } => {
         let kind = NodeKind::Ctr(CtrKind::Con(None));
-        let fst = process_node_subtree(fst, net_root, &mut subtrees, n_vars);
-        let snd = process_node_subtree(snd, net_root, &mut subtrees, n_vars);
+        let fst = process_node_subtree(fst, child_path(1), net_root, &mut subtrees, n_vars);
+        let snd = process_node_subtree(snd, child_path(2), net_root, &mut subtrees, n_vars);
         inodes.push(INode { kind, ports: [subtree_root, fst, snd] });
       }
       Tree::Dup { fst, snd } => {
         let kind = NodeKind::Ctr(CtrKind::Dup(0));
-        let fst = process_node_subtree(fst, net_root, &mut subtrees, n_vars);
-        let snd = process_node_subtree(snd, net_root, &mut subtrees, n_vars);
+        let fst = process_node_subtree(fst, child_path(1), net_root, &mut subtrees, n_vars);
+        let snd = process_node_subtree(snd, child_path(2), net_root, &mut subtrees, n_vars);
         inodes.push(INode { kind, ports: [subtree_root, fst, snd] });
       }
       Tree::Var { .. } => unreachable!(),
@@ -93,29 +129,33 @@ fn tree_to_inodes(tree: &Tree, tree_root: String, net_root: &str, n_vars: &mut N
       }
       Tree::Opr { fst, snd } => {
         let kind = NodeKind::Opr;
-        let fst = process_node_subtree(fst, net_root, &mut subtrees, n_vars);
-        let snd = process_node_subtree(snd, net_root, &mut subtrees, n_vars);
+        let fst = process_node_subtree(fst, child_path(1), net_root, &mut subtrees, n_vars);
+        let snd = process_node_subtree(snd, child_path(2), net_root, &mut subtrees, n_vars);
         inodes.push(INode { kind, ports: [subtree_root, fst, snd] });
       }
       Tree::Swi { fst, snd } => {
         let kind = NodeKind::Swi;
-        let fst = process_node_subtree(fst, net_root, &mut subtrees, n_vars);
-        let snd = process_node_subtree(snd, net_root, &mut subtrees, n_vars);
+        let fst = process_node_subtree(fst, child_path(1), net_root, &mut subtrees, n_vars);
+        let snd = process_node_subtree(snd, child_path(2), net_root, &mut subtrees, n_vars);
         inodes.push(INode { kind, ports: [subtree_root, fst, snd] });
       }
     }
+    paths.push(path);
   }
-  inodes
+  (inodes, paths)
 }
 
 // Converts INodes to an INet by linking ports based on names.
-fn inodes_to_inet(inodes: &INodes) -> INet {
+/// Also returns the id of the node created for each inode.
+fn inodes_to_inet(inodes: &INodes) -> (INet, Vec<NodeId>) {
   let mut inet = INet::new();
   // Maps named inode ports to numeric inet ports.
-  let mut name_map = std::collections::HashMap::new();
+  let mut name_map = HashMap::new();
+  let mut node_ids = vec![];
 
   for inode in inodes {
     let node = inet.new_node(inode.kind.clone());
+    node_ids.push(node);
     for (j, name) in inode.ports.iter().enumerate() {
       let p = Port(node, j as SlotId);
       if name == "_" {
@@ -129,5 +169,5 @@ fn inodes_to_inet(inodes: &INodes) -> INet {
     }
   }
 
-  inet
+  (inet, node_ids)
 }
@@ -36,3 +36,19 @@ macro_rules! multi_iterator {
     }
   };
 }
+
+/// The minimum number of single character insertions, deletions and substitutions
+/// needed to turn one string into another.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+  let b = b.chars().collect::<Vec<_>>();
+  let mut prev_row = (0..=b.len()).collect::<Vec<_>>();
+  for (i, a_chr) in a.chars().enumerate() {
+    let mut row = vec![i + 1];
+    for (j, b_chr) in b.iter().enumerate() {
+      let substitution = prev_row[j] + usize::from(a_chr != *b_chr);
+      row.push(substitution.min(prev_row[j + 1] + 1).min(row[j] + 1));
+    }
+    prev_row = row;
+  }
+  prev_row[b.len()]
+}
@@ -1,12 +1,14 @@
 use bend::{
   compile_book, desugar_book,
   diagnostics::{Diagnostics, DiagnosticsConfig, Severity},
-  fun::{load_book::do_parse_book_default, net_to_term::net_to_term, term_to_net::Labels, Book, Ctx, Name},
+  fun::{
+    load_book::do_parse_book_default, net_to_term::net_to_term, term_to_net::Labels, Book, Ctx, Name, Term,
+  },
   hvm::hvm_book_show_pretty,
   imports::DefaultLoader,
   load_to_book,
   net::hvm_to_net::hvm_to_net,
-  run_book, AdtEncoding, CompileOpts, RunOpts,
+  readback_hvm_net, run_book, AdtEncoding, CompileOpts, RunOpts,
 };
 use insta::assert_snapshot;
 use itertools::Itertools;
@@ -178,6 +180,35 @@ fn run_file() {
   )
 }
 
+#[test]
+fn reduce_file() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let _guard = RUN_MUTEX.lock().unwrap();
+    let mut book = do_parse_book_default(code, path)?;
+    let diagnostics_cfg = DiagnosticsConfig {
+      unused_definition: Severity::Allow,
+      ..DiagnosticsConfig::new(Severity::Error, true)
+    };
+    let compile_opts = CompileOpts::default();
+    let (ran, _, _) =
+      run_book(book.clone(), RunOpts::default(), compile_opts.clone(), diagnostics_cfg, None, "run")?
+        .unwrap();
+
+    desugar_book(&mut book, compile_opts, diagnostics_cfg, None)?;
+    let main = Term::Ref { nam: book.entrypoint.clone().unwrap() };
+    let mut reduced = main.reduce(&book, 100_000)?;
+    reduced.expand_generated(&book, &book.recursive_defs());
+    reduced.resugar_strings(AdtEncoding::NumScott);
+    reduced.resugar_lists(AdtEncoding::NumScott);
+
+    // The term level reduction must give the same result as running the compiled net.
+    if !reduced.alpha_eq(&ran) {
+      return Err(format!("Reduced to '{reduced}', but running gives '{ran}'.").into());
+    }
+    Ok(reduced.to_string())
+  })
+}
+
 #[test]
 fn import_system() {
   run_golden_test_dir_multiple(
@@ -232,7 +263,13 @@ fn readback_hvm() {
     let compat_net = hvm_to_net(&net);
     let mut diags = Diagnostics::default();
     let term = net_to_term(&compat_net, &book, &Labels::default(), false, &mut diags);
-    Ok(format!("{}{}", diags, term))
+    let (resugared, _) =
+      readback_hvm_net(&net, &book, &Labels::default(), &RunOpts::default(), AdtEncoding::NumScott);
+    if resugared == term {
+      Ok(format!("{}{}", diags, term))
+    } else {
+      Ok(format!("{}{}\nResugared:\n{}", diags, term, resugared))
+    }
   })
 }
 
@@ -250,6 +287,7 @@ fn simplify_matches() {
     ctx.set_entrypoint();
     ctx.book.encode_adts(AdtEncoding::NumScott);
     ctx.fix_match_defs()?;
+    ctx.desugar_guards()?;
     ctx.desugar_open()?;
     ctx.book.encode_builtins();
     ctx.resolve_refs()?;
@@ -300,6 +338,7 @@ fn encode_pattern_match() {
       ctx.set_entrypoint();
       ctx.book.encode_adts(adt_encoding);
       ctx.fix_match_defs()?;
+      ctx.desugar_guards()?;
       ctx.desugar_open()?;
       ctx.book.encode_builtins();
       ctx.resolve_refs()?;
@@ -373,7 +412,8 @@ fn compile_entrypoint() {
     let mut book = do_parse_book_default(code, path)?;
     book.entrypoint = Some(Name::new("foo"));
     let diagnostics_cfg = DiagnosticsConfig { ..DiagnosticsConfig::new(Severity::Error, true) };
-    let res = compile_book(&mut book, CompileOpts::default(), diagnostics_cfg, None)?;
+    let compile_opts = CompileOpts { prune: true, ..CompileOpts::default() };
+    let res = compile_book(&mut book, compile_opts, diagnostics_cfg, None)?;
     Ok(format!("{}{}", res.diagnostics, hvm_book_show_pretty(&res.hvm_book)))
   })
 }
@@ -465,7 +505,7 @@ fn examples() -> Result<(), Diagnostics> {
     .min_depth(1)
     .into_iter()
     .filter_map(|e| e.ok())
-    .filter(|e| e.path().extension().map_or(false, |ext| ext == "bend"))
+    .filter(|e| e.path().extension().is_some_and(|ext| ext == "bend"))
   {
     let _guard = RUN_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
     let path = entry.path();